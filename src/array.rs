@@ -0,0 +1,61 @@
+//! ndarray conversions, enabled by the `ndarray` feature.
+
+use ndarray::{ArrayD, ArrayViewD, IxDyn};
+
+use crate::{Error, OnnxElement, OnnxTensor};
+
+impl OnnxTensor {
+    /// Copy the tensor into an owned `ArrayD` with the tensor's shape
+    ///
+    /// `T` must match the tensor's data type, as with
+    /// [`to_vec`](Self::to_vec). Fails for shapes with unknown (`-1`) dims.
+    pub fn to_array<T: OnnxElement>(&self) -> Result<ArrayD<T>, Error> {
+        let shape = self.array_shape()?;
+        let values = if shape.iter().product::<usize>() == 0 {
+            Vec::new()
+        } else {
+            self.to_vec::<T>()?
+        };
+        ArrayD::from_shape_vec(IxDyn(&shape), values).map_err(|e| self.shape_error(e))
+    }
+
+    /// Borrow the tensor as an `ArrayViewD` without copying
+    ///
+    /// Only possible where [`as_slice`](Self::as_slice) is, e.g. aligned
+    /// raw_data or a typed field matching `T`. Fall back to
+    /// [`to_array`](Self::to_array) otherwise.
+    pub fn array_view<T: OnnxElement>(&self) -> Result<ArrayViewD<'_, T>, Error> {
+        let shape = self.array_shape()?;
+        let values = if shape.iter().product::<usize>() == 0 {
+            &[]
+        } else {
+            self.as_slice::<T>()?
+        };
+        ArrayViewD::from_shape(IxDyn(&shape), values).map_err(|e| self.shape_error(e))
+    }
+
+    /// Shape as array dimensions, rejecting unknown dims
+    fn array_shape(&self) -> Result<Vec<usize>, Error> {
+        self.shape()
+            .iter()
+            .map(|&d| {
+                usize::try_from(d).map_err(|_| {
+                    Error::DataConversion(format!(
+                        "Tensor '{}' has unknown dimension in shape {:?}",
+                        self.name(),
+                        self.shape()
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    fn shape_error(&self, e: ndarray::ShapeError) -> Error {
+        Error::DataConversion(format!(
+            "Tensor '{}' data does not match shape {:?}: {}",
+            self.name(),
+            self.shape(),
+            e
+        ))
+    }
+}
@@ -0,0 +1,148 @@
+//! Decoding of ONNX floating point element encodings into standard floats.
+//!
+//! All inputs are little-endian element bytes as laid out in `raw_data`.
+
+use crate::{DataType, Error, PackedElements, TensorData};
+
+/// Decode little-endian float elements of `data_type` into f64 values
+///
+/// `count` is the number of elements, needed for 4-bit types where the last
+/// byte may be padding.
+pub(crate) fn decode_floats(
+    data_type: DataType,
+    bytes: &[u8],
+    count: usize,
+) -> Result<Vec<f64>, Error> {
+    let bits = data_type
+        .bits_per_element()
+        .filter(|_| data_type.is_float())
+        .ok_or_else(|| {
+            Error::DataConversion(format!(
+                "Cannot decode {:?} tensor as floating point",
+                data_type
+            ))
+        })?;
+    let expected = (count * bits).div_ceil(8);
+    if bytes.len() != expected {
+        return Err(Error::DataConversion(format!(
+            "Data size {} does not match {} {:?} elements ({} bytes)",
+            bytes.len(),
+            count,
+            data_type,
+            expected
+        )));
+    }
+
+    let values = match data_type {
+        DataType::Float => bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+            .collect(),
+        DataType::Double => bytes
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes(b.try_into().expect("chunk of 8 bytes")))
+            .collect(),
+        DataType::Float16 => bytes
+            .chunks_exact(2)
+            .map(|b| f16_to_f64(u16::from_le_bytes([b[0], b[1]])))
+            .collect(),
+        DataType::Bfloat16 => bytes
+            .chunks_exact(2)
+            .map(|b| bf16_to_f64(u16::from_le_bytes([b[0], b[1]])))
+            .collect(),
+        DataType::Float8e4m3fn => bytes.iter().map(|&b| f8e4m3fn_to_f64(b)).collect(),
+        DataType::Float8e4m3fnuz => bytes.iter().map(|&b| f8e4m3fnuz_to_f64(b)).collect(),
+        DataType::Float8e5m2 => bytes.iter().map(|&b| f8e5m2_to_f64(b)).collect(),
+        DataType::Float8e5m2fnuz => bytes.iter().map(|&b| f8e5m2fnuz_to_f64(b)).collect(),
+        DataType::Float8e8m0 => bytes.iter().map(|&b| f8e8m0_to_f64(b)).collect(),
+        DataType::Float4e2m1 => PackedElements::new(TensorData::Numeric(bytes.into()), 4, count)
+            .map(f4e2m1_to_f64)
+            .collect(),
+        _ => unreachable!("non-float types are rejected above"),
+    };
+    Ok(values)
+}
+
+/// Decode a finite sign/exponent/mantissa encoding, including subnormals
+fn minifloat(bits: u32, exp_bits: u32, mant_bits: u32, bias: i32) -> f64 {
+    let sign = if (bits >> (exp_bits + mant_bits)) & 1 == 1 {
+        -1.0
+    } else {
+        1.0
+    };
+    let exp = ((bits >> mant_bits) & ((1 << exp_bits) - 1)) as i32;
+    let mant = (bits & ((1 << mant_bits) - 1)) as f64 / (1u32 << mant_bits) as f64;
+
+    if exp == 0 {
+        sign * mant * 2f64.powi(1 - bias)
+    } else {
+        sign * (1.0 + mant) * 2f64.powi(exp - bias)
+    }
+}
+
+/// IEEE 754 half precision
+fn f16_to_f64(bits: u16) -> f64 {
+    if bits & 0x7C00 == 0x7C00 {
+        return ieee_special(bits & 0x03FF != 0, bits & 0x8000 != 0);
+    }
+    minifloat(bits as u32, 5, 10, 15)
+}
+
+/// bfloat16 is the upper half of an f32
+fn bf16_to_f64(bits: u16) -> f64 {
+    f32::from_bits((bits as u32) << 16) as f64
+}
+
+/// E4M3FN: no infinities, NaN is S.1111.111
+fn f8e4m3fn_to_f64(bits: u8) -> f64 {
+    if bits & 0x7F == 0x7F {
+        return f64::NAN;
+    }
+    minifloat(bits as u32, 4, 3, 7)
+}
+
+/// E4M3FNUZ: no infinities or negative zero, NaN is 0x80
+fn f8e4m3fnuz_to_f64(bits: u8) -> f64 {
+    if bits == 0x80 {
+        return f64::NAN;
+    }
+    minifloat(bits as u32, 4, 3, 8)
+}
+
+/// E5M2: IEEE 754 semantics with infinities and NaNs
+fn f8e5m2_to_f64(bits: u8) -> f64 {
+    if bits & 0x7C == 0x7C {
+        return ieee_special(bits & 0x03 != 0, bits & 0x80 != 0);
+    }
+    minifloat(bits as u32, 5, 2, 15)
+}
+
+/// E5M2FNUZ: no infinities or negative zero, NaN is 0x80
+fn f8e5m2fnuz_to_f64(bits: u8) -> f64 {
+    if bits == 0x80 {
+        return f64::NAN;
+    }
+    minifloat(bits as u32, 5, 2, 16)
+}
+
+/// E8M0: unsigned power of two scale, NaN is 0xFF
+fn f8e8m0_to_f64(bits: u8) -> f64 {
+    if bits == 0xFF {
+        return f64::NAN;
+    }
+    2f64.powi(bits as i32 - 127)
+}
+
+/// E2M1: no infinities or NaNs
+pub(crate) fn f4e2m1_to_f64(bits: u8) -> f64 {
+    minifloat(bits as u32, 2, 1, 1)
+}
+
+/// Value for an all-ones exponent in IEEE-style formats
+fn ieee_special(is_nan: bool, negative: bool) -> f64 {
+    match (is_nan, negative) {
+        (true, _) => f64::NAN,
+        (false, true) => f64::NEG_INFINITY,
+        (false, false) => f64::INFINITY,
+    }
+}
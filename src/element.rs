@@ -0,0 +1,73 @@
+//! Rust element types that tensor data can be decoded into.
+
+use crate::DataType;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A Rust type matching the element layout of an ONNX [`DataType`]
+///
+/// Implemented for the primitive integer and float types and `bool`. Types
+/// without a native Rust equivalent, such as float16 or the FP8 formats, are
+/// decoded with [`OnnxTensor::to_f32_vec`](crate::OnnxTensor::to_f32_vec).
+///
+/// This trait is sealed, which keeps zero-copy reinterpretation sound.
+pub trait OnnxElement: Copy + sealed::Sealed + 'static {
+    /// The ONNX data type stored as this Rust type
+    const DATA_TYPE: DataType;
+
+    /// Decode one element from little-endian bytes, None for invalid bit patterns
+    #[doc(hidden)]
+    fn from_le_bytes(bytes: &[u8]) -> Option<Self>;
+
+    /// Whether every bit pattern is a valid value of this type
+    #[doc(hidden)]
+    const ANY_BIT_PATTERN: bool = true;
+}
+
+macro_rules! impl_onnx_element {
+    ($($ty:ty => $data_type:ident),* $(,)?) => {
+        $(
+            impl sealed::Sealed for $ty {}
+
+            impl OnnxElement for $ty {
+                const DATA_TYPE: DataType = DataType::$data_type;
+
+                #[inline]
+                fn from_le_bytes(bytes: &[u8]) -> Option<Self> {
+                    Some(<$ty>::from_le_bytes(bytes.try_into().ok()?))
+                }
+            }
+        )*
+    };
+}
+
+impl_onnx_element!(
+    f32 => Float,
+    f64 => Double,
+    i8 => Int8,
+    i16 => Int16,
+    i32 => Int32,
+    i64 => Int64,
+    u8 => Uint8,
+    u16 => Uint16,
+    u32 => Uint32,
+    u64 => Uint64,
+);
+
+impl sealed::Sealed for bool {}
+
+impl OnnxElement for bool {
+    const DATA_TYPE: DataType = DataType::Bool;
+    const ANY_BIT_PATTERN: bool = false;
+
+    #[inline]
+    fn from_le_bytes(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0] => Some(false),
+            [1] => Some(true),
+            _ => None,
+        }
+    }
+}
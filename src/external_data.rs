@@ -1,27 +1,44 @@
 use prost::bytes::Bytes;
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::io::Read;
+use std::io::{Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
-use std::rc::Rc;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 
 use crate::{Error, StringStringEntryProto};
 
+/// How external tensor data is cached after it is read
+///
+/// Ignored for memory-mapped loading, where whole files are always mapped
+/// since mapping does not read pages until they are touched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CachePolicy {
+    /// Read each tensor's byte range on every access and cache nothing
+    None,
+    /// Read each tensor's byte range once and keep it for later accesses
+    PerTensor,
+    /// Read the whole external file on first access and slice tensors from it
+    #[default]
+    WholeFile,
+    /// Cache tensor byte ranges, evicting the least recently used ones once
+    /// the cached total exceeds `max_bytes`
+    Lru { max_bytes: usize },
+}
+
 /// Metadata for external tensor data
 #[derive(Debug, Clone)]
 pub(crate) struct ExternalDataInfo {
     pub location: String,
     pub offset: Option<u64>,
     pub length: Option<u64>,
-    pub loader: Rc<ExternalDataLoader>,
+    pub loader: Arc<ExternalDataLoader>,
 }
 
 impl ExternalDataInfo {
     /// Parse external data info from key-value pairs
     pub fn from_key_value_pairs(
         pairs: &[StringStringEntryProto],
-        loader: Rc<ExternalDataLoader>,
+        loader: Arc<ExternalDataLoader>,
     ) -> Result<Self, Error> {
         let mut location: Option<String> = None;
         let mut offset: Option<u64> = None;
@@ -61,74 +78,211 @@ impl ExternalDataInfo {
     }
 }
 
+/// Cache slot for one external file, filled on first access
+type FileSlot = Arc<Mutex<Option<Bytes>>>;
+
+/// Identifies a tensor's byte range within an external file
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RangeKey {
+    location: String,
+    offset: u64,
+    length: Option<u64>,
+}
+
+/// Cached tensor byte ranges with least-recently-used eviction
+///
+/// Each entry carries the tick of its last use, and `by_use` maps ticks back
+/// to keys so the oldest entry is found without scanning the cache.
+#[derive(Default)]
+struct RangeCache {
+    entries: HashMap<RangeKey, (Bytes, u64)>,
+    by_use: BTreeMap<u64, RangeKey>,
+    total_bytes: usize,
+    tick: u64,
+}
+
+impl RangeCache {
+    fn get(&mut self, key: &RangeKey) -> Option<Bytes> {
+        let (data, last_used) = self.entries.get_mut(key)?;
+        self.tick += 1;
+        self.by_use.remove(last_used);
+        self.by_use.insert(self.tick, key.clone());
+        *last_used = self.tick;
+        Some(data.clone())
+    }
+
+    fn insert(&mut self, key: RangeKey, data: Bytes, max_bytes: usize) {
+        if data.len() > max_bytes {
+            return;
+        }
+        self.tick += 1;
+        self.total_bytes += data.len();
+        self.by_use.insert(self.tick, key.clone());
+        if let Some((old, last_used)) = self.entries.insert(key, (data, self.tick)) {
+            self.total_bytes -= old.len();
+            self.by_use.remove(&last_used);
+        }
+
+        while self.total_bytes > max_bytes {
+            let Some((_, oldest)) = self.by_use.pop_first() else {
+                break;
+            };
+            if let Some((evicted, _)) = self.entries.remove(&oldest) {
+                self.total_bytes -= evicted.len();
+            }
+        }
+    }
+}
+
 /// Manages lazy loading and caching of external tensor data files
+///
+/// The loader is shared between tensors via `Arc` and is safe to use from
+/// multiple threads. With [`CachePolicy::WholeFile`] each file has its own
+/// cache slot, so different files can be loaded concurrently while each
+/// file is still only read once. Other policies read only the byte range a
+/// tensor needs.
 pub(crate) struct ExternalDataLoader {
     model_dir: PathBuf,
-    cache: RefCell<HashMap<String, Bytes>>,
+    mmap: bool,
+    cache_policy: CachePolicy,
+    cache: Mutex<HashMap<String, FileSlot>>,
+    ranges: Mutex<RangeCache>,
 }
 
 impl ExternalDataLoader {
     /// Create a new external data loader for a given model directory
-    pub(crate) fn new(model_dir: PathBuf) -> Self {
+    ///
+    /// With `mmap` set, external files are memory-mapped instead of read.
+    pub(crate) fn new(model_dir: PathBuf, mmap: bool, cache_policy: CachePolicy) -> Self {
         ExternalDataLoader {
             model_dir,
-            cache: RefCell::new(HashMap::new()),
+            mmap,
+            cache_policy,
+            cache: Mutex::new(HashMap::new()),
+            ranges: Mutex::new(RangeCache::default()),
         }
     }
 
     /// Load tensor data from external file with optional offset and length
     ///
-    /// This method lazily loads the entire external file into the cache on first access,
-    /// then returns a slice of the cached data based on offset and length.
+    /// Dispatches on the cache policy: whole files are loaded and sliced,
+    /// otherwise only the tensor's range is read.
     pub(crate) fn load_data(&self, info: &ExternalDataInfo) -> Result<Bytes, Error> {
-        let file_path = self.model_dir.join(&info.location);
+        // mapping is lazy, so mapped files are always handled whole
+        if self.mmap {
+            return self.load_from_whole_file(info);
+        }
 
-        {
-            let cache = self.cache.borrow();
-            if let Some(cached_data) = cache.get(&info.location) {
-                // File is cached, return the requested slice
-                return self.slice_data(cached_data, info);
-            }
+        let max_bytes = match self.cache_policy {
+            CachePolicy::WholeFile => return self.load_from_whole_file(info),
+            CachePolicy::None => return self.read_range(info),
+            CachePolicy::PerTensor => usize::MAX,
+            CachePolicy::Lru { max_bytes } => max_bytes,
+        };
+
+        let key = RangeKey {
+            location: info.location.clone(),
+            offset: info.offset.unwrap_or(0),
+            length: info.length,
+        };
+        if let Some(cached) = lock(&self.ranges).get(&key) {
+            return Ok(cached);
         }
 
-        // File not cached
-        let file_data = self.load_file(&file_path)?;
+        // read outside the lock so other tensors are not blocked on I/O
+        let data = self.read_range(info)?;
+        lock(&self.ranges).insert(key, data.clone(), max_bytes);
+        Ok(data)
+    }
+
+    /// Load tensor data by slicing the cached whole external file
+    ///
+    /// This method lazily loads the entire external file into the cache on first access,
+    /// then returns a slice of the cached data based on offset and length.
+    fn load_from_whole_file(&self, info: &ExternalDataInfo) -> Result<Bytes, Error> {
+        // hold the map lock only long enough to find this file's slot
+        let slot = lock(&self.cache)
+            .entry(info.location.clone())
+            .or_default()
+            .clone();
 
-        let slice = self.slice_data(&file_data, info)?;
+        // concurrent loads of the same file wait here, other files proceed
+        let mut file_data = lock(&slot);
+        if let Some(cached_data) = file_data.as_ref() {
+            // File is cached, return the requested slice
+            return self.slice_data(cached_data, info);
+        }
 
-        // Cache the entire file
-        let mut cache = self.cache.borrow_mut();
-        cache.insert(info.location.clone(), file_data);
+        // File not cached, load and cache the entire file
+        let file_path = self.model_dir.join(&info.location);
+        let loaded = file_data.insert(self.load_file(&file_path)?);
 
-        Ok(slice)
+        self.slice_data(loaded, info)
     }
 
-    /// Load entire file into memory as Bytes
-    pub(crate) fn load_file(&self, path: &Path) -> Result<Bytes, Error> {
-        let mut file = File::open(path).map_err(|e| {
+    /// Number of files currently held in the cache
+    ///
+    /// Slots that are still being loaded by another thread are not counted.
+    fn cached_file_count(&self) -> usize {
+        lock(&self.cache)
+            .values()
+            .filter(|slot| matches!(slot.try_lock().as_deref(), Ok(Some(_))))
+            .count()
+    }
+
+    /// Read exactly the tensor's byte range from its external file
+    fn read_range(&self, info: &ExternalDataInfo) -> Result<Bytes, Error> {
+        let path = self.model_dir.join(&info.location);
+        let context = |e: std::io::Error| {
             Error::Io(std::io::Error::new(
                 e.kind(),
                 format!(
-                    "Failed to open external data file '{}': {}",
+                    "Failed to read external data file '{}': {}",
                     path.display(),
                     e
                 ),
             ))
-        })?;
+        };
+
+        let mut file = File::open(&path).map_err(context)?;
+        let file_len = file.metadata().map_err(context)?.len();
+
+        let start = info.offset.unwrap_or(0);
+        let end = match info.length {
+            Some(len) => start.saturating_add(len),
+            None => file_len,
+        };
+        if start > file_len {
+            return Err(Error::InvalidModel(format!(
+                "External data offset {} exceeds file size {}",
+                start, file_len
+            )));
+        }
+        if end > file_len {
+            return Err(Error::InvalidModel(format!(
+                "External data range {}..{} exceeds file size {}",
+                start, end, file_len
+            )));
+        }
+
+        let mut buffer = vec![0u8; usize::try_from(end - start)?];
+        file.seek(SeekFrom::Start(start)).map_err(context)?;
+        file.read_exact(&mut buffer).map_err(context)?;
+        Ok(Bytes::from(buffer))
+    }
 
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer).map_err(|e| {
+    /// Load entire file as Bytes, reading or memory-mapping it
+    pub(crate) fn load_file(&self, path: &Path) -> Result<Bytes, Error> {
+        read_file(path, self.mmap).map_err(|e| {
             Error::Io(std::io::Error::new(
                 e.kind(),
                 format!(
-                    "Failed to read external data file '{}': {}",
+                    "Failed to load external data file '{}': {}",
                     path.display(),
                     e
                 ),
             ))
-        })?;
-
-        Ok(Bytes::from(buffer))
+        })
     }
 
     /// Extract a slice of data based on offset and length
@@ -165,7 +319,49 @@ impl std::fmt::Debug for ExternalDataLoader {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ExternalDataLoader")
             .field("model_dir", &self.model_dir)
-            .field("cached_files", &self.cache.borrow().len())
+            .field("mmap", &self.mmap)
+            .field("cache_policy", &self.cache_policy)
+            .field("cached_files", &self.cached_file_count())
+            .field("cached_ranges", &lock(&self.ranges).entries.len())
             .finish()
     }
 }
+
+/// Lock a mutex, recovering the data if another thread panicked while holding it
+///
+/// The cache only ever holds fully loaded files, so a poisoned lock does not
+/// leave it in an inconsistent state.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Read a whole file into Bytes, or memory-map it when `mmap` is set
+///
+/// Mapped Bytes keep the mapping alive, so slices of it stay zero-copy.
+pub(crate) fn read_file(path: &Path, mmap: bool) -> std::io::Result<Bytes> {
+    let mut file = File::open(path)?;
+
+    if mmap {
+        return map_file(&file);
+    }
+
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+    Ok(Bytes::from(buffer))
+}
+
+#[cfg(feature = "mmap")]
+fn map_file(file: &File) -> std::io::Result<Bytes> {
+    // SAFETY: the mapping is read-only. As documented on `LoadOptions::mmap`,
+    // callers must not modify or truncate mapped files while the model is alive.
+    let map = unsafe { memmap2::Mmap::map(file)? };
+    Ok(Bytes::from_owner(map))
+}
+
+#[cfg(not(feature = "mmap"))]
+fn map_file(_file: &File) -> std::io::Result<Bytes> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "memory-mapped loading requires the `mmap` feature",
+    ))
+}
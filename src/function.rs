@@ -0,0 +1,362 @@
+//! Model-local functions and inlining them into the main graph.
+//!
+//! A node calls a function when its `domain`, `op_type` and `overload` match
+//! the function's `domain`, `name` and `overload`. Inlining replaces each
+//! call with a copy of the function body: formal inputs and outputs are
+//! renamed to the caller's values, every other value and node name gets a
+//! unique prefix, and attributes referring to the caller (`ref_attr_name`)
+//! are substituted.
+
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+use crate::operation::is_default_domain;
+use crate::{AttributeValue, Error, OnnxGraph, OnnxModel, OnnxOperation, OpsetImport};
+
+/// Calls nested deeper than this are reported as recursive
+const MAX_INLINE_DEPTH: usize = 64;
+
+/// A function defined in the model's `functions` list
+#[derive(Debug, Clone)]
+pub struct OnnxFunction {
+    pub name: String,
+    pub domain: String,
+    /// Distinguishes functions sharing a domain and name, empty if unused
+    pub overload: String,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    /// Attribute parameters without a default value
+    pub attributes: Vec<String>,
+    /// Attribute parameters with their default values
+    pub attribute_defaults: HashMap<String, AttributeValue>,
+    /// Body nodes, in the order they appear in the function
+    pub operations: Vec<OnnxOperation>,
+    pub opset_import: Vec<OpsetImport>,
+    pub doc_string: String,
+}
+
+impl OnnxFunction {
+    /// Check if an operation is a call to this function
+    ///
+    /// Domain, name and overload must all match. The empty domain and
+    /// `ai.onnx` are treated as the same default domain.
+    pub fn is_called_by(&self, op: &OnnxOperation) -> bool {
+        op.op_type == self.name
+            && op.overload == self.overload
+            && same_domain(&op.domain, &self.domain)
+    }
+
+    /// Names of all attribute parameters, with and without defaults, sorted
+    pub fn attribute_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.attributes.iter().map(String::as_str).collect();
+        names.extend(self.attribute_defaults.keys().map(String::as_str));
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+}
+
+/// Expand every function call in the main graph and its subgraphs
+pub(crate) fn inline_model(model: &mut OnnxModel) -> Result<usize, Error> {
+    if model.functions.is_empty() {
+        return Ok(0);
+    }
+
+    let mut used: HashSet<String> = HashSet::new();
+    used.extend(model.tensors.keys().cloned());
+    used.extend(model.sparse_tensors.keys().cloned());
+    used.extend(model.inputs.iter().cloned());
+    used.extend(model.outputs.iter().cloned());
+    collect_names(&model.operations, &mut used);
+
+    let mut inliner = Inliner {
+        functions: &model.functions,
+        used,
+        opsets: model.opset_import.clone(),
+        count: 0,
+    };
+    // expand a copy so the model is left untouched on error
+    let operations = inliner.expand(model.operations.clone(), 0)?;
+    model.operations = operations;
+    model.opset_import = inliner.opsets;
+    Ok(inliner.count)
+}
+
+struct Inliner<'f> {
+    functions: &'f [OnnxFunction],
+    /// Value and node names already taken anywhere in the model
+    used: HashSet<String>,
+    /// Opsets imported by the model followed by those added by inlined functions
+    opsets: Vec<OpsetImport>,
+    count: usize,
+}
+
+impl<'f> Inliner<'f> {
+    fn expand(
+        &mut self,
+        operations: Vec<OnnxOperation>,
+        depth: usize,
+    ) -> Result<Vec<OnnxOperation>, Error> {
+        let mut expanded = Vec::with_capacity(operations.len());
+        for mut op in operations {
+            for graph in subgraphs_mut(&mut op) {
+                let ops = mem::take(&mut graph.operations);
+                graph.operations = self.expand(ops, depth)?;
+            }
+
+            let Some(function) = self.functions.iter().find(|f| f.is_called_by(&op)) else {
+                expanded.push(op);
+                continue;
+            };
+            if depth >= MAX_INLINE_DEPTH {
+                return Err(Error::InvalidModel(format!(
+                    "function '{}' is nested more than {} calls deep, it may be recursive",
+                    function.name, MAX_INLINE_DEPTH
+                )));
+            }
+
+            let body = self.instantiate(function, &op)?;
+            self.count += 1;
+            for opset in &function.opset_import {
+                self.import_opset(function, opset)?;
+            }
+            expanded.extend(self.expand(body, depth + 1)?);
+        }
+        Ok(expanded)
+    }
+
+    /// Add a function's opset import, which must agree with earlier imports
+    ///
+    /// Body nodes keep their op types, so a different version of the same
+    /// domain would change what they mean once inlined.
+    fn import_opset(&mut self, function: &OnnxFunction, opset: &OpsetImport) -> Result<(), Error> {
+        match self
+            .opsets
+            .iter()
+            .find(|o| same_domain(&o.domain, &opset.domain))
+        {
+            Some(imported) if imported.version != opset.version => {
+                Err(Error::InvalidModel(format!(
+                    "function '{}' imports domain '{}' version {}, the model uses version {}",
+                    function.name, opset.domain, opset.version, imported.version
+                )))
+            }
+            Some(_) => Ok(()),
+            None => {
+                self.opsets.push(opset.clone());
+                Ok(())
+            }
+        }
+    }
+
+    /// Copy a function body with names and attributes bound to one call
+    fn instantiate(
+        &mut self,
+        function: &OnnxFunction,
+        call: &OnnxOperation,
+    ) -> Result<Vec<OnnxOperation>, Error> {
+        if call.inputs.len() > function.inputs.len() || call.outputs.len() > function.outputs.len()
+        {
+            return Err(Error::InvalidModel(format!(
+                "node '{}' calls function '{}' with {} inputs and {} outputs, it takes {} and {}",
+                call.name,
+                function.name,
+                call.inputs.len(),
+                call.outputs.len(),
+                function.inputs.len(),
+                function.outputs.len()
+            )));
+        }
+
+        // formal parameters bind to the caller, omitted ones to "" or a local name
+        let mut renames: HashMap<String, String> = HashMap::new();
+        for (i, formal) in function.inputs.iter().enumerate() {
+            let actual = call.inputs.get(i).cloned().unwrap_or_default();
+            renames.insert(formal.clone(), actual);
+        }
+        let mut bound: HashSet<&str> = function.inputs.iter().map(String::as_str).collect();
+        for (formal, actual) in function.outputs.iter().zip(&call.outputs) {
+            renames.insert(formal.clone(), actual.clone());
+            bound.insert(formal);
+        }
+
+        let mut locals: HashSet<String> = HashSet::new();
+        collect_names(&function.operations, &mut locals);
+        locals.retain(|name| !name.is_empty() && !bound.contains(name.as_str()));
+
+        let prefix = self.unique_prefix(call, function, &locals);
+        for name in locals {
+            let mangled = format!("{}/{}", prefix, name);
+            self.used.insert(mangled.clone());
+            renames.insert(name, mangled);
+        }
+
+        let mut body = function.operations.clone();
+        for op in &mut body {
+            prefix_node_names(op, &prefix, &mut self.used);
+            bind_attributes(op, call, function);
+            rename_operation(op, &renames);
+        }
+        Ok(body)
+    }
+
+    /// Pick a prefix whose mangled names do not clash with existing names
+    fn unique_prefix(
+        &self,
+        call: &OnnxOperation,
+        function: &OnnxFunction,
+        locals: &HashSet<String>,
+    ) -> String {
+        let base = if call.name.is_empty() {
+            &function.name
+        } else {
+            &call.name
+        };
+        let clashes = |prefix: &str| {
+            let taken = |name: &str| self.used.contains(&format!("{}/{}", prefix, name));
+            locals.iter().any(|n| taken(n))
+                || function
+                    .operations
+                    .iter()
+                    .any(|op| !op.name.is_empty() && taken(&op.name))
+        };
+
+        let mut prefix = base.clone();
+        let mut suffix = 1;
+        while clashes(&prefix) {
+            prefix = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        prefix
+    }
+}
+
+/// Replace attribute references with the caller's values or the defaults
+///
+/// References with neither are dropped, as if the attribute were not set.
+/// Subgraphs of the body node are handled too.
+fn bind_attributes(op: &mut OnnxOperation, call: &OnnxOperation, function: &OnnxFunction) {
+    op.attributes.retain(|_, value| {
+        let Some(name) = value.as_ref_name() else {
+            return true;
+        };
+        match call
+            .attributes
+            .get(name)
+            .or_else(|| function.attribute_defaults.get(name))
+        {
+            Some(bound) => {
+                *value = bound.clone();
+                true
+            }
+            None => false,
+        }
+    });
+
+    for graph in subgraphs_mut(op) {
+        for sub_op in &mut graph.operations {
+            bind_attributes(sub_op, call, function);
+        }
+    }
+}
+
+/// Prefix node names, recursing into subgraphs
+fn prefix_node_names(op: &mut OnnxOperation, prefix: &str, used: &mut HashSet<String>) {
+    if !op.name.is_empty() {
+        op.name = format!("{}/{}", prefix, op.name);
+        used.insert(op.name.clone());
+    }
+    for graph in subgraphs_mut(op) {
+        for sub_op in &mut graph.operations {
+            prefix_node_names(sub_op, prefix, used);
+        }
+    }
+}
+
+/// Rename node inputs and outputs, recursing into subgraphs
+fn rename_operation(op: &mut OnnxOperation, renames: &HashMap<String, String>) {
+    let rename = |name: &mut String| {
+        if let Some(new_name) = renames.get(name.as_str()) {
+            *name = new_name.clone();
+        }
+    };
+    op.inputs.iter_mut().for_each(rename);
+    op.outputs.iter_mut().for_each(rename);
+
+    for graph in subgraphs_mut(op) {
+        graph.inputs.iter_mut().for_each(rename);
+        graph.outputs.iter_mut().for_each(rename);
+        graph.initializer_inputs.iter_mut().for_each(rename);
+        graph.tensors = mem::take(&mut graph.tensors)
+            .into_iter()
+            .map(|(name, mut tensor)| match renames.get(&name) {
+                Some(new_name) => {
+                    tensor.set_name(new_name.clone());
+                    (new_name.clone(), tensor)
+                }
+                None => (name, tensor),
+            })
+            .collect();
+        graph.quantization_annotation = mem::take(&mut graph.quantization_annotation)
+            .into_iter()
+            .map(|(name, mut params)| {
+                params.values_mut().for_each(rename);
+                (renames.get(&name).cloned().unwrap_or(name), params)
+            })
+            .collect();
+        graph.value_types = mem::take(&mut graph.value_types)
+            .into_iter()
+            .map(|(name, value_type)| match renames.get(&name) {
+                Some(new_name) => (new_name.clone(), value_type),
+                None => (name, value_type),
+            })
+            .collect();
+        graph.sparse_tensors = mem::take(&mut graph.sparse_tensors)
+            .into_iter()
+            .map(|(name, mut tensor)| match renames.get(&name) {
+                Some(new_name) => {
+                    tensor.set_name(new_name.clone());
+                    (new_name.clone(), tensor)
+                }
+                None => (name, tensor),
+            })
+            .collect();
+        for sub_op in &mut graph.operations {
+            rename_operation(sub_op, renames);
+        }
+    }
+}
+
+/// Collect value and node names used by operations and their subgraphs
+fn collect_names(operations: &[OnnxOperation], names: &mut HashSet<String>) {
+    for op in operations {
+        if !op.name.is_empty() {
+            names.insert(op.name.clone());
+        }
+        names.extend(op.inputs.iter().cloned());
+        names.extend(op.outputs.iter().cloned());
+        for (_, graph) in op.subgraphs() {
+            names.extend(graph.inputs.iter().cloned());
+            names.extend(graph.outputs.iter().cloned());
+            names.extend(graph.tensors.keys().cloned());
+            names.extend(graph.sparse_tensors.keys().cloned());
+            collect_names(&graph.operations, names);
+        }
+    }
+}
+
+fn subgraphs_mut(op: &mut OnnxOperation) -> Vec<&mut OnnxGraph> {
+    let mut graphs = Vec::new();
+    for value in op.attributes.values_mut() {
+        match value {
+            AttributeValue::Graph(g) => graphs.push(g.as_mut()),
+            AttributeValue::Graphs(gs) => graphs.extend(gs.iter_mut()),
+            _ => {}
+        }
+    }
+    graphs
+}
+
+fn same_domain(a: &str, b: &str) -> bool {
+    a == b || is_default_domain(a) && is_default_domain(b)
+}
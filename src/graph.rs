@@ -0,0 +1,537 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::quantization::{QuantParams, SCALE_TENSOR, ZERO_POINT_TENSOR};
+use crate::{Dim, OnnxOperation, OnnxSparseTensor, OnnxTensor, ValueType};
+
+/// A nested ONNX graph held by a GRAPH or GRAPHS attribute
+///
+/// Control-flow operations such as `If`, `Loop` and `Scan` carry their bodies
+/// as subgraphs. Subgraph nodes may reference values defined in any enclosing
+/// graph, see [`outer_scope_names`](Self::outer_scope_names) and
+/// [`GraphScope`] for resolving them.
+#[derive(Debug, Clone)]
+pub struct OnnxGraph {
+    pub name: String,
+    pub tensors: HashMap<String, OnnxTensor>,
+    pub sparse_tensors: HashMap<String, OnnxSparseTensor>,
+    pub operations: Vec<OnnxOperation>,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    /// Initializers also listed as graph inputs, so callers may override
+    /// them; `inputs` leaves these out
+    pub initializer_inputs: Vec<String>,
+    /// Declared types of values that are not dense tensors, such as
+    /// sequences, maps, optionals and sparse tensors
+    pub value_types: HashMap<String, ValueType>,
+    /// Tensor name to annotation keys such as `SCALE_TENSOR`, each naming
+    /// a parameter tensor
+    pub quantization_annotation: HashMap<String, HashMap<String, String>>,
+}
+
+impl OnnxGraph {
+    /// Get tensor defined in this graph by name
+    pub fn get_tensor(&self, name: &str) -> Option<&OnnxTensor> {
+        self.tensors.get(name)
+    }
+
+    /// Get the type of a named value in this graph
+    ///
+    /// See [`OnnxModel::value_type`](crate::OnnxModel::value_type).
+    pub fn value_type(&self, name: &str) -> Option<ValueType> {
+        GraphParts::from(self).value_type(name)
+    }
+
+    /// Get quantization parameters annotated on a tensor in this graph
+    ///
+    /// See [`OnnxModel::quant_params`](crate::OnnxModel::quant_params).
+    pub fn quant_params(&self, name: &str) -> Option<QuantParams<'_>> {
+        GraphParts::from(self).quant_params(name)
+    }
+
+    /// Names consumed by this graph that are not defined inside it
+    ///
+    /// A name is defined locally if it is a graph input, a dense or sparse
+    /// initializer or an operation output. References from nested subgraphs
+    /// are included when this graph does not define them either. The result
+    /// is sorted.
+    pub fn outer_scope_names(&self) -> Vec<&str> {
+        let mut defined: HashSet<&str> = self.inputs.iter().map(String::as_str).collect();
+        defined.extend(self.sparse_tensors.keys().map(String::as_str));
+        for (name, tensor) in &self.tensors {
+            if tensor.data_location().is_some() {
+                defined.insert(name);
+            }
+        }
+        for op in &self.operations {
+            defined.extend(op.outputs.iter().map(String::as_str));
+        }
+
+        let mut outer: HashSet<&str> = HashSet::new();
+        for op in &self.operations {
+            for input in &op.inputs {
+                if !input.is_empty() && !defined.contains(input.as_str()) {
+                    outer.insert(input);
+                }
+            }
+            for (_, subgraph) in op.subgraphs() {
+                for name in subgraph.outer_scope_names() {
+                    if !defined.contains(name) {
+                        outer.insert(name);
+                    }
+                }
+            }
+        }
+
+        let mut names: Vec<&str> = outer.into_iter().collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Get operation by id
+    pub fn operation(&self, id: OpId) -> Option<&OnnxOperation> {
+        self.operations.get(id.index())
+    }
+
+    /// Build an id and producer/consumer index over this graph
+    pub fn graph_index(&self) -> GraphIndex<'_> {
+        GraphIndex::new(&GraphParts::from(self))
+    }
+}
+
+/// Borrowed view of the graph fields shared by OnnxModel and OnnxGraph
+pub(crate) struct GraphParts<'a> {
+    pub name: &'a str,
+    pub tensors: &'a HashMap<String, OnnxTensor>,
+    pub sparse_tensors: &'a HashMap<String, OnnxSparseTensor>,
+    pub operations: &'a [OnnxOperation],
+    pub inputs: &'a [String],
+    pub outputs: &'a [String],
+    pub initializer_inputs: &'a [String],
+    pub value_types: &'a HashMap<String, ValueType>,
+    pub quantization_annotation: &'a HashMap<String, HashMap<String, String>>,
+}
+
+impl<'a> GraphParts<'a> {
+    /// Declared type first, then the type of a dense or sparse tensor
+    pub fn value_type(&self, name: &str) -> Option<ValueType> {
+        if let Some(value_type) = self.value_types.get(name) {
+            return Some(value_type.clone());
+        }
+        if let Some(tensor) = self.tensors.get(name) {
+            return Some(ValueType::Tensor {
+                data_type: tensor.data_type(),
+                shape: Some(tensor.dims().to_vec()),
+            });
+        }
+        let sparse = self.sparse_tensors.get(name)?;
+        Some(ValueType::SparseTensor {
+            data_type: sparse.data_type(),
+            shape: Some(sparse.shape().iter().map(|&d| Dim::Fixed(d)).collect()),
+        })
+    }
+
+    /// Resolve a tensor's annotation to its scale and zero-point initializers
+    pub fn quant_params(&self, name: &str) -> Option<QuantParams<'a>> {
+        let tensors = self.tensors;
+        let initializer = |name: &String| tensors.get(name).filter(|t| t.data_location().is_some());
+        let annotation = self.quantization_annotation.get(name)?;
+        let scale = initializer(annotation.get(SCALE_TENSOR)?)?;
+        let zero_point = match annotation.get(ZERO_POINT_TENSOR) {
+            Some(zero_point) => Some(initializer(zero_point)?),
+            None => None,
+        };
+        Some(QuantParams { scale, zero_point })
+    }
+}
+
+impl<'a> From<&'a OnnxGraph> for GraphParts<'a> {
+    fn from(graph: &'a OnnxGraph) -> Self {
+        GraphParts {
+            name: &graph.name,
+            tensors: &graph.tensors,
+            sparse_tensors: &graph.sparse_tensors,
+            operations: &graph.operations,
+            inputs: &graph.inputs,
+            outputs: &graph.outputs,
+            initializer_inputs: &graph.initializer_inputs,
+            value_types: &graph.value_types,
+            quantization_annotation: &graph.quantization_annotation,
+        }
+    }
+}
+
+/// A chain of graph scopes used to resolve names from inner to outer graphs
+///
+/// Start from [`OnnxModel::scope`](crate::OnnxModel::scope) and call
+/// [`enter`](Self::enter) for each subgraph while walking down.
+#[derive(Debug, Clone, Copy)]
+pub struct GraphScope<'a> {
+    tensors: &'a HashMap<String, OnnxTensor>,
+    parent: Option<&'a GraphScope<'a>>,
+}
+
+impl<'a> GraphScope<'a> {
+    pub(crate) fn new(tensors: &'a HashMap<String, OnnxTensor>) -> Self {
+        GraphScope {
+            tensors,
+            parent: None,
+        }
+    }
+
+    /// Create a scope for a subgraph nested inside this one
+    pub fn enter(&'a self, graph: &'a OnnxGraph) -> GraphScope<'a> {
+        GraphScope {
+            tensors: &graph.tensors,
+            parent: Some(self),
+        }
+    }
+
+    /// Resolve a tensor by name, searching the innermost graph first
+    pub fn get_tensor(&self, name: &str) -> Option<&'a OnnxTensor> {
+        self.tensors
+            .get(name)
+            .or_else(|| self.parent.and_then(|p| p.get_tensor(name)))
+    }
+
+    /// Number of enclosing scopes, 0 for the main graph
+    pub fn depth(&self) -> usize {
+        self.parent.map_or(0, |p| p.depth() + 1)
+    }
+}
+
+/// Identifier of an operation, its position in the graph's operation list
+///
+/// Unlike node names ids are always present and unique. They are positions
+/// at the time the [`GraphIndex`] was built, so any edit of the operation
+/// list, including [`OnnxModel::inline_functions`](crate::OnnxModel::inline_functions),
+/// invalidates them. Build a new index after an edit instead of reusing ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OpId(usize);
+
+impl OpId {
+    /// Position in the graph's operation list
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// Identifier of a tensor name referenced by a graph
+///
+/// Ids are numbered deterministically: graph inputs first, then initializers
+/// by name, then operation outputs in order, then any remaining names read
+/// from enclosing graphs. The same unmodified graph always yields the same
+/// ids, but they are only meaningful to the [`GraphIndex`] that assigned them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TensorId(usize);
+
+impl TensorId {
+    /// Position in the index's tensor name table
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// Id-based lookups and producer/consumer queries over one graph
+///
+/// Built by [`OnnxModel::graph_index`](crate::OnnxModel::graph_index) or
+/// [`OnnxGraph::graph_index`] and borrowed from the graph, so build it once
+/// and reuse it for many queries.
+#[derive(Debug, Clone)]
+pub struct GraphIndex<'a> {
+    operations: &'a [OnnxOperation],
+    op_names: HashMap<&'a str, Vec<OpId>>,
+    tensor_names: Vec<&'a str>,
+    tensor_ids: HashMap<&'a str, TensorId>,
+    producers: HashMap<&'a str, usize>,
+    /// One entry per consuming input slot, so an operation reading a tensor
+    /// twice is listed twice
+    consumers: HashMap<&'a str, Vec<usize>>,
+}
+
+impl<'a> GraphIndex<'a> {
+    /// Index a graph
+    ///
+    /// When several operations write the same output, the first one is its
+    /// producer.
+    pub(crate) fn new(graph: &GraphParts<'a>) -> Self {
+        let operations = graph.operations;
+        let mut op_names: HashMap<&str, Vec<OpId>> = HashMap::new();
+        let mut producers: HashMap<&str, usize> = HashMap::new();
+        let mut consumers: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (idx, op) in operations.iter().enumerate() {
+            if !op.name.is_empty() {
+                op_names
+                    .entry(op.name.as_str())
+                    .or_default()
+                    .push(OpId(idx));
+            }
+            for out in op.outputs.iter().filter(|o| !o.is_empty()) {
+                producers.entry(out.as_str()).or_insert(idx);
+            }
+            for input in op.inputs.iter().filter(|i| !i.is_empty()) {
+                consumers.entry(input.as_str()).or_default().push(idx);
+            }
+        }
+
+        let mut initializers: Vec<&str> = graph
+            .tensors
+            .iter()
+            .filter(|(_, t)| t.data_location().is_some())
+            .map(|(name, _)| name.as_str())
+            .chain(graph.sparse_tensors.keys().map(String::as_str))
+            .collect();
+        initializers.sort_unstable();
+        let names = graph
+            .inputs
+            .iter()
+            .map(String::as_str)
+            .chain(initializers)
+            .chain(
+                operations
+                    .iter()
+                    .flat_map(|op| op.outputs.iter().map(String::as_str)),
+            )
+            .chain(
+                operations
+                    .iter()
+                    .flat_map(|op| op.inputs.iter().map(String::as_str)),
+            );
+
+        let mut tensor_names: Vec<&str> = Vec::new();
+        let mut tensor_ids: HashMap<&str, TensorId> = HashMap::new();
+        for name in names.filter(|n| !n.is_empty()) {
+            tensor_ids.entry(name).or_insert_with(|| {
+                tensor_names.push(name);
+                TensorId(tensor_names.len() - 1)
+            });
+        }
+
+        GraphIndex {
+            operations,
+            op_names,
+            tensor_names,
+            tensor_ids,
+            producers,
+            consumers,
+        }
+    }
+
+    /// Number of indexed operations
+    pub fn op_count(&self) -> usize {
+        self.operations.len()
+    }
+
+    /// Ids of all operations, in graph order
+    pub fn op_ids(&self) -> impl ExactSizeIterator<Item = OpId> + use<> {
+        (0..self.operations.len()).map(OpId)
+    }
+
+    /// Get an operation by id
+    pub fn operation(&self, id: OpId) -> Option<&'a OnnxOperation> {
+        self.operations.get(id.0)
+    }
+
+    /// Get the only operation with a given name
+    ///
+    /// Returns None if no operation or more than one operation has this
+    /// name, see [`ops_named`](Self::ops_named) for the latter case.
+    pub fn op_named(&self, name: &str) -> Option<OpId> {
+        match self.ops_named(name) {
+            [id] => Some(*id),
+            _ => None,
+        }
+    }
+
+    /// Get all operations with a given name, in graph order
+    pub fn ops_named(&self, name: &str) -> &[OpId] {
+        self.op_names.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Node names used by more than one operation, sorted
+    pub fn duplicate_op_names(&self) -> Vec<&'a str> {
+        let mut names: Vec<&str> = self
+            .op_names
+            .iter()
+            .filter(|(_, ids)| ids.len() > 1)
+            .map(|(&name, _)| name)
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Number of distinct tensor names referenced by the graph
+    pub fn tensor_count(&self) -> usize {
+        self.tensor_names.len()
+    }
+
+    /// Get the id of a tensor name
+    pub fn tensor_id(&self, name: &str) -> Option<TensorId> {
+        self.tensor_ids.get(name).copied()
+    }
+
+    /// Get the name of a tensor id
+    pub fn tensor_name(&self, id: TensorId) -> Option<&'a str> {
+        self.tensor_names.get(id.0).copied()
+    }
+
+    /// Ids of an operation's inputs, skipping omitted optional inputs
+    pub fn op_inputs(&self, id: OpId) -> Vec<TensorId> {
+        self.tensor_ids_of(self.operation(id).map(|op| &op.inputs))
+    }
+
+    /// Ids of an operation's outputs, skipping omitted optional outputs
+    pub fn op_outputs(&self, id: OpId) -> Vec<TensorId> {
+        self.tensor_ids_of(self.operation(id).map(|op| &op.outputs))
+    }
+
+    /// Operation producing a tensor, None for graph inputs and initializers
+    pub fn producer_of(&self, tensor: &str) -> Option<OpId> {
+        self.producers.get(tensor).map(|&idx| OpId(idx))
+    }
+
+    /// Operations reading a tensor, in graph order and without repeats
+    pub fn consumers_of(&self, tensor: &str) -> Vec<OpId> {
+        let mut indices = self.consumer_slots(tensor).to_vec();
+        indices.dedup();
+        indices.into_iter().map(OpId).collect()
+    }
+
+    /// Operation producing a tensor id, see [`producer_of`](Self::producer_of)
+    pub fn producer(&self, tensor: TensorId) -> Option<OpId> {
+        self.producer_of(self.tensor_name(tensor)?)
+    }
+
+    /// Operations reading a tensor id, see [`consumers_of`](Self::consumers_of)
+    pub fn consumers(&self, tensor: TensorId) -> Vec<OpId> {
+        self.tensor_name(tensor)
+            .map_or_else(Vec::new, |name| self.consumers_of(name))
+    }
+
+    /// Operations producing any input of `op`, without repeats
+    pub fn predecessors(&self, op: OpId) -> Vec<OpId> {
+        self.predecessor_indices(op.0)
+            .into_iter()
+            .map(OpId)
+            .collect()
+    }
+
+    /// Operations reading any output of `op`, without repeats
+    pub fn successors(&self, op: OpId) -> Vec<OpId> {
+        self.successor_indices(op.0).into_iter().map(OpId).collect()
+    }
+
+    /// Walk all transitive predecessors of `op`, nearest first
+    pub fn upstream(&self, op: OpId) -> Walk<'_, 'a> {
+        Walk::new(self, op.0, Direction::Upstream)
+    }
+
+    /// Walk all transitive successors of `op`, nearest first
+    pub fn downstream(&self, op: OpId) -> Walk<'_, 'a> {
+        Walk::new(self, op.0, Direction::Downstream)
+    }
+
+    /// Consumer positions of a tensor, one per input slot
+    pub(crate) fn consumer_slots(&self, tensor: &str) -> &[usize] {
+        self.consumers.get(tensor).map_or(&[], Vec::as_slice)
+    }
+
+    /// Number of input slots of an operation fed by some operation
+    pub(crate) fn dependency_count(&self, idx: usize) -> usize {
+        self.operations[idx]
+            .inputs
+            .iter()
+            .filter(|i| !i.is_empty() && self.producers.contains_key(i.as_str()))
+            .count()
+    }
+
+    fn tensor_ids_of(&self, names: Option<&Vec<String>>) -> Vec<TensorId> {
+        names
+            .into_iter()
+            .flatten()
+            .filter_map(|name| self.tensor_id(name))
+            .collect()
+    }
+
+    fn predecessor_indices(&self, idx: usize) -> Vec<usize> {
+        let mut found: Vec<usize> = Vec::new();
+        let Some(op) = self.operations.get(idx) else {
+            return found;
+        };
+        for input in &op.inputs {
+            if let Some(&p) = self.producers.get(input.as_str())
+                && !found.contains(&p)
+            {
+                found.push(p);
+            }
+        }
+        found
+    }
+
+    fn successor_indices(&self, idx: usize) -> Vec<usize> {
+        let mut found: Vec<usize> = Vec::new();
+        let Some(op) = self.operations.get(idx) else {
+            return found;
+        };
+        for out in op.outputs.iter().filter(|o| !o.is_empty()) {
+            for &c in self.consumer_slots(out) {
+                if !found.contains(&c) {
+                    found.push(c);
+                }
+            }
+        }
+        found
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Direction {
+    Upstream,
+    Downstream,
+}
+
+/// Breadth-first walk over a [`GraphIndex`], excluding the starting operation
+///
+/// Created by [`GraphIndex::upstream`] and [`GraphIndex::downstream`]. Each
+/// operation is visited once, even if the graph has cycles.
+#[derive(Debug, Clone)]
+pub struct Walk<'i, 'a> {
+    index: &'i GraphIndex<'a>,
+    direction: Direction,
+    queue: VecDeque<usize>,
+    visited: HashSet<usize>,
+}
+
+impl<'i, 'a> Walk<'i, 'a> {
+    fn new(index: &'i GraphIndex<'a>, start: usize, direction: Direction) -> Self {
+        let mut walk = Walk {
+            index,
+            direction,
+            queue: VecDeque::new(),
+            visited: HashSet::from([start]),
+        };
+        walk.enqueue_neighbours(start);
+        walk
+    }
+
+    fn enqueue_neighbours(&mut self, idx: usize) {
+        let neighbours = match self.direction {
+            Direction::Upstream => self.index.predecessor_indices(idx),
+            Direction::Downstream => self.index.successor_indices(idx),
+        };
+        for n in neighbours {
+            if self.visited.insert(n) {
+                self.queue.push_back(n);
+            }
+        }
+    }
+}
+
+impl Iterator for Walk<'_, '_> {
+    type Item = OpId;
+
+    fn next(&mut self) -> Option<OpId> {
+        let idx = self.queue.pop_front()?;
+        self.enqueue_neighbours(idx);
+        Some(OpId(idx))
+    }
+}
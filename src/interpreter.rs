@@ -0,0 +1,1562 @@
+//! Reference CPU interpreter for small models.
+//!
+//! Evaluates the main graph in execution order with plain loops, for golden
+//! tests and export sanity checks rather than speed. Floating-point tensors
+//! are computed in `f32`, integer tensors in `i64` and booleans as `bool`.
+
+use std::collections::HashMap;
+
+use crate::shape_inference::{normalize_axis, normalize_unique_axes, slice_range};
+use crate::{AttributeValue, Bytes, DataType, Error, OnnxModel, OnnxOperation, OnnxTensor};
+
+/// Element storage of an evaluated value
+#[derive(Debug, Clone, PartialEq)]
+enum Buffer {
+    Float(Vec<f32>),
+    Int(Vec<i64>),
+    Bool(Vec<bool>),
+}
+
+impl Buffer {
+    fn len(&self) -> usize {
+        match self {
+            Buffer::Float(v) => v.len(),
+            Buffer::Int(v) => v.len(),
+            Buffer::Bool(v) => v.len(),
+        }
+    }
+
+    /// Elements at the given positions, in order
+    fn take(&self, positions: &[usize]) -> Buffer {
+        match self {
+            Buffer::Float(v) => Buffer::Float(positions.iter().map(|&p| v[p]).collect()),
+            Buffer::Int(v) => Buffer::Int(positions.iter().map(|&p| v[p]).collect()),
+            Buffer::Bool(v) => Buffer::Bool(positions.iter().map(|&p| v[p]).collect()),
+        }
+    }
+
+    /// Append buffers of the same kind end to end
+    fn concat(parts: &[&Buffer]) -> Result<Buffer, String> {
+        let mut out = match parts.first() {
+            Some(first) => first.take(&[]),
+            None => return Err("no buffers to concatenate".to_string()),
+        };
+        for part in parts {
+            match (&mut out, part) {
+                (Buffer::Float(o), Buffer::Float(p)) => o.extend_from_slice(p),
+                (Buffer::Int(o), Buffer::Int(p)) => o.extend_from_slice(p),
+                (Buffer::Bool(o), Buffer::Bool(p)) => o.extend_from_slice(p),
+                _ => return Err("inputs have mismatched element types".to_string()),
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// An evaluated tensor
+#[derive(Debug, Clone)]
+struct Value {
+    data_type: DataType,
+    shape: Vec<usize>,
+    buffer: Buffer,
+}
+
+impl Value {
+    fn float(shape: Vec<usize>, values: Vec<f32>) -> Self {
+        Value {
+            data_type: DataType::Float,
+            shape,
+            buffer: Buffer::Float(values),
+        }
+    }
+
+    fn int(shape: Vec<usize>, values: Vec<i64>) -> Self {
+        Value {
+            data_type: DataType::Int64,
+            shape,
+            buffer: Buffer::Int(values),
+        }
+    }
+
+    fn bool(shape: Vec<usize>, values: Vec<bool>) -> Self {
+        Value {
+            data_type: DataType::Bool,
+            shape,
+            buffer: Buffer::Bool(values),
+        }
+    }
+
+    /// Same element type as `self` with new contents
+    fn with(&self, shape: Vec<usize>, buffer: Buffer) -> Self {
+        Value {
+            data_type: self.data_type,
+            shape,
+            buffer,
+        }
+    }
+
+    fn reshaped(&self, shape: Vec<usize>) -> Self {
+        self.with(shape, self.buffer.clone())
+    }
+
+    fn floats(&self) -> Result<&[f32], String> {
+        match &self.buffer {
+            Buffer::Float(v) => Ok(v),
+            _ => Err(format!(
+                "expected a float tensor, found {:?}",
+                self.data_type
+            )),
+        }
+    }
+
+    fn ints(&self) -> Result<&[i64], String> {
+        match &self.buffer {
+            Buffer::Int(v) => Ok(v),
+            _ => Err(format!(
+                "expected an integer tensor, found {:?}",
+                self.data_type
+            )),
+        }
+    }
+
+    fn bools(&self) -> Result<&[bool], String> {
+        match &self.buffer {
+            Buffer::Bool(v) => Ok(v),
+            _ => Err(format!(
+                "expected a bool tensor, found {:?}",
+                self.data_type
+            )),
+        }
+    }
+
+    fn from_tensor(tensor: &OnnxTensor) -> Result<Self, Error> {
+        let shape = tensor
+            .shape()
+            .iter()
+            .map(|&d| {
+                usize::try_from(d).map_err(|_| {
+                    Error::DataConversion(format!(
+                        "tensor '{}' has unknown dim {}",
+                        tensor.name(),
+                        d
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let ints = Buffer::Int;
+        let data_type = tensor.data_type();
+        let buffer = match data_type {
+            t if t.is_float() => Buffer::Float(tensor.to_f32_vec()?),
+            DataType::Bool => Buffer::Bool(tensor.to_vec::<bool>()?),
+            DataType::Int8 => ints(tensor.to_vec::<i8>()?.into_iter().map(i64::from).collect()),
+            DataType::Int16 => ints(tensor.to_vec::<i16>()?.into_iter().map(i64::from).collect()),
+            DataType::Int32 => ints(tensor.to_vec::<i32>()?.into_iter().map(i64::from).collect()),
+            DataType::Int64 => ints(tensor.to_vec::<i64>()?),
+            DataType::Uint8 => ints(tensor.to_vec::<u8>()?.into_iter().map(i64::from).collect()),
+            DataType::Uint16 => ints(tensor.to_vec::<u16>()?.into_iter().map(i64::from).collect()),
+            DataType::Uint32 => ints(tensor.to_vec::<u32>()?.into_iter().map(i64::from).collect()),
+            DataType::Uint64 => ints(
+                tensor
+                    .to_vec::<u64>()?
+                    .into_iter()
+                    .map(|v| v as i64)
+                    .collect(),
+            ),
+            DataType::Int4 | DataType::Int2 => ints(tensor.unpack_i8()?.map(i64::from).collect()),
+            DataType::Uint4 | DataType::Uint2 => ints(tensor.unpack_u8()?.map(i64::from).collect()),
+            other => {
+                return Err(Error::Unsupported(format!(
+                    "{:?} tensor '{}' in the interpreter",
+                    other,
+                    tensor.name()
+                )));
+            }
+        };
+
+        let count: usize = shape.iter().product();
+        if buffer.len() != count {
+            return Err(Error::DataConversion(format!(
+                "tensor '{}' has {} elements, shape {:?} needs {}",
+                tensor.name(),
+                buffer.len(),
+                shape,
+                count
+            )));
+        }
+        Ok(Value {
+            data_type,
+            shape,
+            buffer,
+        })
+    }
+
+    /// Encode as a raw_data tensor of `data_type`, or of the nearest type
+    /// the interpreter can produce (Float or Int64)
+    fn into_tensor(self, name: &str) -> OnnxTensor {
+        let (data_type, bytes): (DataType, Vec<u8>) = match self.buffer {
+            Buffer::Float(v) => match self.data_type {
+                DataType::Double => (
+                    DataType::Double,
+                    v.iter().flat_map(|&x| f64::from(x).to_le_bytes()).collect(),
+                ),
+                _ => (
+                    DataType::Float,
+                    v.iter().flat_map(|x| x.to_le_bytes()).collect(),
+                ),
+            },
+            Buffer::Int(v) => match self.data_type {
+                t @ (DataType::Int8 | DataType::Uint8) => (t, v.iter().map(|&x| x as u8).collect()),
+                t @ (DataType::Int16 | DataType::Uint16) => (
+                    t,
+                    v.iter().flat_map(|&x| (x as u16).to_le_bytes()).collect(),
+                ),
+                t @ (DataType::Int32 | DataType::Uint32) => (
+                    t,
+                    v.iter().flat_map(|&x| (x as u32).to_le_bytes()).collect(),
+                ),
+                t @ DataType::Uint64 => (t, v.iter().flat_map(|x| x.to_le_bytes()).collect()),
+                _ => (
+                    DataType::Int64,
+                    v.iter().flat_map(|x| x.to_le_bytes()).collect(),
+                ),
+            },
+            Buffer::Bool(v) => (DataType::Bool, v.iter().map(|&b| b as u8).collect()),
+        };
+        OnnxTensor::from_raw_data(
+            name.to_string(),
+            self.shape.iter().map(|&d| d as i64).collect(),
+            data_type,
+            Bytes::from(bytes),
+        )
+    }
+}
+
+/// Failure while evaluating one operation
+enum OpError {
+    Invalid(String),
+    Unsupported(String),
+}
+
+impl From<String> for OpError {
+    fn from(message: String) -> Self {
+        OpError::Invalid(message)
+    }
+}
+
+impl From<&str> for OpError {
+    fn from(message: &str) -> Self {
+        OpError::Invalid(message.to_string())
+    }
+}
+
+type OpResult = Result<Vec<Value>, OpError>;
+
+/// Evaluate the main graph and return its outputs by name
+pub(crate) fn run_model(
+    model: &OnnxModel,
+    inputs: &HashMap<String, OnnxTensor>,
+) -> Result<HashMap<String, OnnxTensor>, Error> {
+    let mut values: HashMap<&str, Value> = HashMap::new();
+    for (name, tensor) in inputs {
+        if let Some(slot) = model.inputs.iter().find(|i| *i == name) {
+            values.insert(slot, Value::from_tensor(tensor)?);
+        } else {
+            return Err(Error::InvalidModel(format!(
+                "'{}' is not an input of the model",
+                name
+            )));
+        }
+    }
+    for name in &model.inputs {
+        let has_initializer = model
+            .tensors
+            .get(name)
+            .is_some_and(|t| t.data_location().is_some());
+        if !values.contains_key(name.as_str()) && !has_initializer {
+            return Err(Error::MissingField(format!("model input '{}'", name)));
+        }
+    }
+
+    for op in model.execution_order()? {
+        for name in op.inputs.iter().filter(|n| !n.is_empty()) {
+            if values.contains_key(name.as_str()) {
+                continue;
+            }
+            let tensor = model
+                .tensors
+                .get(name)
+                .filter(|t| t.data_location().is_some())
+                .ok_or_else(|| Error::InvalidModel(format!("value '{}' is not available", name)))?;
+            values.insert(name, Value::from_tensor(tensor)?);
+        }
+
+        let args = Args {
+            op,
+            opset: model.operation_opset_version(op).unwrap_or(0),
+            values: op.inputs.iter().map(|n| values.get(n.as_str())).collect(),
+        };
+        let outputs = if op.is_default_domain() {
+            eval_operation(&args)
+        } else {
+            Err(OpError::Unsupported(format!("domain '{}'", op.domain)))
+        };
+        let outputs = outputs.map_err(|e| match e {
+            OpError::Invalid(message) => {
+                Error::InvalidModel(format!("{} node '{}': {}", op.op_type, op.name, message))
+            }
+            OpError::Unsupported(what) => {
+                Error::Unsupported(format!("{} in {} node '{}'", what, op.op_type, op.name))
+            }
+        })?;
+
+        for (name, value) in op.outputs.iter().zip(outputs) {
+            if !name.is_empty() {
+                values.insert(name, value);
+            }
+        }
+    }
+
+    model
+        .outputs
+        .iter()
+        .map(|name| {
+            let value = match values.remove(name.as_str()) {
+                Some(value) => value,
+                None => model
+                    .tensors
+                    .get(name)
+                    .map(Value::from_tensor)
+                    .transpose()?
+                    .ok_or_else(|| {
+                        Error::InvalidModel(format!("output '{}' is never produced", name))
+                    })?,
+            };
+            Ok((name.clone(), value.into_tensor(name)))
+        })
+        .collect()
+}
+
+/// Inputs and attributes of the operation being evaluated
+struct Args<'a> {
+    op: &'a OnnxOperation,
+    opset: i64,
+    values: Vec<Option<&'a Value>>,
+}
+
+impl<'a> Args<'a> {
+    fn get(&self, index: usize) -> Result<&'a Value, String> {
+        self.optional(index)
+            .ok_or_else(|| format!("missing input {}", index))
+    }
+
+    fn optional(&self, index: usize) -> Option<&'a Value> {
+        self.values.get(index).copied().flatten()
+    }
+
+    fn all(&self) -> Result<Vec<&'a Value>, String> {
+        (0..self.values.len()).map(|i| self.get(i)).collect()
+    }
+
+    fn int_attr(&self, name: &str, default: i64) -> i64 {
+        self.op.get_int_attribute(name).unwrap_or(default)
+    }
+
+    fn float_attr(&self, name: &str, default: f32) -> f32 {
+        self.op.get_float_attribute(name).unwrap_or(default)
+    }
+
+    /// Integer values from an attribute (older opsets) or an input (newer ones)
+    fn ints(&self, attribute: &str, index: usize) -> Result<Option<Vec<i64>>, String> {
+        if let Some(values) = self.op.get_ints_attribute(attribute) {
+            return Ok(Some(values.to_vec()));
+        }
+        self.optional(index)
+            .map(|v| v.ints().map(<[i64]>::to_vec))
+            .transpose()
+    }
+
+    fn axis(&self, default: i64, rank: usize) -> Result<usize, String> {
+        normalize_axis(self.int_attr("axis", default), rank)
+    }
+}
+
+fn eval_operation(args: &Args) -> OpResult {
+    let op = args.op;
+    let one = |value: Value| Ok(vec![value]);
+
+    match op.op_type.as_str() {
+        "Identity" => one(args.get(0)?.clone()),
+        "Dropout" => {
+            let x = args.get(0)?;
+            let mask = Value::bool(x.shape.clone(), vec![true; x.buffer.len()]);
+            Ok(vec![x.clone(), mask])
+        }
+        "Relu" | "Sigmoid" | "Tanh" | "Exp" | "Log" | "Sqrt" | "Reciprocal" | "Floor" | "Ceil"
+        | "Round" | "Sin" | "Cos" | "Softplus" | "Softsign" | "LeakyRelu" | "Elu"
+        | "HardSigmoid" | "Selu" => {
+            let x = args.get(0)?;
+            let f: Box<dyn Fn(f32) -> f32> = match op.op_type.as_str() {
+                "Relu" => Box::new(|v| v.max(0.0)),
+                "Sigmoid" => Box::new(|v| 1.0 / (1.0 + (-v).exp())),
+                "Tanh" => Box::new(f32::tanh),
+                "Exp" => Box::new(f32::exp),
+                "Log" => Box::new(f32::ln),
+                "Sqrt" => Box::new(f32::sqrt),
+                "Reciprocal" => Box::new(f32::recip),
+                "Floor" => Box::new(f32::floor),
+                "Ceil" => Box::new(f32::ceil),
+                "Round" => Box::new(f32::round_ties_even),
+                "Sin" => Box::new(f32::sin),
+                "Cos" => Box::new(f32::cos),
+                "Softplus" => Box::new(|v| v.exp().ln_1p()),
+                "Softsign" => Box::new(|v| v / (1.0 + v.abs())),
+                "LeakyRelu" => {
+                    let alpha = args.float_attr("alpha", 0.01);
+                    Box::new(move |v| if v < 0.0 { alpha * v } else { v })
+                }
+                "Elu" => {
+                    let alpha = args.float_attr("alpha", 1.0);
+                    Box::new(move |v| if v < 0.0 { alpha * v.exp_m1() } else { v })
+                }
+                "HardSigmoid" => {
+                    let alpha = args.float_attr("alpha", 0.2);
+                    let beta = args.float_attr("beta", 0.5);
+                    Box::new(move |v| (alpha * v + beta).clamp(0.0, 1.0))
+                }
+                _ => {
+                    let alpha = args.float_attr("alpha", 1.673_263_2);
+                    let gamma = args.float_attr("gamma", 1.050_701);
+                    Box::new(move |v| {
+                        if v > 0.0 {
+                            gamma * v
+                        } else {
+                            gamma * (alpha * v.exp() - alpha)
+                        }
+                    })
+                }
+            };
+            let values = x.floats()?.iter().map(|&v| f(v)).collect();
+            one(x.with(x.shape.clone(), Buffer::Float(values)))
+        }
+        "Neg" | "Abs" | "Sign" => {
+            let x = args.get(0)?;
+            let buffer = match &x.buffer {
+                Buffer::Float(v) => Buffer::Float(match op.op_type.as_str() {
+                    "Neg" => v.iter().map(|a| -a).collect(),
+                    "Abs" => v.iter().map(|a| a.abs()).collect(),
+                    _ => v
+                        .iter()
+                        .map(|&a| if a == 0.0 { 0.0 } else { a.signum() })
+                        .collect(),
+                }),
+                Buffer::Int(v) => Buffer::Int(match op.op_type.as_str() {
+                    "Neg" => v.iter().map(|a| a.wrapping_neg()).collect(),
+                    "Abs" => v.iter().map(|a| a.wrapping_abs()).collect(),
+                    _ => v.iter().map(|a| a.signum()).collect(),
+                }),
+                Buffer::Bool(_) => return Err("bool input is not supported".into()),
+            };
+            one(x.with(x.shape.clone(), buffer))
+        }
+        "Not" => {
+            let x = args.get(0)?;
+            let values = x.bools()?.iter().map(|b| !b).collect();
+            one(Value::bool(x.shape.clone(), values))
+        }
+        "Clip" => {
+            let x = args.get(0)?;
+            let bound = |index: usize, attr: &str, default: f32| -> Result<f32, String> {
+                match args.optional(index) {
+                    Some(v) => v
+                        .floats()?
+                        .first()
+                        .copied()
+                        .ok_or_else(|| "empty clip bound".to_string()),
+                    None => Ok(args.float_attr(attr, default)),
+                }
+            };
+            let min = bound(1, "min", f32::MIN)?;
+            let max = bound(2, "max", f32::MAX)?;
+            let values = x.floats()?.iter().map(|v| v.max(min).min(max)).collect();
+            one(x.with(x.shape.clone(), Buffer::Float(values)))
+        }
+
+        "Add" | "Sub" | "Mul" | "Div" | "Pow" | "Max" | "Min" | "Sum" | "Mod" => {
+            let inputs = args.all()?;
+            let (first, rest) = inputs.split_first().ok_or("no inputs")?;
+            let mut acc = (*first).clone();
+            for next in rest {
+                acc = arithmetic(&op.op_type, &acc, next, args.int_attr("fmod", 0) != 0)?;
+            }
+            one(acc)
+        }
+        "Equal" | "Less" | "LessOrEqual" | "Greater" | "GreaterOrEqual" => {
+            one(compare(&op.op_type, args.get(0)?, args.get(1)?)?)
+        }
+        "And" | "Or" | "Xor" => {
+            let (a, b) = (args.get(0)?, args.get(1)?);
+            let f = match op.op_type.as_str() {
+                "And" => |l: bool, r: bool| l && r,
+                "Or" => |l: bool, r: bool| l || r,
+                _ => |l: bool, r: bool| l != r,
+            };
+            let (shape, values) = zip_broadcast(a.bools()?, &a.shape, b.bools()?, &b.shape, f)?;
+            one(Value::bool(shape, values))
+        }
+        "Where" => {
+            let (cond, x, y) = (args.get(0)?, args.get(1)?, args.get(2)?);
+            let shape = broadcast_shapes(&[&cond.shape, &x.shape, &y.shape])?;
+            let c = cond.bools()?;
+            let cp = broadcast_positions(&shape, &cond.shape);
+            let xs = x.buffer.take(&broadcast_positions(&shape, &x.shape));
+            let ys = y.buffer.take(&broadcast_positions(&shape, &y.shape));
+            let pick = |i: usize| c[cp[i]];
+            let buffer = match (xs, ys) {
+                (Buffer::Float(a), Buffer::Float(b)) => Buffer::Float(
+                    (0..a.len())
+                        .map(|i| if pick(i) { a[i] } else { b[i] })
+                        .collect(),
+                ),
+                (Buffer::Int(a), Buffer::Int(b)) => Buffer::Int(
+                    (0..a.len())
+                        .map(|i| if pick(i) { a[i] } else { b[i] })
+                        .collect(),
+                ),
+                (Buffer::Bool(a), Buffer::Bool(b)) => Buffer::Bool(
+                    (0..a.len())
+                        .map(|i| if pick(i) { a[i] } else { b[i] })
+                        .collect(),
+                ),
+                _ => return Err("X and Y have mismatched element types".into()),
+            };
+            one(x.with(shape, buffer))
+        }
+
+        "MatMul" => one(matmul(args.get(0)?, args.get(1)?)?),
+        "Gemm" => one(gemm(args)?),
+        "Conv" => one(conv(args)?),
+        "MaxPool" | "AveragePool" => pool(args),
+        "GlobalAveragePool" | "GlobalMaxPool" => {
+            let x = args.get(0)?;
+            if x.shape.len() < 3 {
+                return Err("input must have rank >= 3".into());
+            }
+            let inner: usize = x.shape[2..].iter().product();
+            let values = x
+                .floats()?
+                .chunks(inner.max(1))
+                .map(|c| {
+                    if op.op_type == "GlobalMaxPool" {
+                        c.iter().copied().fold(f32::NEG_INFINITY, f32::max)
+                    } else {
+                        c.iter().sum::<f32>() / c.len() as f32
+                    }
+                })
+                .collect();
+            let mut shape = x.shape[..2].to_vec();
+            shape.resize(x.shape.len(), 1);
+            one(x.with(shape, Buffer::Float(values)))
+        }
+        "BatchNormalization" => {
+            let x = args.get(0)?;
+            let [scale, bias, mean, var] =
+                [1, 2, 3, 4].map(|i| args.get(i).and_then(|v| v.floats()));
+            let (scale, bias, mean, var) = (scale?, bias?, mean?, var?);
+            let epsilon = args.float_attr("epsilon", 1e-5);
+            let channels = *x.shape.get(1).ok_or("input must have rank >= 2")?;
+            if [scale, bias, mean, var].iter().any(|p| p.len() != channels) {
+                return Err("parameter lengths do not match channels".into());
+            }
+            let inner: usize = x.shape[2..].iter().product();
+            let values = x
+                .floats()?
+                .iter()
+                .enumerate()
+                .map(|(i, &v)| {
+                    let c = (i / inner.max(1)) % channels;
+                    (v - mean[c]) / (var[c] + epsilon).sqrt() * scale[c] + bias[c]
+                })
+                .collect();
+            one(x.with(x.shape.clone(), Buffer::Float(values)))
+        }
+        "Softmax" | "LogSoftmax" => {
+            let x = args.get(0)?;
+            let rank = x.shape.len();
+            let (outer, len, inner) = if args.opset >= 13 {
+                let axis = args.axis(-1, rank)?;
+                (
+                    x.shape[..axis].iter().product(),
+                    x.shape[axis],
+                    x.shape[axis + 1..].iter().product(),
+                )
+            } else {
+                let axis = args.axis(1, rank)?;
+                (
+                    x.shape[..axis].iter().product(),
+                    x.shape[axis..].iter().product(),
+                    1,
+                )
+            };
+            let mut values = x.floats()?.to_vec();
+            softmax(&mut values, outer, len, inner, op.op_type == "LogSoftmax");
+            one(x.with(x.shape.clone(), Buffer::Float(values)))
+        }
+        "ReduceMean" | "ReduceSum" | "ReduceMax" | "ReduceMin" | "ReduceProd" => reduce(args),
+        "ArgMax" | "ArgMin" => {
+            let x = args.get(0)?;
+            let axis = args.axis(0, x.shape.len())?;
+            let keep = args.int_attr("keepdims", 1) != 0;
+            let last = args.int_attr("select_last_index", 0) != 0;
+            let values = x.floats()?;
+            let (outer, len, inner) = split_at_axis(&x.shape, axis);
+            let better = |candidate: f32, best: f32| match (op.op_type == "ArgMax", last) {
+                (true, false) => candidate > best,
+                (true, true) => candidate >= best,
+                (false, false) => candidate < best,
+                (false, true) => candidate <= best,
+            };
+            let mut out = Vec::with_capacity(outer * inner);
+            for o in 0..outer {
+                for i in 0..inner {
+                    let at = |k: usize| values[(o * len + k) * inner + i];
+                    let mut best = 0;
+                    for k in 1..len {
+                        if better(at(k), at(best)) {
+                            best = k;
+                        }
+                    }
+                    out.push(best as i64);
+                }
+            }
+            let mut shape = x.shape.clone();
+            if keep {
+                shape[axis] = 1;
+            } else {
+                shape.remove(axis);
+            }
+            one(Value::int(shape, out))
+        }
+
+        "Constant" => one(constant(args)?),
+        "ConstantOfShape" => {
+            let shape = to_shape(args.get(0)?.ints()?)?;
+            let count = shape.iter().product();
+            let fill = match op
+                .get_attribute("value")
+                .and_then(AttributeValue::as_tensor)
+            {
+                Some(t) => Value::from_tensor(t).map_err(|e| e.to_string())?,
+                None => Value::float(vec![1], vec![0.0]),
+            };
+            if fill.buffer.len() != 1 {
+                return Err("value must hold one element".into());
+            }
+            one(fill.with(shape, fill.buffer.take(&vec![0; count])))
+        }
+        "Shape" => {
+            let x = args.get(0)?;
+            let rank = x.shape.len() as i64;
+            let clamp = |v: i64| (if v < 0 { v + rank } else { v }).clamp(0, rank) as usize;
+            let start = clamp(args.int_attr("start", 0));
+            let end = clamp(args.int_attr("end", rank)).max(start);
+            let dims: Vec<i64> = x.shape[start..end].iter().map(|&d| d as i64).collect();
+            one(Value::int(vec![dims.len()], dims))
+        }
+        "Size" => {
+            let x = args.get(0)?;
+            one(Value::int(vec![], vec![x.buffer.len() as i64]))
+        }
+        "Cast" => {
+            let x = args.get(0)?;
+            let to = op.get_int_attribute("to").ok_or("missing 'to' attribute")?;
+            one(cast(x, DataType::from_onnx_type(to as i32))?)
+        }
+        "CastLike" => one(cast(args.get(0)?, args.get(1)?.data_type)?),
+        "Reshape" => {
+            let x = args.get(0)?;
+            let target = args.ints("shape", 1)?.ok_or("missing shape")?;
+            let allow_zero = args.int_attr("allowzero", 0) != 0;
+            one(x.reshaped(reshape_dims(&x.shape, &target, allow_zero)?))
+        }
+        "Flatten" => {
+            let x = args.get(0)?;
+            let rank = x.shape.len();
+            let axis = match args.int_attr("axis", 1) {
+                a if a == rank as i64 => rank,
+                a => normalize_axis(a, rank)?,
+            };
+            one(x.reshaped(vec![
+                x.shape[..axis].iter().product(),
+                x.shape[axis..].iter().product(),
+            ]))
+        }
+        "Squeeze" => {
+            let x = args.get(0)?;
+            let shape = match args.ints("axes", 1)? {
+                Some(axes) => {
+                    let axes = normalize_axes(&axes, x.shape.len())?;
+                    if axes.iter().any(|&a| x.shape[a] != 1) {
+                        return Err("cannot squeeze a dim that is not 1".into());
+                    }
+                    x.shape
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| !axes.contains(i))
+                        .map(|(_, &d)| d)
+                        .collect()
+                }
+                None => x.shape.iter().copied().filter(|&d| d != 1).collect(),
+            };
+            one(x.reshaped(shape))
+        }
+        "Unsqueeze" => {
+            let x = args.get(0)?;
+            let axes = args.ints("axes", 1)?.ok_or("missing axes")?;
+            let mut axes = normalize_unique_axes(&axes, x.shape.len() + axes.len())?;
+            axes.sort_unstable();
+            let mut shape = x.shape.clone();
+            for axis in axes {
+                shape.insert(axis, 1);
+            }
+            one(x.reshaped(shape))
+        }
+        "Transpose" => {
+            let x = args.get(0)?;
+            let rank = x.shape.len();
+            let perm = match op.get_ints_attribute("perm") {
+                Some(p) => normalize_unique_axes(p, rank)?,
+                None => (0..rank).rev().collect(),
+            };
+            if perm.len() != rank {
+                return Err("perm does not match input rank".into());
+            }
+            let in_strides = strides(&x.shape);
+            let shape: Vec<usize> = perm.iter().map(|&p| x.shape[p]).collect();
+            let walk: Vec<isize> = perm.iter().map(|&p| in_strides[p] as isize).collect();
+            one(x.with(
+                shape.clone(),
+                x.buffer.take(&map_positions(&shape, 0, &walk)),
+            ))
+        }
+        "Concat" => {
+            let inputs = args.all()?;
+            let first = inputs.first().ok_or("no inputs")?;
+            let axis = normalize_axis(
+                op.get_int_attribute("axis")
+                    .ok_or("missing 'axis' attribute")?,
+                first.shape.len(),
+            )?;
+            let mut shape = first.shape.clone();
+            shape[axis] = 0;
+            for input in &inputs {
+                let matches = input.shape.len() == shape.len()
+                    && (0..shape.len()).all(|d| d == axis || input.shape[d] == shape[d]);
+                if !matches {
+                    return Err("input shapes do not match outside the axis".into());
+                }
+                shape[axis] += input.shape[axis];
+            }
+            let (outer, _, inner) = split_at_axis(&first.shape, axis);
+            let parts: Vec<&Buffer> = inputs.iter().map(|v| &v.buffer).collect();
+            let joined = Buffer::concat(&parts)?;
+            let mut positions = Vec::with_capacity(joined.len());
+            for o in 0..outer {
+                let mut base = 0;
+                for input in &inputs {
+                    let chunk = input.shape[axis] * inner;
+                    positions.extend(base + o * chunk..base + (o + 1) * chunk);
+                    base += input.buffer.len();
+                }
+            }
+            one(first.with(shape, joined.take(&positions)))
+        }
+        "Gather" => {
+            let (data, indices) = (args.get(0)?, args.get(1)?);
+            let axis = args.axis(0, data.shape.len())?;
+            let (outer, len, inner) = split_at_axis(&data.shape, axis);
+            let resolved = indices
+                .ints()?
+                .iter()
+                .map(|&i| {
+                    let r = if i < 0 { i + len as i64 } else { i };
+                    usize::try_from(r)
+                        .ok()
+                        .filter(|&r| r < len)
+                        .ok_or_else(|| format!("index {} is out of range", i))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut positions = Vec::with_capacity(outer * resolved.len() * inner);
+            for o in 0..outer {
+                for &r in &resolved {
+                    positions.extend((0..inner).map(|j| (o * len + r) * inner + j));
+                }
+            }
+            let mut shape = data.shape[..axis].to_vec();
+            shape.extend(&indices.shape);
+            shape.extend(&data.shape[axis + 1..]);
+            one(data.with(shape, data.buffer.take(&positions)))
+        }
+        "Slice" => one(slice(args)?),
+        "Expand" => {
+            let x = args.get(0)?;
+            let target = to_shape(args.get(1)?.ints()?)?;
+            let shape = broadcast_shapes(&[&x.shape, &target])?;
+            one(x.with(
+                shape.clone(),
+                x.buffer.take(&broadcast_positions(&shape, &x.shape)),
+            ))
+        }
+        other => Err(OpError::Unsupported(format!("operator '{}'", other))),
+    }
+}
+
+fn strides(shape: &[usize]) -> Vec<usize> {
+    let mut strides = vec![1; shape.len()];
+    for i in (0..shape.len().saturating_sub(1)).rev() {
+        strides[i] = strides[i + 1] * shape[i + 1];
+    }
+    strides
+}
+
+/// Input positions visited when walking `shape` in row-major order, starting
+/// at `start` and moving by `strides` per dim
+fn map_positions(shape: &[usize], start: usize, strides: &[isize]) -> Vec<usize> {
+    let total: usize = shape.iter().product();
+    let mut out = Vec::with_capacity(total);
+    let mut index = vec![0usize; shape.len()];
+    let mut pos = start as isize;
+    for _ in 0..total {
+        out.push(pos as usize);
+        for d in (0..shape.len()).rev() {
+            index[d] += 1;
+            pos += strides[d];
+            if index[d] < shape[d] {
+                break;
+            }
+            pos -= strides[d] * shape[d] as isize;
+            index[d] = 0;
+        }
+    }
+    out
+}
+
+/// Numpy-style broadcast of several shapes
+fn broadcast_shapes(shapes: &[&[usize]]) -> Result<Vec<usize>, String> {
+    let rank = shapes.iter().map(|s| s.len()).max().unwrap_or(0);
+    (0..rank)
+        .map(|i| {
+            shapes.iter().try_fold(1, |acc, shape| {
+                let Some(offset) = (i + shape.len()).checked_sub(rank) else {
+                    return Ok(acc);
+                };
+                match (acc, shape[offset]) {
+                    (a, 1) => Ok(a),
+                    (1, d) => Ok(d),
+                    (a, d) if a == d => Ok(a),
+                    (a, d) => Err(format!("cannot broadcast dims {} and {}", a, d)),
+                }
+            })
+        })
+        .collect()
+}
+
+/// Position in a tensor of `shape` for each element of the broadcast `out` shape
+fn broadcast_positions(out: &[usize], shape: &[usize]) -> Vec<usize> {
+    let offset = out.len() - shape.len();
+    let in_strides = strides(shape);
+    let walk: Vec<isize> = (0..out.len())
+        .map(|i| match i.checked_sub(offset) {
+            Some(j) if shape[j] != 1 => in_strides[j] as isize,
+            _ => 0,
+        })
+        .collect();
+    map_positions(out, 0, &walk)
+}
+
+fn zip_broadcast<T: Copy, U>(
+    a: &[T],
+    a_shape: &[usize],
+    b: &[T],
+    b_shape: &[usize],
+    f: impl Fn(T, T) -> U,
+) -> Result<(Vec<usize>, Vec<U>), String> {
+    let shape = broadcast_shapes(&[a_shape, b_shape])?;
+    let values = if a_shape == b_shape {
+        a.iter().zip(b).map(|(&l, &r)| f(l, r)).collect()
+    } else {
+        let ap = broadcast_positions(&shape, a_shape);
+        let bp = broadcast_positions(&shape, b_shape);
+        ap.iter().zip(&bp).map(|(&i, &j)| f(a[i], b[j])).collect()
+    };
+    Ok((shape, values))
+}
+
+/// Sizes before, at and after `axis`
+fn split_at_axis(shape: &[usize], axis: usize) -> (usize, usize, usize) {
+    (
+        shape[..axis].iter().product(),
+        shape[axis],
+        shape[axis + 1..].iter().product(),
+    )
+}
+
+fn normalize_axes(axes: &[i64], rank: usize) -> Result<Vec<usize>, String> {
+    axes.iter().map(|&a| normalize_axis(a, rank)).collect()
+}
+
+fn to_shape(dims: &[i64]) -> Result<Vec<usize>, String> {
+    dims.iter()
+        .map(|&d| usize::try_from(d).map_err(|_| format!("invalid dim {}", d)))
+        .collect()
+}
+
+fn arithmetic(op_type: &str, a: &Value, b: &Value, fmod: bool) -> Result<Value, String> {
+    let (shape, buffer) = match (&a.buffer, &b.buffer) {
+        (Buffer::Float(x), Buffer::Float(y)) => {
+            let f = match op_type {
+                "Add" | "Sum" => |l: f32, r: f32| l + r,
+                "Sub" => |l, r| l - r,
+                "Mul" => |l, r| l * r,
+                "Div" => |l, r| l / r,
+                "Pow" => f32::powf,
+                "Max" => f32::max,
+                "Min" => f32::min,
+                _ if fmod => |l: f32, r: f32| l % r,
+                _ => return Err("float Mod requires fmod=1".to_string()),
+            };
+            let (shape, values) = zip_broadcast(x, &a.shape, y, &b.shape, f)?;
+            (shape, Buffer::Float(values))
+        }
+        (Buffer::Int(x), Buffer::Int(y)) => {
+            let f = match op_type {
+                "Add" | "Sum" => |l: i64, r: i64| Some(l.wrapping_add(r)),
+                "Sub" => |l: i64, r: i64| Some(l.wrapping_sub(r)),
+                "Mul" => |l: i64, r: i64| Some(l.wrapping_mul(r)),
+                "Div" => i64::checked_div,
+                "Pow" => |l: i64, r: i64| l.checked_pow(u32::try_from(r).ok()?),
+                "Max" => |l: i64, r: i64| Some(l.max(r)),
+                "Min" => |l: i64, r: i64| Some(l.min(r)),
+                _ if fmod => i64::checked_rem,
+                // sign follows the divisor, like Python
+                _ => |l: i64, r: i64| {
+                    l.checked_rem(r).map(|m| {
+                        if m != 0 && (m < 0) != (r < 0) {
+                            m + r
+                        } else {
+                            m
+                        }
+                    })
+                },
+            };
+            let (shape, values) = zip_broadcast(x, &a.shape, y, &b.shape, f)?;
+            let values = values
+                .into_iter()
+                .collect::<Option<Vec<_>>>()
+                .ok_or("integer division by zero or overflow")?;
+            (shape, Buffer::Int(values))
+        }
+        _ => {
+            return Err(format!(
+                "mismatched input types {:?} and {:?}",
+                a.data_type, b.data_type
+            ));
+        }
+    };
+    Ok(a.with(shape, buffer))
+}
+
+fn compare(op_type: &str, a: &Value, b: &Value) -> Result<Value, String> {
+    fn apply<T: PartialOrd + Copy>(
+        op_type: &str,
+        a: &[T],
+        a_shape: &[usize],
+        b: &[T],
+        b_shape: &[usize],
+    ) -> Result<(Vec<usize>, Vec<bool>), String> {
+        match op_type {
+            "Equal" => zip_broadcast(a, a_shape, b, b_shape, |l, r| l == r),
+            "Less" => zip_broadcast(a, a_shape, b, b_shape, |l, r| l < r),
+            "LessOrEqual" => zip_broadcast(a, a_shape, b, b_shape, |l, r| l <= r),
+            "Greater" => zip_broadcast(a, a_shape, b, b_shape, |l, r| l > r),
+            _ => zip_broadcast(a, a_shape, b, b_shape, |l, r| l >= r),
+        }
+    }
+    let (shape, values) = match (&a.buffer, &b.buffer) {
+        (Buffer::Float(x), Buffer::Float(y)) => apply(op_type, x, &a.shape, y, &b.shape)?,
+        (Buffer::Int(x), Buffer::Int(y)) => apply(op_type, x, &a.shape, y, &b.shape)?,
+        (Buffer::Bool(x), Buffer::Bool(y)) => apply(op_type, x, &a.shape, y, &b.shape)?,
+        _ => {
+            return Err(format!(
+                "mismatched input types {:?} and {:?}",
+                a.data_type, b.data_type
+            ));
+        }
+    };
+    Ok(Value::bool(shape, values))
+}
+
+fn cast(x: &Value, to: DataType) -> Result<Value, String> {
+    // wrap to the width of the target integer type
+    let wrap = |v: i64| match to {
+        DataType::Int8 | DataType::Int4 | DataType::Int2 => v as i8 as i64,
+        DataType::Int16 => v as i16 as i64,
+        DataType::Int32 => v as i32 as i64,
+        DataType::Uint8 | DataType::Uint4 | DataType::Uint2 => v as u8 as i64,
+        DataType::Uint16 => v as u16 as i64,
+        DataType::Uint32 => v as u32 as i64,
+        _ => v,
+    };
+    let buffer = if to.is_float() {
+        Buffer::Float(match &x.buffer {
+            Buffer::Float(v) => v.clone(),
+            Buffer::Int(v) => v.iter().map(|&i| i as f32).collect(),
+            Buffer::Bool(v) => v.iter().map(|&b| b as u8 as f32).collect(),
+        })
+    } else if to.is_integer() {
+        Buffer::Int(match &x.buffer {
+            Buffer::Float(v) => v.iter().map(|&f| wrap(f as i64)).collect(),
+            Buffer::Int(v) => v.iter().map(|&i| wrap(i)).collect(),
+            Buffer::Bool(v) => v.iter().map(|&b| b as i64).collect(),
+        })
+    } else if to == DataType::Bool {
+        Buffer::Bool(match &x.buffer {
+            Buffer::Float(v) => v.iter().map(|&f| f != 0.0).collect(),
+            Buffer::Int(v) => v.iter().map(|&i| i != 0).collect(),
+            Buffer::Bool(v) => v.clone(),
+        })
+    } else {
+        return Err(format!("cannot cast to {:?}", to));
+    };
+    Ok(Value {
+        data_type: to,
+        shape: x.shape.clone(),
+        buffer,
+    })
+}
+
+fn constant(args: &Args) -> Result<Value, OpError> {
+    let (name, value) = args
+        .op
+        .attributes
+        .iter()
+        .next()
+        .ok_or("Constant has no value attribute")?;
+    let value = match value {
+        AttributeValue::Tensor(t) => {
+            Value::from_tensor(t).map_err(|e| OpError::Invalid(e.to_string()))?
+        }
+        AttributeValue::Float(v) => Value::float(vec![], vec![*v]),
+        AttributeValue::Floats(v) => Value::float(vec![v.len()], v.clone()),
+        AttributeValue::Int(v) => Value::int(vec![], vec![*v]),
+        AttributeValue::Ints(v) => Value::int(vec![v.len()], v.clone()),
+        _ => {
+            return Err(OpError::Unsupported(format!(
+                "Constant attribute '{}'",
+                name
+            )));
+        }
+    };
+    Ok(value)
+}
+
+fn reshape_dims(input: &[usize], target: &[i64], allow_zero: bool) -> Result<Vec<usize>, String> {
+    let mut shape = Vec::with_capacity(target.len());
+    let mut infer_at = None;
+    for (i, &d) in target.iter().enumerate() {
+        shape.push(match d {
+            -1 if infer_at.is_none() => {
+                infer_at = Some(i);
+                1
+            }
+            0 if !allow_zero => *input.get(i).ok_or("shape copies a missing dim")?,
+            d => usize::try_from(d).map_err(|_| format!("invalid dim {}", d))?,
+        });
+    }
+    let total: usize = input.iter().product();
+    let known: usize = shape.iter().product();
+    if let Some(i) = infer_at {
+        if known == 0 || !total.is_multiple_of(known) {
+            return Err(format!("cannot reshape {:?} to {:?}", input, target));
+        }
+        shape[i] = total / known;
+    } else if known != total {
+        return Err(format!("cannot reshape {:?} to {:?}", input, target));
+    }
+    Ok(shape)
+}
+
+fn softmax(values: &mut [f32], outer: usize, len: usize, inner: usize, log: bool) {
+    for o in 0..outer {
+        for i in 0..inner {
+            let index = |k: usize| (o * len + k) * inner + i;
+            let max = (0..len)
+                .map(|k| values[index(k)])
+                .fold(f32::NEG_INFINITY, f32::max);
+            let sum: f32 = (0..len).map(|k| (values[index(k)] - max).exp()).sum();
+            for k in 0..len {
+                let v = values[index(k)] - max;
+                values[index(k)] = if log { v - sum.ln() } else { v.exp() / sum };
+            }
+        }
+    }
+}
+
+fn reduce(args: &Args) -> OpResult {
+    let x = args.get(0)?;
+    let values = x.floats()?;
+    let keep = args.int_attr("keepdims", 1) != 0;
+    let rank = x.shape.len();
+    // axes moved from attribute to input in opset 18 (opset 13 for ReduceSum)
+    let axes = args.ints("axes", 1)?.unwrap_or_default();
+    if axes.is_empty() && args.int_attr("noop_with_empty_axes", 0) != 0 {
+        return Ok(vec![x.clone()]);
+    }
+    let axes = if axes.is_empty() {
+        (0..rank).collect()
+    } else {
+        normalize_axes(&axes, rank)?
+    };
+
+    let kept: Vec<usize> = (0..rank)
+        .map(|d| if axes.contains(&d) { 1 } else { x.shape[d] })
+        .collect();
+    let targets = {
+        let walk: Vec<isize> = strides(&kept)
+            .iter()
+            .zip(&kept)
+            .map(|(&s, &d)| if d == 1 { 0 } else { s as isize })
+            .collect();
+        map_positions(&x.shape, 0, &walk)
+    };
+    let op_type = args.op.op_type.as_str();
+    let init = match op_type {
+        "ReduceMax" => f32::NEG_INFINITY,
+        "ReduceMin" => f32::INFINITY,
+        "ReduceProd" => 1.0,
+        _ => 0.0,
+    };
+    let mut out = vec![init; kept.iter().product()];
+    for (&v, &t) in values.iter().zip(&targets) {
+        out[t] = match op_type {
+            "ReduceMax" => out[t].max(v),
+            "ReduceMin" => out[t].min(v),
+            "ReduceProd" => out[t] * v,
+            _ => out[t] + v,
+        };
+    }
+    if op_type == "ReduceMean" {
+        let count = (values.len() / out.len().max(1)) as f32;
+        out.iter_mut().for_each(|v| *v /= count);
+    }
+
+    let shape = if keep {
+        kept
+    } else {
+        (0..rank)
+            .filter(|d| !axes.contains(d))
+            .map(|d| x.shape[d])
+            .collect()
+    };
+    Ok(vec![x.with(shape, Buffer::Float(out))])
+}
+
+fn slice(args: &Args) -> Result<Value, String> {
+    let x = args.get(0)?;
+    let rank = x.shape.len();
+    let (starts, ends, axes, steps) = if args.opset >= 10 {
+        let optional = |i: usize| -> Result<Option<Vec<i64>>, String> {
+            args.optional(i)
+                .map(|v| v.ints().map(<[i64]>::to_vec))
+                .transpose()
+        };
+        (
+            args.get(1)?.ints()?.to_vec(),
+            args.get(2)?.ints()?.to_vec(),
+            optional(3)?,
+            optional(4)?,
+        )
+    } else {
+        let op = args.op;
+        (
+            op.get_ints_attribute("starts")
+                .ok_or("missing starts")?
+                .to_vec(),
+            op.get_ints_attribute("ends")
+                .ok_or("missing ends")?
+                .to_vec(),
+            op.get_ints_attribute("axes").map(<[i64]>::to_vec),
+            None,
+        )
+    };
+    let axes = axes.unwrap_or_else(|| (0..starts.len() as i64).collect());
+    let steps = steps.unwrap_or_else(|| vec![1; starts.len()]);
+    if ends.len() != starts.len() || axes.len() != starts.len() || steps.len() != starts.len() {
+        return Err("starts, ends, axes and steps lengths differ".to_string());
+    }
+
+    let axes = normalize_unique_axes(&axes, rank)?;
+    let in_strides = strides(&x.shape);
+    let mut shape = x.shape.clone();
+    let mut walk: Vec<isize> = in_strides.iter().map(|&s| s as isize).collect();
+    let mut offset = 0usize;
+    for (i, &axis) in axes.iter().enumerate() {
+        let step = steps[i];
+        let (start, len) = slice_range(x.shape[axis] as i64, starts[i], ends[i], step)?;
+        shape[axis] = len as usize;
+        // a single element never moves along the axis, so a huge step is harmless
+        walk[axis] = if len > 1 {
+            in_strides[axis] as isize * step as isize
+        } else {
+            0
+        };
+        if len > 0 {
+            offset += start as usize * in_strides[axis];
+        }
+    }
+    let positions = map_positions(&shape, offset, &walk);
+    Ok(x.with(shape, x.buffer.take(&positions)))
+}
+
+fn matmul(a: &Value, b: &Value) -> Result<Value, String> {
+    let (x, y) = (a.floats()?, b.floats()?);
+    if a.shape.is_empty() || b.shape.is_empty() {
+        return Err("inputs must not be scalars".to_string());
+    }
+    // 1-D operands are promoted to matrices and the added dim removed afterwards
+    let mut a_shape = a.shape.clone();
+    if a_shape.len() == 1 {
+        a_shape.insert(0, 1);
+    }
+    let mut b_shape = b.shape.clone();
+    if b_shape.len() == 1 {
+        b_shape.push(1);
+    }
+    let (a_batch, a_mat) = a_shape.split_at(a_shape.len() - 2);
+    let (b_batch, b_mat) = b_shape.split_at(b_shape.len() - 2);
+    let (m, k, n) = (a_mat[0], a_mat[1], b_mat[1]);
+    if b_mat[0] != k {
+        return Err(format!("cannot multiply {:?} by {:?}", a.shape, b.shape));
+    }
+
+    let batch = broadcast_shapes(&[a_batch, b_batch])?;
+    let a_pos = broadcast_positions(&batch, a_batch);
+    let b_pos = broadcast_positions(&batch, b_batch);
+    let mut out = vec![0.0f32; a_pos.len() * m * n];
+    for (bi, (&pa, &pb)) in a_pos.iter().zip(&b_pos).enumerate() {
+        let (a_off, b_off, o_off) = (pa * m * k, pb * k * n, bi * m * n);
+        for i in 0..m {
+            for p in 0..k {
+                let av = x[a_off + i * k + p];
+                let row = &y[b_off + p * n..b_off + (p + 1) * n];
+                for (o, &bv) in out[o_off + i * n..o_off + (i + 1) * n].iter_mut().zip(row) {
+                    *o += av * bv;
+                }
+            }
+        }
+    }
+
+    let mut shape = batch;
+    if a.shape.len() > 1 {
+        shape.push(m);
+    }
+    if b.shape.len() > 1 {
+        shape.push(n);
+    }
+    Ok(a.with(shape, Buffer::Float(out)))
+}
+
+fn gemm(args: &Args) -> Result<Value, String> {
+    let (a, b) = (args.get(0)?, args.get(1)?);
+    if a.shape.len() != 2 || b.shape.len() != 2 {
+        return Err("A and B must be 2-D".to_string());
+    }
+    let trans_a = args.int_attr("transA", 0) != 0;
+    let trans_b = args.int_attr("transB", 0) != 0;
+    let alpha = args.float_attr("alpha", 1.0);
+    let beta = args.float_attr("beta", 1.0);
+    let (m, k) = if trans_a {
+        (a.shape[1], a.shape[0])
+    } else {
+        (a.shape[0], a.shape[1])
+    };
+    let (kb, n) = if trans_b {
+        (b.shape[1], b.shape[0])
+    } else {
+        (b.shape[0], b.shape[1])
+    };
+    if k != kb {
+        return Err(format!("cannot multiply {:?} by {:?}", a.shape, b.shape));
+    }
+    let (x, y) = (a.floats()?, b.floats()?);
+    let at = |i: usize, p: usize| if trans_a { x[p * m + i] } else { x[i * k + p] };
+    let bt = |p: usize, j: usize| if trans_b { y[j * k + p] } else { y[p * n + j] };
+
+    let bias = match args.optional(2) {
+        Some(c) => {
+            if broadcast_shapes(&[&[m, n], &c.shape])? != [m, n] {
+                return Err(format!(
+                    "C of shape {:?} does not broadcast to output",
+                    c.shape
+                ));
+            }
+            let values = c.floats()?;
+            Some((values, broadcast_positions(&[m, n], &c.shape)))
+        }
+        None => None,
+    };
+    let mut out = Vec::with_capacity(m * n);
+    for i in 0..m {
+        for j in 0..n {
+            let sum: f32 = (0..k).map(|p| at(i, p) * bt(p, j)).sum();
+            let c = bias.as_ref().map_or(0.0, |(v, pos)| v[pos[i * n + j]]);
+            out.push(alpha * sum + beta * c);
+        }
+    }
+    Ok(a.with(vec![m, n], Buffer::Float(out)))
+}
+
+/// Sliding window geometry for Conv and pooling, with 1-D lifted to 2-D
+struct Window {
+    input: [usize; 2],
+    kernel: [usize; 2],
+    strides: [usize; 2],
+    dilations: [usize; 2],
+    pads_begin: [usize; 2],
+    pads_end: [usize; 2],
+    output: [usize; 2],
+}
+
+impl Window {
+    fn new(
+        op: &OnnxOperation,
+        spatial: &[usize],
+        kernel: &[usize],
+        ceil_mode: bool,
+    ) -> Result<Self, OpError> {
+        let n = spatial.len();
+        if n == 0 || n > 2 {
+            return Err(OpError::Unsupported(format!("{}-D windows", n)));
+        }
+        if kernel.len() != n {
+            return Err("kernel does not match spatial rank".into());
+        }
+        let ints = |name: &str, default: usize, len: usize| -> Result<Vec<usize>, String> {
+            match op.get_ints_attribute(name) {
+                Some(v) if v.len() == len => to_shape(v),
+                Some(_) => Err(format!("'{}' has the wrong length", name)),
+                None => Ok(vec![default; len]),
+            }
+        };
+        let strides = ints("strides", 1, n)?;
+        let dilations = ints("dilations", 1, n)?;
+        let pads = ints("pads", 0, 2 * n)?;
+        let lift = |v: &[usize], fill: usize| {
+            if v.len() == 1 {
+                [fill, v[0]]
+            } else {
+                [v[0], v[1]]
+            }
+        };
+
+        let mut w = Window {
+            input: lift(spatial, 1),
+            kernel: lift(kernel, 1),
+            strides: lift(&strides, 1),
+            dilations: lift(&dilations, 1),
+            pads_begin: lift(&pads[..n], 0),
+            pads_end: lift(&pads[n..], 0),
+            output: [0; 2],
+        };
+        let auto_pad = op.get_string_attribute("auto_pad").unwrap_or("NOTSET");
+        for i in 0..2 {
+            let (input, stride) = (w.input[i], w.strides[i]);
+            if w.kernel[i] == 0 || stride == 0 || w.dilations[i] == 0 {
+                return Err("kernel, strides and dilations must be positive".into());
+            }
+            let effective = w.dilations[i] * (w.kernel[i] - 1) + 1;
+            w.output[i] = match auto_pad {
+                "SAME_UPPER" | "SAME_LOWER" => {
+                    let out = input.div_ceil(stride);
+                    let total = (out.saturating_sub(1) * stride + effective).saturating_sub(input);
+                    let (small, large) = (total / 2, total - total / 2);
+                    (w.pads_begin[i], w.pads_end[i]) = if auto_pad == "SAME_UPPER" {
+                        (small, large)
+                    } else {
+                        (large, small)
+                    };
+                    out
+                }
+                "VALID" => {
+                    (w.pads_begin[i], w.pads_end[i]) = (0, 0);
+                    input
+                        .checked_sub(effective)
+                        .map_or(0, |span| span / stride + 1)
+                }
+                "NOTSET" => {
+                    let padded = input + w.pads_begin[i] + w.pads_end[i];
+                    let span = padded
+                        .checked_sub(effective)
+                        .ok_or("kernel is larger than the padded input")?;
+                    if ceil_mode {
+                        let mut out = span.div_ceil(stride) + 1;
+                        // the last window must start inside the input or left padding
+                        if (out - 1) * stride >= input + w.pads_begin[i] {
+                            out -= 1;
+                        }
+                        out
+                    } else {
+                        span / stride + 1
+                    }
+                }
+                other => return Err(format!("unknown auto_pad '{}'", other).into()),
+            };
+        }
+        Ok(w)
+    }
+
+    /// Input coordinate for an output position and kernel offset, None in padding
+    fn source(&self, axis: usize, out: usize, k: usize) -> Option<usize> {
+        let pos = out * self.strides[axis] + k * self.dilations[axis];
+        pos.checked_sub(self.pads_begin[axis])
+            .filter(|&p| p < self.input[axis])
+    }
+
+    /// Whether a kernel tap falls inside the input or its explicit padding
+    fn in_padded(&self, axis: usize, out: usize, k: usize) -> bool {
+        let pos = out * self.strides[axis] + k * self.dilations[axis];
+        pos < self.pads_begin[axis] + self.input[axis] + self.pads_end[axis]
+    }
+
+    /// Output shape, with the lifted dim dropped again for 1-D windows
+    fn output_shape(&self, leading: &[usize], spatial_rank: usize) -> Vec<usize> {
+        let mut shape = leading.to_vec();
+        shape.extend(&self.output[2 - spatial_rank..]);
+        shape
+    }
+}
+
+fn conv(args: &Args) -> Result<Value, OpError> {
+    let (x, w) = (args.get(0)?, args.get(1)?);
+    if x.shape.len() < 3 || w.shape.len() != x.shape.len() {
+        return Err("input and weight ranks do not match".into());
+    }
+    let (xs, ws) = (x.floats()?, w.floats()?);
+    let (batch, channels, maps) = (x.shape[0], x.shape[1], w.shape[0]);
+    let group = usize::try_from(args.int_attr("group", 1))
+        .ok()
+        .filter(|&g| g > 0)
+        .ok_or("group must be positive")?;
+    if channels % group != 0 || maps % group != 0 || w.shape[1] * group != channels {
+        return Err("channels do not match the group count".into());
+    }
+    let bias = match args.optional(2) {
+        Some(b) if b.buffer.len() == maps => Some(b.floats()?),
+        Some(_) => return Err("bias length does not match output channels".into()),
+        None => None,
+    };
+
+    let spatial_rank = x.shape.len() - 2;
+    let win = Window::new(args.op, &x.shape[2..], &w.shape[2..], false)?;
+    let [ih, iw] = win.input;
+    let [oh, ow] = win.output;
+    let [kh, kw] = win.kernel;
+    let (cg, mg) = (channels / group, maps / group);
+
+    let mut out = Vec::with_capacity(batch * maps * oh * ow);
+    for b in 0..batch {
+        for m in 0..maps {
+            let g = m / mg;
+            for oy in 0..oh {
+                for ox in 0..ow {
+                    let mut acc = bias.map_or(0.0, |bias| bias[m]);
+                    for ic in 0..cg {
+                        let x_plane = (b * channels + g * cg + ic) * ih;
+                        let w_plane = (m * cg + ic) * kh;
+                        for ky in 0..kh {
+                            let Some(y) = win.source(0, oy, ky) else {
+                                continue;
+                            };
+                            for kx in 0..kw {
+                                if let Some(xx) = win.source(1, ox, kx) {
+                                    acc +=
+                                        xs[(x_plane + y) * iw + xx] * ws[(w_plane + ky) * kw + kx];
+                                }
+                            }
+                        }
+                    }
+                    out.push(acc);
+                }
+            }
+        }
+    }
+    let shape = win.output_shape(&[batch, maps], spatial_rank);
+    Ok(x.with(shape, Buffer::Float(out)))
+}
+
+fn pool(args: &Args) -> OpResult {
+    let x = args.get(0)?;
+    if x.shape.len() < 3 {
+        return Err("input must have rank >= 3".into());
+    }
+    let xs = x.floats()?;
+    let kernel = to_shape(
+        args.op
+            .get_ints_attribute("kernel_shape")
+            .ok_or("missing 'kernel_shape' attribute")?,
+    )?;
+    let ceil_mode = args.int_attr("ceil_mode", 0) != 0;
+    let include_pad = args.int_attr("count_include_pad", 0) != 0;
+    let is_max = args.op.op_type == "MaxPool";
+
+    let spatial_rank = x.shape.len() - 2;
+    let win = Window::new(args.op, &x.shape[2..], &kernel, ceil_mode)?;
+    let [ih, iw] = win.input;
+    let [oh, ow] = win.output;
+    let [kh, kw] = win.kernel;
+    let planes = x.shape[0] * x.shape[1];
+
+    let mut out = Vec::with_capacity(planes * oh * ow);
+    let mut indices = Vec::with_capacity(if is_max { out.capacity() } else { 0 });
+    for plane in 0..planes {
+        for oy in 0..oh {
+            for ox in 0..ow {
+                let (mut best, mut best_at) = (f32::NEG_INFINITY, 0);
+                let (mut sum, mut count) = (0.0f32, 0usize);
+                for ky in 0..kh {
+                    for kx in 0..kw {
+                        match (win.source(0, oy, ky), win.source(1, ox, kx)) {
+                            (Some(y), Some(xx)) => {
+                                let at = (plane * ih + y) * iw + xx;
+                                if xs[at] > best {
+                                    (best, best_at) = (xs[at], at);
+                                }
+                                sum += xs[at];
+                                count += 1;
+                            }
+                            _ if include_pad
+                                && win.in_padded(0, oy, ky)
+                                && win.in_padded(1, ox, kx) =>
+                            {
+                                count += 1;
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                if is_max {
+                    out.push(best);
+                    indices.push(best_at as i64);
+                } else {
+                    out.push(if count == 0 { 0.0 } else { sum / count as f32 });
+                }
+            }
+        }
+    }
+    let shape = win.output_shape(&x.shape[..2], spatial_rank);
+    let mut outputs = vec![x.with(shape.clone(), Buffer::Float(out))];
+    if is_max {
+        outputs.push(Value::int(shape, indices));
+    }
+    Ok(outputs)
+}
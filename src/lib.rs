@@ -11,13 +11,18 @@
 //!
 //! `OnnxTensor::data()` borrows tensor data without copying the underlying bytes.
 //! Raw and Strings variants increment Arc refcounts, Numeric borrows directly.
+//! Types narrower than their typed proto field (e.g. Int8 in `int32_data`) are
+//! copied into the same packed layout `raw_data` would use.
 //!
 //! `OnnxTensor::into_data()` returns owned data:
 //! - Raw returns Arc-backed bytes
-//! - Numeric performs zero-copy reinterpretation from typed fields
+//! - Numeric performs zero-copy reinterpretation from typed fields where the
+//!   field width matches the element type
 //! - Strings returns Arc-backed elements
 //!
-//! Endianness: Multi-byte interpretations assume little-endian platforms.
+//! Endianness: tensor bytes are always little-endian, as in ONNX `raw_data`.
+//! On big-endian hosts typed fields are byte-swapped into a new buffer and
+//! `copy_data_as` swaps each element into host order.
 //!
 //! ## Quick Start
 //!
@@ -42,19 +47,44 @@ mod onnx_generated {
     include!(concat!(env!("OUT_DIR"), "/onnx.rs"));
 }
 
+#[cfg(feature = "ndarray")]
+mod array;
+pub(crate) mod convert;
 pub(crate) mod proto_adapter;
 pub(crate) use onnx_generated::*;
 
+pub mod element;
 pub mod error;
 pub mod external_data;
+pub mod function;
+pub mod graph;
+mod interpreter;
 pub mod model;
 pub mod operation;
+pub mod packed;
+pub mod quantization;
+pub mod shape_inference;
+pub mod sparse;
 pub mod tensor;
+pub mod training;
 pub mod types;
+pub mod validate;
 
+pub use element::OnnxElement;
 pub use error::Error;
-pub use model::OnnxModel;
+pub use external_data::CachePolicy;
+pub use function::OnnxFunction;
+pub use graph::{GraphIndex, GraphScope, OnnxGraph, OpId, TensorId, Walk};
+pub use model::{LoadOptions, OnnxModel};
+#[cfg(feature = "ndarray")]
+pub use ndarray;
 pub use operation::OnnxOperation;
+pub use packed::PackedElements;
 pub use prost::bytes::Bytes;
+pub use quantization::QuantParams;
+pub use shape_inference::{ShapeInferenceReport, SkippedOperation};
+pub use sparse::OnnxSparseTensor;
 pub use tensor::{OnnxTensor, TensorData};
-pub use types::{AttributeValue, DataType};
+pub use training::OnnxTrainingInfo;
+pub use types::{AttributeType, AttributeValue, DataType, Dim, OpsetImport, ValueType};
+pub use validate::{Diagnostic, Location, Severity};
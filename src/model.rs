@@ -1,31 +1,86 @@
 use prost::Message;
 use prost::bytes::Bytes;
 use std::collections::{HashMap, HashSet, VecDeque};
-use std::fs::File;
-use std::io::Read;
+use std::fs;
 use std::path::{Path, PathBuf};
-use std::rc::Rc;
-
-use crate::external_data::ExternalDataLoader;
-use crate::{Error, ModelProto, OnnxOperation, OnnxTensor, proto_adapter, type_proto};
+use std::sync::Arc;
+
+use crate::external_data::{self, CachePolicy, ExternalDataLoader};
+use crate::function;
+use crate::graph::GraphParts;
+use crate::interpreter;
+use crate::operation::is_default_domain;
+use crate::quantization::QuantParams;
+use crate::shape_inference::{self, ShapeInferenceReport};
+use crate::validate::{self, Diagnostic};
+use crate::{
+    Error, GraphIndex, GraphScope, ModelProto, OnnxFunction, OnnxOperation, OnnxSparseTensor,
+    OnnxTensor, OnnxTrainingInfo, OpId, OpsetImport, ValueType, proto_adapter,
+};
+
+/// Options controlling how a model and its external data are loaded
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    /// Memory-map the model file and external data files instead of reading
+    /// them into memory
+    ///
+    /// Tensor `Bytes` then point straight into mapped pages, so
+    /// `TensorData::Raw` stays zero-copy from disk and peak memory does not
+    /// double for large checkpoints. Requires the `mmap` feature.
+    ///
+    /// Mapped files must not be modified or truncated while the model or any
+    /// data borrowed from it is alive.
+    pub mmap: bool,
+    /// How external data is read and cached, see [`CachePolicy`]
+    pub cache_policy: CachePolicy,
+}
 
 /// Main ONNX model container
 pub struct OnnxModel {
     pub tensors: HashMap<String, OnnxTensor>,
+    pub sparse_tensors: HashMap<String, OnnxSparseTensor>,
     pub operations: Vec<OnnxOperation>,
     pub inputs: Vec<String>,
     pub outputs: Vec<String>,
+    /// Initializers also listed as graph inputs, so callers may override
+    /// them; `inputs` leaves these out
+    pub initializer_inputs: Vec<String>,
+    /// Declared types of main graph values that are not dense tensors, such
+    /// as sequences, maps, optionals and sparse tensors
+    pub value_types: HashMap<String, ValueType>,
+    /// Main graph tensor name to annotation keys such as `SCALE_TENSOR`,
+    /// each naming a parameter tensor, see [`quant_params`](Self::quant_params)
+    pub quantization_annotation: HashMap<String, HashMap<String, String>>,
     pub model_version: i64,
     pub producer_name: String,
     pub producer_version: String,
+    pub graph_name: String,
+    pub ir_version: i64,
+    pub opset_import: Vec<OpsetImport>,
+    pub domain: String,
+    pub doc_string: String,
+    pub metadata_props: HashMap<String, String>,
+    /// Model-local functions that nodes may call, see [`OnnxFunction`]
+    pub functions: Vec<OnnxFunction>,
+    /// Training steps for on-device training, empty for inference-only models
+    pub training_info: Vec<OnnxTrainingInfo>,
 }
 
 impl OnnxModel {
     /// Load ONNX model from file path
     pub fn load_from_file(path: &str) -> Result<Self, Error> {
-        let mut file = File::open(path)?;
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)?;
+        Self::load_from_file_with_options(path, &LoadOptions::default())
+    }
+
+    /// Load ONNX model from file path with custom loading options
+    pub fn load_from_file_with_options(path: &str, options: &LoadOptions) -> Result<Self, Error> {
+        if options.mmap && !cfg!(feature = "mmap") {
+            return Err(Error::Unsupported(
+                "memory-mapped loading requires the `mmap` feature".to_string(),
+            ));
+        }
+
+        let buffer = external_data::read_file(Path::new(path), options.mmap)?;
 
         // Extract model directory for external data loading
         let model_dir = Path::new(path)
@@ -33,116 +88,199 @@ impl OnnxModel {
             .map(|p| p.to_path_buf())
             .unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
 
-        Self::load_from_bytes_with_dir(buffer, Some(model_dir))
+        Self::load_from_bytes_with_dir(buffer, Some(model_dir), options)
     }
 
     /// Load ONNX model from owned byte vector
     pub fn load_from_bytes(data: Vec<u8>) -> Result<Self, Error> {
-        Self::load_from_bytes_with_dir(data, None)
+        Self::load_from_bytes_with_dir(Bytes::from(data), None, &LoadOptions::default())
     }
 
-    /// Load ONNX model from owned byte vector with optional model directory for external data
-    fn load_from_bytes_with_dir(data: Vec<u8>, model_dir: Option<PathBuf>) -> Result<Self, Error> {
-        let model = ModelProto::decode(Bytes::from(data))?;
-        let mut graph = model
+    /// Load ONNX model from bytes with optional model directory for external data
+    ///
+    /// Bytes-typed proto fields such as `raw_data` are slices of `data`, so a
+    /// memory-mapped buffer is never copied.
+    fn load_from_bytes_with_dir(
+        data: Bytes,
+        model_dir: Option<PathBuf>,
+        options: &LoadOptions,
+    ) -> Result<Self, Error> {
+        let model = ModelProto::decode(data)?;
+        let graph = model
             .graph
             .ok_or_else(|| Error::InvalidModel("No graph found in model".to_string()))?;
 
         // Create external data loader if model directory is available
-        // Tensors keep the loader alive via Rc as long as they need it
-        let external_data_loader = model_dir.map(|dir| Rc::new(ExternalDataLoader::new(dir)));
-
-        let mut onnx_model = OnnxModel {
-            tensors: HashMap::new(),
-            operations: Vec::new(),
-            inputs: Vec::new(),
-            outputs: Vec::new(),
+        // Tensors keep the loader alive via Arc as long as they need it
+        let external_data_loader = model_dir.map(|dir| {
+            Arc::new(ExternalDataLoader::new(
+                dir,
+                options.mmap,
+                options.cache_policy,
+            ))
+        });
+
+        let graph = proto_adapter::graph_from_proto(graph, external_data_loader.as_ref())?;
+        let functions = model
+            .functions
+            .into_iter()
+            .map(|f| proto_adapter::function_from_proto(f, external_data_loader.as_ref()))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let training_info = model
+            .training_info
+            .into_iter()
+            .map(|t| proto_adapter::training_info_from_proto(t, external_data_loader.as_ref()))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(OnnxModel {
+            tensors: graph.tensors,
+            sparse_tensors: graph.sparse_tensors,
+            operations: graph.operations,
+            inputs: graph.inputs,
+            outputs: graph.outputs,
+            initializer_inputs: graph.initializer_inputs,
+            value_types: graph.value_types,
+            quantization_annotation: graph.quantization_annotation,
             model_version: model.model_version.unwrap_or(0),
             producer_name: model.producer_name.unwrap_or_default(),
             producer_version: model.producer_version.unwrap_or_default(),
-        };
-
-        // pre-allocate based on graph sizes to avoid repeated reallocations
-        onnx_model.tensors.reserve(
-            graph.initializer.len()
-                + graph.value_info.len()
-                + graph.input.len()
-                + graph.output.len(),
-        );
-        onnx_model.operations.reserve(graph.node.len());
-        onnx_model.inputs.reserve(graph.input.len());
-        onnx_model.outputs.reserve(graph.output.len());
-
-        // parse initialiser tensors (weights/constants) by draining to avoid clones
-        for tensor in graph.initializer.drain(..) {
-            let onnx_tensor =
-                proto_adapter::tensor_from_proto(tensor, external_data_loader.clone())?;
-            let tensor_name = onnx_tensor.name().to_string();
-            if !tensor_name.is_empty() {
-                onnx_model.tensors.insert(tensor_name, onnx_tensor);
-            }
-        }
+            graph_name: graph.name,
+            ir_version: model.ir_version.unwrap_or(0),
+            opset_import: proto_adapter::opset_imports_from_proto(model.opset_import),
+            domain: model.domain.unwrap_or_default(),
+            doc_string: model.doc_string.unwrap_or_default(),
+            metadata_props: proto_adapter::metadata_from_proto(model.metadata_props),
+            functions,
+            training_info,
+        })
+    }
 
-        // parse input tensor info and extract input names
-        for input in graph.input.drain(..) {
-            let name = input.name.clone().unwrap_or_default();
-            if name.is_empty() {
-                continue;
-            }
+    /// Serialize the model to ONNX protobuf bytes
+    ///
+    /// The `ModelProto` is rebuilt from the crate-native fields, so edits to
+    /// `tensors`, `operations`, `inputs` and `outputs` are reflected in the
+    /// output. Tensors with data are written as initializers keyed by their
+    /// map name, and external data is inlined as `raw_data`.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let proto = proto_adapter::model_to_proto(self)?;
+        Ok(proto.encode_to_vec())
+    }
 
-            // If the name is already in tensors, it's an initialiser, so we skip adding it to inputs
-            if !onnx_model.tensors.contains_key(&name) {
-                onnx_model.inputs.push(name.clone());
-            }
+    /// Serialize the model and write it to a file path
+    pub fn save_to_file(&self, path: &str) -> Result<(), Error> {
+        fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
 
-            if let Some(t) = &input.r#type
-                && let Some(type_proto_value) = &t.value
-                && let type_proto::Value::TensorType(tensor_type) = type_proto_value
-            {
-                let onnx_tensor = OnnxTensor::from_tensor_type(name.clone(), tensor_type)?;
-                onnx_model.tensors.entry(name).or_insert(onnx_tensor);
-            }
-        }
+    /// Get the imported opset version for a domain
+    ///
+    /// The empty domain and `ai.onnx` are treated as the same default domain.
+    pub fn opset_version(&self, domain: &str) -> Option<i64> {
+        let default = is_default_domain(domain);
+        self.opset_import
+            .iter()
+            .find(|opset| opset.domain == domain || (default && is_default_domain(&opset.domain)))
+            .map(|opset| opset.version)
+    }
 
-        // parse value_info for intermediate tensor shapes and types
-        for value_info in graph.value_info.drain(..) {
-            if let Some(t) = &value_info.r#type
-                && let Some(type_proto_value) = &t.value
-                && let type_proto::Value::TensorType(tensor_type) = type_proto_value
-            {
-                let name = value_info.name.unwrap_or_default();
-                if !name.is_empty() {
-                    let onnx_tensor = OnnxTensor::from_tensor_type(name.clone(), tensor_type)?;
-                    onnx_model.tensors.entry(name).or_insert(onnx_tensor);
-                }
-            }
+    /// Get the effective opset version for an operation's domain
+    pub fn operation_opset_version(&self, op: &OnnxOperation) -> Option<i64> {
+        self.opset_version(&op.domain)
+    }
+
+    /// Name resolution scope for the main graph
+    ///
+    /// Use [`GraphScope::enter`] to resolve names inside subgraphs, which may
+    /// refer to tensors defined in enclosing graphs.
+    pub fn scope(&self) -> GraphScope<'_> {
+        GraphScope::new(&self.tensors)
+    }
+
+    /// Borrowed view of the main graph
+    pub(crate) fn graph_parts(&self) -> GraphParts<'_> {
+        GraphParts {
+            name: &self.graph_name,
+            tensors: &self.tensors,
+            sparse_tensors: &self.sparse_tensors,
+            operations: &self.operations,
+            inputs: &self.inputs,
+            outputs: &self.outputs,
+            initializer_inputs: &self.initializer_inputs,
+            value_types: &self.value_types,
+            quantization_annotation: &self.quantization_annotation,
         }
+    }
 
-        // parse output tensor info and extract output names
-        for output in graph.output.drain(..) {
-            let name = output.name.clone().unwrap_or_default();
-            if name.is_empty() {
-                continue;
-            }
+    /// Check the model for structural problems, similar to `onnx.checker`
+    ///
+    /// Covers the main graph and all subgraphs. An empty result means no
+    /// problems were found; see [`Diagnostic`] for the details reported.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        validate::validate_model(self)
+    }
 
-            onnx_model.outputs.push(name.clone());
+    /// Infer types and shapes of intermediate tensors in the main graph
+    ///
+    /// Walks [`execution_order`](Self::execution_order) and adds shape-only
+    /// tensors for operation outputs, or fills in unknown dims of existing
+    /// ones. Shapes that depend on data use constant initializers and
+    /// `Constant`/`Shape` results where possible. Operations that could not
+    /// be inferred are listed in the report.
+    pub fn infer_shapes(&mut self) -> Result<ShapeInferenceReport, Error> {
+        shape_inference::infer_model(self)
+    }
 
-            if let Some(t) = &output.r#type
-                && let Some(type_proto_value) = &t.value
-                && let type_proto::Value::TensorType(tensor_type) = type_proto_value
-            {
-                let onnx_tensor = OnnxTensor::from_tensor_type(name.clone(), tensor_type)?;
-                onnx_model.tensors.entry(name).or_insert(onnx_tensor);
-            }
-        }
+    /// Get a model-local function by domain and name
+    ///
+    /// The empty domain and `ai.onnx` are treated as the same default domain.
+    /// When overloads share the name the first one is returned, see
+    /// [`called_function`](Self::called_function).
+    pub fn get_function(&self, domain: &str, name: &str) -> Option<&OnnxFunction> {
+        self.functions.iter().find(|f| {
+            f.name == name
+                && (f.domain == domain || is_default_domain(&f.domain) && is_default_domain(domain))
+        })
+    }
 
-        // parse operations/nodes by draining to allow owned conversion
-        for node in graph.node.drain(..) {
-            let operation = OnnxOperation::from_node_proto(node)?;
-            onnx_model.operations.push(operation);
-        }
+    /// Get the model-local function an operation calls, if any
+    pub fn called_function(&self, op: &OnnxOperation) -> Option<&OnnxFunction> {
+        self.functions.iter().find(|f| f.is_called_by(op))
+    }
+
+    /// Replace calls to model-local functions with the function bodies
+    ///
+    /// Applies to the main graph and all subgraphs, and to calls nested
+    /// inside function bodies. Body values and node names are prefixed with
+    /// the calling node's name (or the function name), plus a numeric suffix
+    /// when needed to keep them unique. Attributes referring to the caller
+    /// take its value or the function default, and are dropped if neither
+    /// is set. Opsets imported by inlined functions are added to the model
+    /// when missing. `functions` itself is left unchanged.
+    ///
+    /// Returns `Error::InvalidModel`, leaving the model unchanged, if an
+    /// inlined function imports a different version of a domain than the
+    /// model or another inlined function.
+    ///
+    /// Operation ids from an earlier [`graph_index`](Self::graph_index) no
+    /// longer apply afterwards.
+    ///
+    /// Returns the number of calls that were expanded.
+    pub fn inline_functions(&mut self) -> Result<usize, Error> {
+        function::inline_model(self)
+    }
 
-        Ok(onnx_model)
+    /// Evaluate the main graph on the CPU and return its outputs by name
+    ///
+    /// `inputs` are keyed by graph input name; initializers supply any
+    /// inputs left out. This is a simple reference implementation covering
+    /// common operators, meant for golden tests rather than speed. Float
+    /// outputs other than Double are returned as Float. Operators the
+    /// interpreter does not implement fail with [`Error::Unsupported`].
+    pub fn run(
+        &self,
+        inputs: &HashMap<String, OnnxTensor>,
+    ) -> Result<HashMap<String, OnnxTensor>, Error> {
+        interpreter::run_model(self, inputs)
     }
 
     /// Get tensor information by name
@@ -150,48 +288,191 @@ impl OnnxModel {
         self.tensors.get(name)
     }
 
+    /// Get sparse initializer by name
+    pub fn get_sparse_tensor(&self, name: &str) -> Option<&OnnxSparseTensor> {
+        self.sparse_tensors.get(name)
+    }
+
     /// Get all operations of a specific type
+    ///
+    /// A bare type such as `Conv` matches default-domain operations only;
+    /// other domains are selected with `domain::op_type`, e.g.
+    /// `com.microsoft::Attention`.
+    ///
+    /// Earlier versions matched a bare type in every domain. Code relying on
+    /// that should filter [`operations`](Self::operations) on `op_type`
+    /// directly.
     pub fn get_operations_by_type(&self, op_type: &str) -> Vec<&OnnxOperation> {
         self.operations
             .iter()
-            .filter(|op| op.op_type == op_type)
+            .filter(|op| op.is_qualified_type(op_type))
             .collect()
     }
 
     /// Get operation by name
+    ///
+    /// Returns the first match of a linear scan. Node names are optional and
+    /// may repeat; use [`graph_index`](Self::graph_index) for constant-time
+    /// lookups that detect duplicates.
     pub fn get_operation(&self, name: &str) -> Option<&OnnxOperation> {
         self.operations.iter().find(|op| op.name == name)
     }
 
+    /// Get operation by id
+    ///
+    /// The id must come from an index built since the last edit of the
+    /// operation list, otherwise it may name a different operation.
+    pub fn operation(&self, id: OpId) -> Option<&OnnxOperation> {
+        self.operations.get(id.index())
+    }
+
     /// Get all tensor names
     pub fn tensor_names(&self) -> Vec<&String> {
         self.tensors.keys().collect()
     }
 
+    /// Map each symbolic dimension name to the (tensor, axis) pairs using it
+    ///
+    /// Tensors listed under the same name share that dimension and must be
+    /// bound to the same size at runtime. Entries are sorted by tensor name.
+    pub fn symbolic_dims(&self) -> HashMap<&str, Vec<(&str, usize)>> {
+        let mut symbols: HashMap<&str, Vec<(&str, usize)>> = HashMap::new();
+        for (name, tensor) in &self.tensors {
+            for (axis, dim) in tensor.dims().iter().enumerate() {
+                if let Some(sym) = dim.symbol() {
+                    symbols.entry(sym).or_default().push((name.as_str(), axis));
+                }
+            }
+        }
+        for uses in symbols.values_mut() {
+            uses.sort_unstable();
+        }
+        symbols
+    }
+
     /// Get all operation types in the model
+    ///
+    /// Types outside the default domain are qualified as `domain::op_type`,
+    /// see [`OnnxOperation::qualified_type`].
     pub fn operation_types(&self) -> Vec<String> {
         // collect unique operation types using a hash set of &str to avoid
         // allocating intermediate owned Strings, then sort the resulting Vec
-        let mut set: HashSet<&str> = HashSet::with_capacity(self.operations.len());
+        let mut set: HashSet<(&str, &str)> = HashSet::with_capacity(self.operations.len());
         for op in &self.operations {
-            set.insert(op.op_type.as_str());
+            let domain = if op.is_default_domain() {
+                ""
+            } else {
+                &op.domain
+            };
+            set.insert((domain, op.op_type.as_str()));
         }
-        let mut op_types: Vec<String> = set.into_iter().map(|s| s.to_string()).collect();
+        let mut op_types: Vec<String> = set
+            .into_iter()
+            .map(|(domain, op_type)| match domain {
+                "" => op_type.to_string(),
+                _ => format!("{}::{}", domain, op_type),
+            })
+            .collect();
         op_types.sort();
         op_types
     }
 
     /// Count operations by type
+    ///
+    /// Keys use the same `domain::op_type` qualification as
+    /// [`operation_types`](Self::operation_types).
     pub fn count_operations_by_type(&self) -> HashMap<String, usize> {
         let mut counts = HashMap::new();
         counts.reserve(self.operations.len());
         for op in &self.operations {
-            *counts.entry(op.op_type.clone()).or_insert(0) += 1;
+            *counts.entry(op.qualified_type()).or_insert(0) += 1;
         }
         counts
     }
 
+    /// Get the type of a named value in the main graph
+    ///
+    /// Covers graph inputs, outputs, value_info entries and initializers of
+    /// any kind. Dense tensors report their current dims, so shapes filled in
+    /// by [`infer_shapes`](Self::infer_shapes) are included.
+    pub fn value_type(&self, name: &str) -> Option<ValueType> {
+        self.graph_parts().value_type(name)
+    }
+
+    /// Get the quantization parameters annotated on a main graph tensor
+    ///
+    /// Resolves the `SCALE_TENSOR` and `ZERO_POINT_TENSOR` entries of the
+    /// tensor's `quantization_annotation` to initializers. Returns None when
+    /// the tensor has no annotation, the annotation has no scale, or a named
+    /// parameter tensor is not an initializer; [`validate`](Self::validate)
+    /// reports the latter two.
+    pub fn quant_params(&self, name: &str) -> Option<QuantParams<'_>> {
+        self.graph_parts().quant_params(name)
+    }
+
+    /// Names of trainable parameters, sorted
+    ///
+    /// These are main graph initializers that some `update_binding` assigns
+    /// and that the main graph consumes, e.g. weights and biases.
+    pub fn trainable_parameters(&self) -> Vec<&str> {
+        let consumed: HashSet<&str> = self
+            .operations
+            .iter()
+            .flat_map(|op| op.inputs.iter().map(String::as_str))
+            .collect();
+        let mut names: Vec<&str> = self
+            .updated_initializers()
+            .filter(|name| consumed.contains(name))
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// Names of optimizer state tensors, sorted
+    ///
+    /// These are initializers that some `update_binding` assigns but the main
+    /// graph does not consume, such as momentum buffers or step counters.
+    /// They may live in the main graph or in the binding entry's `algorithm`
+    /// graph. Targets that are not initializers are left out, see
+    /// [`validate`](Self::validate).
+    pub fn optimizer_state_tensors(&self) -> Vec<&str> {
+        let trainable: HashSet<&str> = self.trainable_parameters().into_iter().collect();
+        let mut names: Vec<&str> =
+            self.training_info
+                .iter()
+                .flat_map(|t| {
+                    t.update_binding.keys().map(String::as_str).filter(|name| {
+                        self.is_initializer(name) || t.is_algorithm_initializer(name)
+                    })
+                })
+                .filter(|name| !trainable.contains(name))
+                .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// `update_binding` targets that are main graph initializers
+    fn updated_initializers(&self) -> impl Iterator<Item = &str> {
+        self.training_info
+            .iter()
+            .flat_map(|t| t.update_binding.keys().map(String::as_str))
+            .filter(|name| self.is_initializer(name))
+    }
+
+    /// Check if a name is a dense or sparse main graph initializer
+    fn is_initializer(&self, name: &str) -> bool {
+        self.tensors
+            .get(name)
+            .is_some_and(|t| t.data_location().is_some())
+            || self.sparse_tensors.contains_key(name)
+    }
+
     /// Get input tensors
+    ///
+    /// Inputs that are not dense tensors, e.g. sequences or optionals, are
+    /// left out; use [`value_type`](Self::value_type) for those.
     pub fn get_input_tensors(&self) -> Vec<&OnnxTensor> {
         self.inputs
             .iter()
@@ -200,6 +481,9 @@ impl OnnxModel {
     }
 
     /// Get output tensors
+    ///
+    /// Outputs that are not dense tensors are left out, as for
+    /// [`get_input_tensors`](Self::get_input_tensors).
     pub fn get_output_tensors(&self) -> Vec<&OnnxTensor> {
         self.outputs
             .iter()
@@ -215,6 +499,15 @@ impl OnnxModel {
             .collect()
     }
 
+    /// Build an id and producer/consumer index over the main graph
+    ///
+    /// The index borrows the model and is rebuilt on each call, so keep it
+    /// around for repeated queries. Its ids are only valid until the
+    /// operation list changes.
+    pub fn graph_index(&self) -> GraphIndex<'_> {
+        GraphIndex::new(&self.graph_parts())
+    }
+
     /// Return operations in a simple topological order using Kahn's algorithm.
     ///
     /// The returned vector contains references into `self.operations` and
@@ -228,42 +521,13 @@ impl OnnxModel {
     /// If the graph contains cycles or there are unresolved dependencies,
     /// the function returns an `Error::InvalidModel`.
     pub fn topological_order(&self) -> Result<Vec<&OnnxOperation>, Error> {
+        let index = self.graph_index();
         let op_count = self.operations.len();
 
-        // map tensor name -> producer op index
-        let mut producer: HashMap<&str, usize> = HashMap::new();
-        for (idx, op) in self.operations.iter().enumerate() {
-            for out in &op.outputs {
-                if !out.is_empty() {
-                    producer.entry(out.as_str()).or_insert(idx);
-                }
-            }
-        }
-
-        // map tensor name -> list of consumer op indices
-        let mut consumers: HashMap<&str, Vec<usize>> = HashMap::new();
-        for (idx, op) in self.operations.iter().enumerate() {
-            for input in &op.inputs {
-                if !input.is_empty() {
-                    consumers.entry(input.as_str()).or_default().push(idx);
-                }
-            }
-        }
-
         // indegree = number of inputs coming from other ops (i.e. produced by some op)
-        let mut indegree: Vec<usize> = vec![0; op_count];
-        for (idx, op) in self.operations.iter().enumerate() {
-            let mut count = 0usize;
-            for input in &op.inputs {
-                if input.is_empty() {
-                    continue;
-                }
-                if producer.contains_key(input.as_str()) {
-                    count += 1;
-                }
-            }
-            indegree[idx] = count;
-        }
+        let mut indegree: Vec<usize> = (0..op_count)
+            .map(|idx| index.dependency_count(idx))
+            .collect();
 
         // start with ops that have indegree 0
         let mut queue: VecDeque<usize> = VecDeque::new();
@@ -284,14 +548,12 @@ impl OnnxModel {
                 if out.is_empty() {
                     continue;
                 }
-                if let Some(cons_list) = consumers.get(out.as_str()) {
-                    for &cidx in cons_list {
-                        // only decrease indegree if the dependency was counted from a producer
-                        if indegree[cidx] > 0 {
-                            indegree[cidx] -= 1;
-                            if indegree[cidx] == 0 {
-                                queue.push_back(cidx);
-                            }
+                for &cidx in index.consumer_slots(out) {
+                    // only decrease indegree if the dependency was counted from a producer
+                    if indegree[cidx] > 0 {
+                        indegree[cidx] -= 1;
+                        if indegree[cidx] == 0 {
+                            queue.push_back(cidx);
                         }
                     }
                 }
@@ -321,42 +583,13 @@ impl OnnxModel {
     /// If the graph contains cycles or there are unresolved dependencies,
     /// the function returns an `Error::InvalidModel`.
     pub fn execution_order(&self) -> Result<Vec<&OnnxOperation>, Error> {
+        let index = self.graph_index();
         let op_count = self.operations.len();
 
-        // map tensor name -> producer op index
-        let mut producer: HashMap<&str, usize> = HashMap::new();
-        for (idx, op) in self.operations.iter().enumerate() {
-            for out in &op.outputs {
-                if !out.is_empty() {
-                    producer.entry(out.as_str()).or_insert(idx);
-                }
-            }
-        }
-
-        // map tensor name -> list of consumer op indices
-        let mut consumers: HashMap<&str, Vec<usize>> = HashMap::new();
-        for (idx, op) in self.operations.iter().enumerate() {
-            for input in &op.inputs {
-                if !input.is_empty() {
-                    consumers.entry(input.as_str()).or_default().push(idx);
-                }
-            }
-        }
-
         // indegree = number of inputs coming from other ops
-        let mut indegree: Vec<usize> = vec![0; op_count];
-        for (idx, op) in self.operations.iter().enumerate() {
-            let mut count = 0usize;
-            for input in &op.inputs {
-                if input.is_empty() {
-                    continue;
-                }
-                if producer.contains_key(input.as_str()) {
-                    count += 1;
-                }
-            }
-            indegree[idx] = count;
-        }
+        let mut indegree: Vec<usize> = (0..op_count)
+            .map(|idx| index.dependency_count(idx))
+            .collect();
 
         // start with ops that have indegree 0, prioritizing those that consume model inputs
         let mut queue: VecDeque<usize> = VecDeque::new();
@@ -392,13 +625,11 @@ impl OnnxModel {
                 if out.is_empty() {
                     continue;
                 }
-                if let Some(cons_list) = consumers.get(out.as_str()) {
-                    for &cidx in cons_list {
-                        if indegree[cidx] > 0 {
-                            indegree[cidx] -= 1;
-                            if indegree[cidx] == 0 {
-                                newly_ready.push(cidx);
-                            }
+                for &cidx in index.consumer_slots(out) {
+                    if indegree[cidx] > 0 {
+                        indegree[cidx] -= 1;
+                        if indegree[cidx] == 0 {
+                            newly_ready.push(cidx);
                         }
                     }
                 }
@@ -444,6 +675,15 @@ impl OnnxModel {
             self.producer_name, self.producer_version
         );
         println!("Model Version: {}", self.model_version);
+        println!("IR Version: {}", self.ir_version);
+        for opset in &self.opset_import {
+            let domain = if opset.domain.is_empty() {
+                "ai.onnx"
+            } else {
+                &opset.domain
+            };
+            println!("Opset: {} v{}", domain, opset.version);
+        }
         println!("Inputs: {:?}", self.inputs);
         println!("Outputs: {:?}", self.outputs);
 
@@ -469,6 +709,19 @@ impl OnnxModel {
             );
         }
 
+        if !self.sparse_tensors.is_empty() {
+            println!("\n=== Sparse Tensors ({}) ===", self.sparse_tensors.len());
+            for (name, sparse) in &self.sparse_tensors {
+                println!(
+                    "  {}: {:?} ({:?}) [{} values]",
+                    name,
+                    sparse.shape(),
+                    sparse.data_type(),
+                    sparse.nnz()
+                );
+            }
+        }
+
         println!("\n=== Operations ({}) ===", self.operations.len());
         let op_counts = self.count_operations_by_type();
         for (op_type, count) in &op_counts {
@@ -480,7 +733,7 @@ impl OnnxModel {
             println!(
                 "  {} ({}): {} -> {}",
                 op.name,
-                op.op_type,
+                op.qualified_type(),
                 op.inputs.join(", "),
                 op.outputs.join(", ")
             );
@@ -506,5 +759,15 @@ impl OnnxModel {
 
         let weight_count = self.get_weight_tensors().len();
         println!("Weight tensors: {}", weight_count);
+        if !self.sparse_tensors.is_empty() {
+            println!("Sparse weight tensors: {}", self.sparse_tensors.len());
+        }
+        if !self.training_info.is_empty() {
+            println!(
+                "Training steps: {} | Trainable parameters: {}",
+                self.training_info.len(),
+                self.trainable_parameters().len()
+            );
+        }
     }
 }
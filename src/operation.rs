@@ -1,22 +1,23 @@
-use crate::{AttributeValue, Error, NodeProto, proto_adapter};
+use crate::{AttributeValue, OnnxGraph};
 use std::collections::HashMap;
 
 /// Information about an ONNX operation/node
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct OnnxOperation {
     pub name: String,
     pub op_type: String,
+    /// Operator domain, empty for the default `ai.onnx` domain
+    pub domain: String,
+    /// Selects among model-local functions sharing a domain and name
+    pub overload: String,
+    pub doc_string: String,
+    pub metadata_props: HashMap<String, String>,
     pub inputs: Vec<String>,
     pub outputs: Vec<String>,
     pub attributes: HashMap<String, AttributeValue>,
 }
 
 impl OnnxOperation {
-    /// Create OnnxOperation from ONNX NodeProto
-    pub(crate) fn from_node_proto(node: NodeProto) -> Result<Self, Error> {
-        proto_adapter::operation_from_node_proto(node)
-    }
-
     /// Get attribute by name
     pub fn get_attribute(&self, name: &str) -> Option<&AttributeValue> {
         self.attributes.get(name)
@@ -47,6 +48,30 @@ impl OnnxOperation {
         self.get_attribute(name)?.as_floats()
     }
 
+    /// Get subgraph attribute by name
+    pub fn get_graph_attribute(&self, name: &str) -> Option<&OnnxGraph> {
+        self.get_attribute(name)?.as_graph()
+    }
+
+    /// Get all subgraphs held by GRAPH and GRAPHS attributes
+    ///
+    /// Returns (attribute name, subgraph) pairs sorted by attribute name, e.g.
+    /// `else_branch` and `then_branch` for `If` or `body` for `Loop` and `Scan`.
+    pub fn subgraphs(&self) -> Vec<(&str, &OnnxGraph)> {
+        let mut subgraphs: Vec<(&str, &OnnxGraph)> = Vec::new();
+        for (name, value) in &self.attributes {
+            match value {
+                AttributeValue::Graph(g) => subgraphs.push((name, g)),
+                AttributeValue::Graphs(graphs) => {
+                    subgraphs.extend(graphs.iter().map(|g| (name.as_str(), g)));
+                }
+                _ => {}
+            }
+        }
+        subgraphs.sort_by_key(|(name, _)| *name);
+        subgraphs
+    }
+
     /// Check if operation has a specific attribute
     pub fn has_attribute(&self, name: &str) -> bool {
         self.attributes.contains_key(name)
@@ -67,8 +92,45 @@ impl OnnxOperation {
         self.op_type == op_type
     }
 
+    /// Check if this operation belongs to the default `ai.onnx` domain
+    pub fn is_default_domain(&self) -> bool {
+        is_default_domain(&self.domain)
+    }
+
+    /// Operation type qualified by its domain
+    ///
+    /// Default-domain operations give the bare `op_type`, e.g. `Conv`; others
+    /// are prefixed with the domain, e.g. `com.microsoft::Attention`.
+    pub fn qualified_type(&self) -> String {
+        if self.is_default_domain() {
+            self.op_type.clone()
+        } else {
+            format!("{}::{}", self.domain, self.op_type)
+        }
+    }
+
+    /// Check if this operation matches a domain-qualified type
+    ///
+    /// Accepts the [`qualified_type`](Self::qualified_type) format. A bare
+    /// type only matches default-domain operations.
+    pub fn is_qualified_type(&self, qualified_type: &str) -> bool {
+        match qualified_type.rsplit_once("::") {
+            Some((domain, op_type)) => {
+                self.op_type == op_type
+                    && (self.domain == domain
+                        || self.is_default_domain() && is_default_domain(domain))
+            }
+            None => self.is_default_domain() && self.op_type == qualified_type,
+        }
+    }
+
     /// Get all attribute names
     pub fn attribute_names(&self) -> Vec<&String> {
         self.attributes.keys().collect()
     }
 }
+
+/// Both the empty string and `ai.onnx` name the default operator domain
+pub(crate) fn is_default_domain(domain: &str) -> bool {
+    domain.is_empty() || domain == "ai.onnx"
+}
@@ -0,0 +1,66 @@
+//! Iteration over sub-byte packed tensor elements.
+//!
+//! ONNX packs 4-bit types two to a byte and 2-bit types four to a byte,
+//! filling each byte from the least significant bits upwards.
+
+use crate::TensorData;
+
+/// Iterator over the bit codes of packed sub-byte elements
+///
+/// Yields each element's code in the low bits of a `u8`, in ONNX packing
+/// order. Created by [`OnnxTensor::packed_elements`](crate::OnnxTensor::packed_elements).
+#[derive(Debug, Clone)]
+pub struct PackedElements<'a> {
+    data: TensorData<'a>,
+    bits: usize,
+    index: usize,
+    count: usize,
+}
+
+impl<'a> PackedElements<'a> {
+    /// Caller guarantees `data` holds at least `count * bits` bits and is not strings
+    pub(crate) fn new(data: TensorData<'a>, bits: usize, count: usize) -> Self {
+        PackedElements {
+            data,
+            bits,
+            index: 0,
+            count,
+        }
+    }
+
+    fn bytes(&self) -> &[u8] {
+        match &self.data {
+            TensorData::Raw(b) => b,
+            TensorData::Numeric(c) => c,
+            TensorData::Strings(_) => &[],
+        }
+    }
+}
+
+impl Iterator for PackedElements<'_> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.index >= self.count {
+            return None;
+        }
+        let bit = self.index * self.bits;
+        let byte = self.bytes()[bit / 8];
+        let mask = (1u16 << self.bits) - 1;
+        self.index += 1;
+        Some((byte >> (bit % 8)) & mask as u8)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.count - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for PackedElements<'_> {}
+
+/// Sign-extend a two's complement code of the given bit width
+pub(crate) fn sign_extend(code: u8, bits: usize) -> i8 {
+    let shift = 8 - bits as u32;
+    ((code << shift) as i8) >> shift
+}
@@ -1,10 +1,17 @@
 use crate::external_data::{ExternalDataInfo, ExternalDataLoader};
+use crate::graph::GraphParts;
 use crate::tensor::TensorDataLocation;
 use crate::{
-    AttributeProto, AttributeValue, DataType, Error, NodeProto, OnnxOperation, OnnxTensor,
-    TensorProto,
+    AttributeProto, AttributeValue, DataType, Dim, Error, FunctionProto, GraphProto, ModelProto,
+    NodeProto, OnnxFunction, OnnxGraph, OnnxModel, OnnxOperation, OnnxSparseTensor, OnnxTensor,
+    OnnxTrainingInfo, OperatorSetIdProto, OpsetImport, SparseTensorProto, StringStringEntryProto,
+    TensorAnnotation, TensorProto, TensorShapeProto, TrainingInfoProto, TypeProto, ValueInfoProto,
+    ValueType, attribute_proto::AttributeType, tensor_shape_proto,
+    tensor_shape_proto::dimension::Value as DimensionValue, type_proto,
 };
-use std::{collections::HashMap, mem, rc::Rc};
+use prost::bytes::Bytes;
+use std::collections::{HashMap, hash_map::Entry};
+use std::{mem, sync::Arc};
 
 /// Centralised adapter functions that translate generated protobuf types into
 /// crate-native types. Keep all direct proto-field usage here so future changes
@@ -17,7 +24,7 @@ use std::{collections::HashMap, mem, rc::Rc};
 /// Create OnnxTensor from ONNX TensorProto
 pub(crate) fn tensor_from_proto(
     mut tensor: TensorProto,
-    external_data_loader: Option<Rc<ExternalDataLoader>>,
+    external_data_loader: Option<Arc<ExternalDataLoader>>,
 ) -> Result<OnnxTensor, Error> {
     let shape: Vec<i64> = std::mem::take(&mut tensor.dims);
     let data_type = DataType::from_onnx_type(tensor.data_type.unwrap_or(0));
@@ -59,13 +66,203 @@ pub(crate) fn tensor_from_proto(
     ))
 }
 
+/// Create OnnxSparseTensor from ONNX SparseTensorProto
+pub(crate) fn sparse_tensor_from_proto(
+    mut sparse: SparseTensorProto,
+    external_data_loader: Option<&Arc<ExternalDataLoader>>,
+) -> Result<OnnxSparseTensor, Error> {
+    let values = sparse
+        .values
+        .take()
+        .ok_or_else(|| Error::MissingField("sparse tensor values".to_string()))?;
+    let indices = sparse
+        .indices
+        .take()
+        .ok_or_else(|| Error::MissingField("sparse tensor indices".to_string()))?;
+
+    Ok(OnnxSparseTensor::new(
+        tensor_from_proto(values, external_data_loader.cloned())?,
+        tensor_from_proto(indices, external_data_loader.cloned())?,
+        sparse.dims,
+    ))
+}
+
+/// Create ValueType from ONNX TypeProto
+pub(crate) fn value_type_from_proto(type_proto: &TypeProto) -> Result<ValueType, Error> {
+    let elem = |t: &Option<Box<TypeProto>>| -> Result<Box<ValueType>, Error> {
+        let t = t
+            .as_deref()
+            .ok_or_else(|| Error::MissingField("type elem_type".to_string()))?;
+        Ok(Box::new(value_type_from_proto(t)?))
+    };
+
+    match &type_proto.value {
+        Some(type_proto::Value::TensorType(t)) => Ok(ValueType::Tensor {
+            data_type: DataType::from_onnx_type(t.elem_type.unwrap_or(0)),
+            shape: t.shape.as_ref().map(dims_from_shape_proto),
+        }),
+        Some(type_proto::Value::SparseTensorType(t)) => Ok(ValueType::SparseTensor {
+            data_type: DataType::from_onnx_type(t.elem_type.unwrap_or(0)),
+            shape: t.shape.as_ref().map(dims_from_shape_proto),
+        }),
+        Some(type_proto::Value::SequenceType(t)) => Ok(ValueType::Sequence(elem(&t.elem_type)?)),
+        Some(type_proto::Value::MapType(t)) => Ok(ValueType::Map {
+            key_type: DataType::from_onnx_type(t.key_type.unwrap_or(0)),
+            value_type: elem(&t.value_type)?,
+        }),
+        Some(type_proto::Value::OptionalType(t)) => Ok(ValueType::Optional(elem(&t.elem_type)?)),
+        None => Err(Error::MissingField("type value".to_string())),
+    }
+}
+
+/// Convert shape dimensions, keeping `dim_param` names
+pub(crate) fn dims_from_shape_proto(shape: &TensorShapeProto) -> Vec<Dim> {
+    shape
+        .dim
+        .iter()
+        .map(|d| match &d.value {
+            Some(DimensionValue::DimValue(v)) => Dim::Fixed(*v),
+            Some(DimensionValue::DimParam(p)) if !p.is_empty() => Dim::Symbolic(p.clone()),
+            _ => Dim::Unknown,
+        })
+        .collect()
+}
+
+/// Create OnnxGraph from ONNX GraphProto
+///
+/// Used for the main graph as well as GRAPH/GRAPHS attribute subgraphs, which
+/// share the same loader so nested initializers can use external data.
+pub(crate) fn graph_from_proto(
+    mut graph: GraphProto,
+    external_data_loader: Option<&Arc<ExternalDataLoader>>,
+) -> Result<OnnxGraph, Error> {
+    let mut onnx_graph = OnnxGraph {
+        name: graph.name.take().unwrap_or_default(),
+        tensors: HashMap::new(),
+        sparse_tensors: HashMap::new(),
+        operations: Vec::new(),
+        inputs: Vec::new(),
+        outputs: Vec::new(),
+        initializer_inputs: Vec::new(),
+        value_types: HashMap::new(),
+        quantization_annotation: HashMap::new(),
+    };
+
+    // pre-allocate based on graph sizes to avoid repeated reallocations
+    onnx_graph.tensors.reserve(
+        graph.initializer.len() + graph.value_info.len() + graph.input.len() + graph.output.len(),
+    );
+    onnx_graph.operations.reserve(graph.node.len());
+    onnx_graph.inputs.reserve(graph.input.len());
+    onnx_graph.outputs.reserve(graph.output.len());
+
+    // parse initialiser tensors (weights/constants) by draining to avoid clones
+    for tensor in graph.initializer.drain(..) {
+        let onnx_tensor = tensor_from_proto(tensor, external_data_loader.cloned())?;
+        let tensor_name = onnx_tensor.name().to_string();
+        if !tensor_name.is_empty() {
+            onnx_graph.tensors.insert(tensor_name, onnx_tensor);
+        }
+    }
+
+    // parse sparse initialisers, keyed by the name of their values tensor
+    for sparse in graph.sparse_initializer.drain(..) {
+        let onnx_sparse = sparse_tensor_from_proto(sparse, external_data_loader)?;
+        let tensor_name = onnx_sparse.name().to_string();
+        if !tensor_name.is_empty() {
+            onnx_graph.sparse_tensors.insert(tensor_name, onnx_sparse);
+        }
+    }
+
+    // parse input types and extract input names
+    for input in graph.input.drain(..) {
+        let name = input.name.unwrap_or_default();
+        if name.is_empty() {
+            continue;
+        }
+
+        // initialisers listed as inputs are kept apart so they can be listed again on save
+        if onnx_graph.tensors.contains_key(&name) || onnx_graph.sparse_tensors.contains_key(&name) {
+            onnx_graph.initializer_inputs.push(name.clone());
+        } else {
+            onnx_graph.inputs.push(name.clone());
+        }
+        insert_value_type(&mut onnx_graph, name, input.r#type.as_ref())?;
+    }
+
+    // parse value_info for intermediate value shapes and types
+    for value_info in graph.value_info.drain(..) {
+        let name = value_info.name.unwrap_or_default();
+        if !name.is_empty() {
+            insert_value_type(&mut onnx_graph, name, value_info.r#type.as_ref())?;
+        }
+    }
+
+    // parse output types and extract output names
+    for output in graph.output.drain(..) {
+        let name = output.name.unwrap_or_default();
+        if name.is_empty() {
+            continue;
+        }
+
+        onnx_graph.outputs.push(name.clone());
+        insert_value_type(&mut onnx_graph, name, output.r#type.as_ref())?;
+    }
+
+    // parse quantization annotations, keyed by the annotated tensor name
+    for annotation in graph.quantization_annotation.drain(..) {
+        let name = annotation.tensor_name.unwrap_or_default();
+        if !name.is_empty() {
+            let params = metadata_from_proto(annotation.quant_parameter_tensor_names);
+            onnx_graph.quantization_annotation.insert(name, params);
+        }
+    }
+
+    // parse operations/nodes by draining to allow owned conversion
+    for node in graph.node.drain(..) {
+        let operation = operation_from_node_proto(node, external_data_loader)?;
+        onnx_graph.operations.push(operation);
+    }
+
+    Ok(onnx_graph)
+}
+
+/// Record the declared type of a graph value
+///
+/// Dense tensors become shape-only entries in `tensors`, other types go to
+/// `value_types`. The first declaration of a name wins and initializers are
+/// never replaced.
+fn insert_value_type(
+    graph: &mut OnnxGraph,
+    name: String,
+    type_proto: Option<&TypeProto>,
+) -> Result<(), Error> {
+    let Some(type_proto) = type_proto.filter(|t| t.value.is_some()) else {
+        return Ok(());
+    };
+    if graph.sparse_tensors.contains_key(&name) {
+        return Ok(());
+    }
+
+    if let Some(type_proto::Value::TensorType(tensor_type)) = &type_proto.value {
+        let onnx_tensor = OnnxTensor::from_tensor_type(name.clone(), tensor_type)?;
+        graph.tensors.entry(name).or_insert(onnx_tensor);
+    } else if let Entry::Vacant(entry) = graph.value_types.entry(name) {
+        entry.insert(value_type_from_proto(type_proto)?);
+    }
+    Ok(())
+}
+
 /// Create OnnxOperation from ONNX NodeProto
-pub(crate) fn operation_from_node_proto(mut node: NodeProto) -> Result<OnnxOperation, Error> {
+pub(crate) fn operation_from_node_proto(
+    mut node: NodeProto,
+    external_data_loader: Option<&Arc<ExternalDataLoader>>,
+) -> Result<OnnxOperation, Error> {
     let mut attributes = HashMap::new();
 
     for mut attr in node.attribute.drain(..) {
         let attr_name = attr.name.take().unwrap_or_default();
-        let value = parse_attribute_proto(attr)?;
+        let value = parse_attribute_proto(attr, external_data_loader)?;
         if !attr_name.is_empty() {
             attributes.insert(attr_name, value);
         }
@@ -74,6 +271,10 @@ pub(crate) fn operation_from_node_proto(mut node: NodeProto) -> Result<OnnxOpera
     Ok(OnnxOperation {
         name: node.name.take().unwrap_or_default(),
         op_type: node.op_type.take().unwrap_or_default(),
+        domain: node.domain.take().unwrap_or_default(),
+        overload: node.overload.take().unwrap_or_default(),
+        doc_string: node.doc_string.take().unwrap_or_default(),
+        metadata_props: metadata_from_proto(node.metadata_props),
         inputs: node.input,
         outputs: node.output,
         attributes,
@@ -85,8 +286,19 @@ pub(crate) fn operation_from_node_proto(mut node: NodeProto) -> Result<OnnxOpera
 /// Strings are converted from `prost::bytes::Bytes` to `String` via UTF-8. For
 /// string arrays, we collect all entries; zero-copy is not possible due to the
 /// need to validate UTF-8 and represent as owned `String`.
-pub(crate) fn parse_attribute_proto(mut attr: AttributeProto) -> Result<AttributeValue, Error> {
+pub(crate) fn parse_attribute_proto(
+    mut attr: AttributeProto,
+    external_data_loader: Option<&Arc<ExternalDataLoader>>,
+) -> Result<AttributeValue, Error> {
     let attr_type = attr.r#type.unwrap_or(0);
+
+    // inside a function body the value comes from the calling node
+    if let Some(name) = attr.ref_attr_name.take().filter(|n| !n.is_empty()) {
+        let attr_type = AttributeType::try_from(attr_type)
+            .map_err(|_| Error::Unsupported(format!("attribute type: {}", attr_type)))?;
+        return Ok(AttributeValue::Ref { name, attr_type });
+    }
+
     match attr_type {
         1 => Ok(AttributeValue::Float(attr.f.take().unwrap_or(0.0))),
         2 => Ok(AttributeValue::Int(attr.i.take().unwrap_or(0))),
@@ -96,13 +308,21 @@ pub(crate) fn parse_attribute_proto(mut attr: AttributeProto) -> Result<Attribut
         }
         4 => {
             if let Some(tensor) = attr.t.take() {
-                // Note: Tensor attributes don't have external data loader since they're inline
-                let onnx_tensor = tensor_from_proto(tensor, None)?;
+                // attribute tensors may be stored externally like initializers
+                let onnx_tensor = tensor_from_proto(tensor, external_data_loader.cloned())?;
                 Ok(AttributeValue::Tensor(Box::new(onnx_tensor)))
             } else {
                 Err(Error::MissingField("tensor attribute data".to_string()))
             }
         }
+        5 => {
+            if let Some(graph) = attr.g.take() {
+                let onnx_graph = graph_from_proto(graph, external_data_loader)?;
+                Ok(AttributeValue::Graph(Box::new(onnx_graph)))
+            } else {
+                Err(Error::MissingField("graph attribute data".to_string()))
+            }
+        }
         6 => Ok(AttributeValue::Floats(mem::take(&mut attr.floats))),
         7 => Ok(AttributeValue::Ints(mem::take(&mut attr.ints))),
         8 => {
@@ -113,6 +333,565 @@ pub(crate) fn parse_attribute_proto(mut attr: AttributeProto) -> Result<Attribut
                 .collect();
             Ok(AttributeValue::Strings(strings?))
         }
+        9 => {
+            let tensors: Result<Vec<OnnxTensor>, Error> = attr
+                .tensors
+                .drain(..)
+                .map(|t| tensor_from_proto(t, external_data_loader.cloned()))
+                .collect();
+            Ok(AttributeValue::Tensors(tensors?))
+        }
+        10 => {
+            let graphs: Result<Vec<OnnxGraph>, Error> = attr
+                .graphs
+                .drain(..)
+                .map(|g| graph_from_proto(g, external_data_loader))
+                .collect();
+            Ok(AttributeValue::Graphs(graphs?))
+        }
+        11 => {
+            if let Some(sparse) = attr.sparse_tensor.take() {
+                let onnx_sparse = sparse_tensor_from_proto(sparse, external_data_loader)?;
+                Ok(AttributeValue::SparseTensor(Box::new(onnx_sparse)))
+            } else {
+                Err(Error::MissingField(
+                    "sparse tensor attribute data".to_string(),
+                ))
+            }
+        }
+        12 => {
+            let tensors: Result<Vec<OnnxSparseTensor>, Error> = attr
+                .sparse_tensors
+                .drain(..)
+                .map(|t| sparse_tensor_from_proto(t, external_data_loader))
+                .collect();
+            Ok(AttributeValue::SparseTensors(tensors?))
+        }
+        13 => {
+            if let Some(tp) = &attr.tp {
+                Ok(AttributeValue::Type(value_type_from_proto(tp)?))
+            } else {
+                Err(Error::MissingField("type attribute data".to_string()))
+            }
+        }
+        14 => {
+            let types: Result<Vec<ValueType>, Error> =
+                attr.type_protos.iter().map(value_type_from_proto).collect();
+            Ok(AttributeValue::Types(types?))
+        }
         _ => Err(Error::Unsupported(format!("attribute type: {}", attr_type))),
     }
 }
+
+/// Rebuild a ModelProto from an OnnxModel
+///
+/// This is the inverse of the loading path. Tensors that hold data are
+/// written as initializers, shape-only tensors become graph inputs, outputs
+/// or value_info. External data is loaded and written inline as `raw_data`
+/// so the result does not depend on files next to the original model.
+pub(crate) fn model_to_proto(model: &OnnxModel) -> Result<ModelProto, Error> {
+    let graph = graph_to_proto(model.graph_parts())?;
+
+    Ok(ModelProto {
+        ir_version: Some(model.ir_version),
+        opset_import: opset_imports_to_proto(&model.opset_import),
+        producer_name: non_empty(&model.producer_name),
+        producer_version: non_empty(&model.producer_version),
+        domain: non_empty(&model.domain),
+        model_version: Some(model.model_version),
+        doc_string: non_empty(&model.doc_string),
+        graph: Some(graph),
+        metadata_props: metadata_to_proto(&model.metadata_props),
+        functions: model
+            .functions
+            .iter()
+            .map(function_to_proto)
+            .collect::<Result<Vec<_>, Error>>()?,
+        training_info: model
+            .training_info
+            .iter()
+            .map(training_info_to_proto)
+            .collect::<Result<Vec<_>, Error>>()?,
+        ..Default::default()
+    })
+}
+
+/// Create OnnxTrainingInfo from ONNX TrainingInfoProto
+pub(crate) fn training_info_from_proto(
+    info: TrainingInfoProto,
+    external_data_loader: Option<&Arc<ExternalDataLoader>>,
+) -> Result<OnnxTrainingInfo, Error> {
+    let graph = |g: Option<GraphProto>| {
+        g.map(|g| graph_from_proto(g, external_data_loader))
+            .transpose()
+    };
+
+    Ok(OnnxTrainingInfo {
+        initialization: graph(info.initialization)?,
+        algorithm: graph(info.algorithm)?,
+        initialization_binding: metadata_from_proto(info.initialization_binding),
+        update_binding: metadata_from_proto(info.update_binding),
+    })
+}
+
+/// Create TrainingInfoProto from OnnxTrainingInfo
+///
+/// Bindings are sorted by initializer name so the output is deterministic.
+pub(crate) fn training_info_to_proto(info: &OnnxTrainingInfo) -> Result<TrainingInfoProto, Error> {
+    Ok(TrainingInfoProto {
+        initialization: info
+            .initialization
+            .as_ref()
+            .map(subgraph_to_proto)
+            .transpose()?,
+        algorithm: info.algorithm.as_ref().map(subgraph_to_proto).transpose()?,
+        initialization_binding: metadata_to_proto(&info.initialization_binding),
+        update_binding: metadata_to_proto(&info.update_binding),
+    })
+}
+
+/// Create OnnxFunction from ONNX FunctionProto
+pub(crate) fn function_from_proto(
+    mut function: FunctionProto,
+    external_data_loader: Option<&Arc<ExternalDataLoader>>,
+) -> Result<OnnxFunction, Error> {
+    let mut attribute_defaults = HashMap::new();
+    for mut attr in function.attribute_proto.drain(..) {
+        let attr_name = attr.name.take().unwrap_or_default();
+        let value = parse_attribute_proto(attr, external_data_loader)?;
+        if !attr_name.is_empty() {
+            attribute_defaults.insert(attr_name, value);
+        }
+    }
+
+    let operations = function
+        .node
+        .drain(..)
+        .map(|node| operation_from_node_proto(node, external_data_loader))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(OnnxFunction {
+        name: function.name.take().unwrap_or_default(),
+        domain: function.domain.take().unwrap_or_default(),
+        overload: function.overload.take().unwrap_or_default(),
+        inputs: function.input,
+        outputs: function.output,
+        attributes: function.attribute,
+        attribute_defaults,
+        operations,
+        opset_import: opset_imports_from_proto(function.opset_import),
+        doc_string: function.doc_string.unwrap_or_default(),
+    })
+}
+
+/// Create FunctionProto from OnnxFunction
+///
+/// Attribute defaults are sorted by name so the output is deterministic.
+pub(crate) fn function_to_proto(function: &OnnxFunction) -> Result<FunctionProto, Error> {
+    let mut default_names: Vec<&String> = function.attribute_defaults.keys().collect();
+    default_names.sort();
+
+    let attribute_proto = default_names
+        .into_iter()
+        .map(|name| attribute_to_proto(name, &function.attribute_defaults[name]))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let node = function
+        .operations
+        .iter()
+        .map(node_proto_from_operation)
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(FunctionProto {
+        name: Some(function.name.clone()),
+        input: function.inputs.clone(),
+        output: function.outputs.clone(),
+        attribute: function.attributes.clone(),
+        attribute_proto,
+        node,
+        doc_string: non_empty(&function.doc_string),
+        opset_import: opset_imports_to_proto(&function.opset_import),
+        domain: Some(function.domain.clone()),
+        overload: non_empty(&function.overload),
+        ..Default::default()
+    })
+}
+
+/// Convert ONNX OperatorSetIdProto entries into OpsetImport values
+pub(crate) fn opset_imports_from_proto(opsets: Vec<OperatorSetIdProto>) -> Vec<OpsetImport> {
+    opsets
+        .into_iter()
+        .map(|opset| OpsetImport {
+            domain: opset.domain.unwrap_or_default(),
+            version: opset.version.unwrap_or(0),
+        })
+        .collect()
+}
+
+/// Convert OpsetImport values into ONNX OperatorSetIdProto entries
+pub(crate) fn opset_imports_to_proto(opsets: &[OpsetImport]) -> Vec<OperatorSetIdProto> {
+    opsets
+        .iter()
+        .map(|opset| OperatorSetIdProto {
+            domain: Some(opset.domain.clone()),
+            version: Some(opset.version),
+        })
+        .collect()
+}
+
+/// Convert ONNX metadata_props entries into a key/value map
+pub(crate) fn metadata_from_proto(props: Vec<StringStringEntryProto>) -> HashMap<String, String> {
+    props
+        .into_iter()
+        .map(|p| (p.key.unwrap_or_default(), p.value.unwrap_or_default()))
+        .collect()
+}
+
+/// Convert a key/value map into metadata_props entries sorted by key
+pub(crate) fn metadata_to_proto(props: &HashMap<String, String>) -> Vec<StringStringEntryProto> {
+    let mut entries: Vec<StringStringEntryProto> = props
+        .iter()
+        .map(|(k, v)| StringStringEntryProto {
+            key: Some(k.clone()),
+            value: Some(v.clone()),
+        })
+        .collect();
+    entries.sort_by(|a, b| a.key.cmp(&b.key));
+    entries
+}
+
+/// Rebuild a GraphProto from crate-native graph parts
+///
+/// Initializers, value_info and quantization annotations are sorted by name
+/// so the output is deterministic regardless of HashMap iteration order.
+pub(crate) fn graph_to_proto(graph: GraphParts<'_>) -> Result<GraphProto, Error> {
+    let GraphParts {
+        name,
+        tensors,
+        sparse_tensors,
+        operations,
+        inputs,
+        outputs,
+        initializer_inputs,
+        value_types,
+        quantization_annotation,
+    } = graph;
+
+    let mut names: Vec<&String> = tensors.keys().collect();
+    names.sort();
+
+    let mut initializer = Vec::new();
+    let mut value_info = Vec::new();
+    for name in names {
+        let tensor = &tensors[name];
+        if tensor.data_location().is_some() {
+            initializer.push(tensor_to_proto(name, tensor)?);
+        } else if !inputs.contains(name) && !outputs.contains(name) {
+            value_info.push(value_info_from_tensor(name, tensor));
+        }
+    }
+
+    let mut typed_names: Vec<&String> = value_types.keys().collect();
+    typed_names.sort();
+    for name in typed_names {
+        if !tensors.contains_key(name) && !inputs.contains(name) && !outputs.contains(name) {
+            value_info.push(value_info_from_type(name, &value_types[name]));
+        }
+    }
+    value_info.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let value_info_for = |name: &String| match (tensors.get(name), value_types.get(name)) {
+        (Some(tensor), _) => value_info_from_tensor(name, tensor),
+        (None, Some(value_type)) => value_info_from_type(name, value_type),
+        (None, None) => match graph.value_type(name) {
+            Some(value_type) => value_info_from_type(name, &value_type),
+            None => ValueInfoProto {
+                name: Some(name.clone()),
+                ..Default::default()
+            },
+        },
+    };
+    // initializers removed since loading are no longer listed
+    let is_initializer = |name: &&String| {
+        tensors
+            .get(name.as_str())
+            .is_some_and(|t| t.data_location().is_some())
+            || sparse_tensors.contains_key(name.as_str())
+    };
+
+    let mut sparse_names: Vec<&String> = sparse_tensors.keys().collect();
+    sparse_names.sort();
+    let sparse_initializer = sparse_names
+        .into_iter()
+        .map(|name| sparse_tensor_to_proto(&sparse_tensors[name]))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let node = operations
+        .iter()
+        .map(node_proto_from_operation)
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let mut annotated: Vec<&String> = quantization_annotation.keys().collect();
+    annotated.sort();
+    let quantization_annotation = annotated
+        .into_iter()
+        .map(|name| TensorAnnotation {
+            tensor_name: Some(name.clone()),
+            quant_parameter_tensor_names: metadata_to_proto(&quantization_annotation[name]),
+        })
+        .collect();
+
+    Ok(GraphProto {
+        node,
+        name: non_empty(name),
+        initializer,
+        sparse_initializer,
+        input: inputs
+            .iter()
+            .chain(initializer_inputs.iter().filter(is_initializer))
+            .map(value_info_for)
+            .collect(),
+        output: outputs.iter().map(value_info_for).collect(),
+        value_info,
+        quantization_annotation,
+        ..Default::default()
+    })
+}
+
+/// Create GraphProto from a subgraph
+fn subgraph_to_proto(graph: &OnnxGraph) -> Result<GraphProto, Error> {
+    graph_to_proto(GraphParts::from(graph))
+}
+
+/// Create TensorProto from OnnxTensor, inlining any external data
+pub(crate) fn tensor_to_proto(name: &str, tensor: &OnnxTensor) -> Result<TensorProto, Error> {
+    let mut proto = tensor.proto().cloned().unwrap_or_default();
+
+    if let Some(TensorDataLocation::External(info)) = tensor.data_location() {
+        proto.raw_data = Some(info.load_data()?);
+        proto.external_data.clear();
+        proto.data_location = None;
+    }
+
+    proto.name = non_empty(name);
+    proto.dims = tensor.shape().to_vec();
+    proto.data_type = Some(tensor.data_type() as i32);
+    Ok(proto)
+}
+
+/// Create ValueInfoProto describing a tensor's type and shape
+pub(crate) fn value_info_from_tensor(name: &str, tensor: &OnnxTensor) -> ValueInfoProto {
+    let tensor_type = type_proto::Tensor {
+        elem_type: Some(tensor.data_type() as i32),
+        shape: Some(shape_proto_from_dims(tensor.dims())),
+    };
+
+    ValueInfoProto {
+        name: Some(name.to_string()),
+        r#type: Some(TypeProto {
+            value: Some(type_proto::Value::TensorType(tensor_type)),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Create ValueInfoProto from a declared value type
+pub(crate) fn value_info_from_type(name: &str, value_type: &ValueType) -> ValueInfoProto {
+    ValueInfoProto {
+        name: Some(name.to_string()),
+        r#type: Some(value_type_to_proto(value_type)),
+        ..Default::default()
+    }
+}
+
+/// Create SparseTensorProto from OnnxSparseTensor
+pub(crate) fn sparse_tensor_to_proto(
+    sparse: &OnnxSparseTensor,
+) -> Result<SparseTensorProto, Error> {
+    Ok(SparseTensorProto {
+        values: Some(tensor_to_proto(sparse.name(), sparse.values())?),
+        indices: Some(tensor_to_proto(sparse.indices().name(), sparse.indices())?),
+        dims: sparse.shape().to_vec(),
+    })
+}
+
+/// Create TypeProto from ValueType
+pub(crate) fn value_type_to_proto(value_type: &ValueType) -> TypeProto {
+    let boxed = |t: &ValueType| Some(Box::new(value_type_to_proto(t)));
+
+    let value = match value_type {
+        ValueType::Tensor { data_type, shape } => {
+            type_proto::Value::TensorType(type_proto::Tensor {
+                elem_type: Some(*data_type as i32),
+                shape: shape.as_deref().map(shape_proto_from_dims),
+            })
+        }
+        ValueType::SparseTensor { data_type, shape } => {
+            type_proto::Value::SparseTensorType(type_proto::SparseTensor {
+                elem_type: Some(*data_type as i32),
+                shape: shape.as_deref().map(shape_proto_from_dims),
+            })
+        }
+        ValueType::Sequence(elem) => {
+            type_proto::Value::SequenceType(Box::new(type_proto::Sequence {
+                elem_type: boxed(elem),
+            }))
+        }
+        ValueType::Map {
+            key_type,
+            value_type,
+        } => type_proto::Value::MapType(Box::new(type_proto::Map {
+            key_type: Some(*key_type as i32),
+            value_type: boxed(value_type),
+        })),
+        ValueType::Optional(elem) => {
+            type_proto::Value::OptionalType(Box::new(type_proto::Optional {
+                elem_type: boxed(elem),
+            }))
+        }
+    };
+
+    TypeProto {
+        value: Some(value),
+        ..Default::default()
+    }
+}
+
+/// Create TensorShapeProto from dims
+///
+/// Symbolic dimensions are written as `dim_param`, unknown ones without a value.
+pub(crate) fn shape_proto_from_dims(dims: &[Dim]) -> TensorShapeProto {
+    let dim = dims
+        .iter()
+        .map(|d| tensor_shape_proto::Dimension {
+            value: match d {
+                Dim::Fixed(v) => Some(DimensionValue::DimValue(*v)),
+                Dim::Symbolic(p) => Some(DimensionValue::DimParam(p.clone())),
+                Dim::Unknown => None,
+            },
+            ..Default::default()
+        })
+        .collect();
+    TensorShapeProto { dim }
+}
+
+/// Create NodeProto from OnnxOperation
+///
+/// Attributes are sorted by name so the output is deterministic.
+pub(crate) fn node_proto_from_operation(op: &OnnxOperation) -> Result<NodeProto, Error> {
+    let mut attr_names: Vec<&String> = op.attributes.keys().collect();
+    attr_names.sort();
+
+    let attribute = attr_names
+        .into_iter()
+        .map(|name| attribute_to_proto(name, &op.attributes[name]))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(NodeProto {
+        input: op.inputs.clone(),
+        output: op.outputs.clone(),
+        name: non_empty(&op.name),
+        op_type: Some(op.op_type.clone()),
+        domain: non_empty(&op.domain),
+        overload: non_empty(&op.overload),
+        attribute,
+        doc_string: non_empty(&op.doc_string),
+        metadata_props: metadata_to_proto(&op.metadata_props),
+        ..Default::default()
+    })
+}
+
+/// Create AttributeProto from AttributeValue
+pub(crate) fn attribute_to_proto(
+    name: &str,
+    value: &AttributeValue,
+) -> Result<AttributeProto, Error> {
+    let mut attr = AttributeProto {
+        name: Some(name.to_string()),
+        ..Default::default()
+    };
+
+    let attr_type = match value {
+        AttributeValue::Float(f) => {
+            attr.f = Some(*f);
+            AttributeType::Float
+        }
+        AttributeValue::Int(i) => {
+            attr.i = Some(*i);
+            AttributeType::Int
+        }
+        AttributeValue::String(s) => {
+            attr.s = Some(Bytes::copy_from_slice(s.as_bytes()));
+            AttributeType::String
+        }
+        AttributeValue::Tensor(t) => {
+            attr.t = Some(tensor_to_proto(t.name(), t)?);
+            AttributeType::Tensor
+        }
+        AttributeValue::Graph(g) => {
+            attr.g = Some(subgraph_to_proto(g)?);
+            AttributeType::Graph
+        }
+        AttributeValue::SparseTensor(t) => {
+            attr.sparse_tensor = Some(sparse_tensor_to_proto(t)?);
+            AttributeType::SparseTensor
+        }
+        AttributeValue::Type(t) => {
+            attr.tp = Some(value_type_to_proto(t));
+            AttributeType::TypeProto
+        }
+        AttributeValue::Floats(floats) => {
+            attr.floats = floats.clone();
+            AttributeType::Floats
+        }
+        AttributeValue::Ints(ints) => {
+            attr.ints = ints.clone();
+            AttributeType::Ints
+        }
+        AttributeValue::Strings(strings) => {
+            attr.strings = strings
+                .iter()
+                .map(|s| Bytes::copy_from_slice(s.as_bytes()))
+                .collect();
+            AttributeType::Strings
+        }
+        AttributeValue::Tensors(tensors) => {
+            attr.tensors = tensors
+                .iter()
+                .map(|t| tensor_to_proto(t.name(), t))
+                .collect::<Result<Vec<_>, Error>>()?;
+            AttributeType::Tensors
+        }
+        AttributeValue::Graphs(graphs) => {
+            attr.graphs = graphs
+                .iter()
+                .map(subgraph_to_proto)
+                .collect::<Result<Vec<_>, Error>>()?;
+            AttributeType::Graphs
+        }
+        AttributeValue::SparseTensors(tensors) => {
+            attr.sparse_tensors = tensors
+                .iter()
+                .map(sparse_tensor_to_proto)
+                .collect::<Result<Vec<_>, Error>>()?;
+            AttributeType::SparseTensors
+        }
+        AttributeValue::Types(types) => {
+            attr.type_protos = types.iter().map(value_type_to_proto).collect();
+            AttributeType::TypeProtos
+        }
+        AttributeValue::Ref { name, attr_type } => {
+            attr.ref_attr_name = Some(name.clone());
+            *attr_type
+        }
+    };
+
+    attr.r#type = Some(attr_type as i32);
+    Ok(attr)
+}
+
+/// Map empty strings to None so unset optional fields stay unset
+fn non_empty(s: &str) -> Option<String> {
+    (!s.is_empty()).then(|| s.to_string())
+}
@@ -0,0 +1,63 @@
+//! Quantization parameters attached to tensors by graph annotations.
+
+use crate::{DataType, Error, OnnxTensor};
+
+/// Annotation key naming the scale tensor
+pub const SCALE_TENSOR: &str = "SCALE_TENSOR";
+/// Annotation key naming the zero-point tensor
+pub const ZERO_POINT_TENSOR: &str = "ZERO_POINT_TENSOR";
+
+/// Scale and zero-point tensors for a quantized tensor
+///
+/// Resolved from `quantization_annotation` entries, see
+/// [`OnnxModel::quant_params`](crate::OnnxModel::quant_params). A scale with
+/// more than one element holds per-axis parameters.
+#[derive(Debug, Clone, Copy)]
+pub struct QuantParams<'a> {
+    pub scale: &'a OnnxTensor,
+    /// None for symmetric quantization without a zero point
+    pub zero_point: Option<&'a OnnxTensor>,
+}
+
+impl QuantParams<'_> {
+    /// Check if a single scale applies to the whole tensor
+    pub fn is_per_tensor(&self) -> bool {
+        self.scale.shape().iter().product::<i64>() == 1
+    }
+
+    /// Decode the scale values as f32
+    pub fn scales(&self) -> Result<Vec<f32>, Error> {
+        self.scale.to_f32_vec()
+    }
+
+    /// Decode the zero points as i64, all zeros when there is no zero point
+    ///
+    /// Supports the integer types, including packed 4-bit and 2-bit ones.
+    pub fn zero_points(&self) -> Result<Vec<i64>, Error> {
+        let Some(zero_point) = self.zero_point else {
+            return Ok(vec![0; self.scales()?.len()]);
+        };
+        match zero_point.data_type() {
+            DataType::Int8 => widen(zero_point.to_vec::<i8>()?),
+            DataType::Uint8 => widen(zero_point.to_vec::<u8>()?),
+            DataType::Int16 => widen(zero_point.to_vec::<i16>()?),
+            DataType::Uint16 => widen(zero_point.to_vec::<u16>()?),
+            DataType::Int32 => widen(zero_point.to_vec::<i32>()?),
+            DataType::Uint32 => widen(zero_point.to_vec::<u32>()?),
+            DataType::Int64 => zero_point.to_vec::<i64>(),
+            DataType::Int4 | DataType::Int2 => Ok(zero_point.unpack_i8()?.map(i64::from).collect()),
+            DataType::Uint4 | DataType::Uint2 => {
+                Ok(zero_point.unpack_u8()?.map(i64::from).collect())
+            }
+            other => Err(Error::DataConversion(format!(
+                "Zero point tensor '{}' has non-integer type {:?}",
+                zero_point.name(),
+                other
+            ))),
+        }
+    }
+}
+
+fn widen<T: Into<i64>>(values: Vec<T>) -> Result<Vec<i64>, Error> {
+    Ok(values.into_iter().map(Into::into).collect())
+}
@@ -0,0 +1,1128 @@
+//! Static shape and type inference for common ONNX operators.
+//!
+//! Walks the main graph in execution order, propagating element types and
+//! dimensions from graph inputs and initializers. Small integer tensors
+//! (initializers, `Constant`, `Shape` and simple arithmetic on them) are
+//! tracked by value so data-dependent shapes such as `Reshape` targets can
+//! be resolved.
+
+use std::collections::HashMap;
+
+use crate::{AttributeValue, DataType, Dim, Error, OnnxModel, OnnxOperation, OnnxTensor};
+
+/// Integer constants with more elements than this are not tracked by value
+const MAX_CONSTANT_ELEMENTS: usize = 1024;
+
+/// Outcome of [`OnnxModel::infer_shapes`]
+#[derive(Debug, Clone, Default)]
+pub struct ShapeInferenceReport {
+    /// Names of tensors that were added or had unknown dims filled in
+    pub inferred: Vec<String>,
+    /// Operations whose outputs could not be inferred
+    pub skipped: Vec<SkippedOperation>,
+}
+
+/// An operation that shape inference could not handle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedOperation {
+    pub name: String,
+    pub op_type: String,
+    pub reason: String,
+}
+
+/// Type, dims and optionally the integer contents of a value
+#[derive(Debug, Clone)]
+struct ValueInfo {
+    data_type: DataType,
+    dims: Vec<Dim>,
+    constant: Option<Vec<i64>>,
+}
+
+impl ValueInfo {
+    fn new(data_type: DataType, dims: Vec<Dim>) -> Self {
+        ValueInfo {
+            data_type,
+            dims,
+            constant: None,
+        }
+    }
+
+    fn with_constant(mut self, constant: Option<Vec<i64>>) -> Self {
+        self.constant = constant;
+        self
+    }
+
+    fn rank(&self) -> usize {
+        self.dims.len()
+    }
+}
+
+type InferResult = Result<Vec<Option<ValueInfo>>, String>;
+
+/// Infer shapes for the main graph and record them in `model.tensors`
+pub(crate) fn infer_model(model: &mut OnnxModel) -> Result<ShapeInferenceReport, Error> {
+    let mut values: HashMap<String, ValueInfo> = HashMap::new();
+    for (name, tensor) in &model.tensors {
+        let constant = if tensor.data_location().is_some() {
+            int_constant(tensor)
+        } else {
+            None
+        };
+        values.insert(
+            name.clone(),
+            ValueInfo::new(tensor.data_type(), tensor.dims().to_vec()).with_constant(constant),
+        );
+    }
+
+    let mut report = ShapeInferenceReport::default();
+    let mut results: Vec<(String, ValueInfo)> = Vec::new();
+    for op in model.execution_order()? {
+        let opset = model.operation_opset_version(op).unwrap_or(0);
+        let outputs = if op.is_default_domain() {
+            infer_operation(op, opset, &values)
+        } else {
+            Err(format!("domain '{}' is not supported", op.domain))
+        };
+
+        match outputs {
+            Ok(outputs) => {
+                for (name, info) in op.outputs.iter().zip(outputs) {
+                    if let Some(info) = info.filter(|_| !name.is_empty()) {
+                        values.insert(name.clone(), info.clone());
+                        results.push((name.clone(), info));
+                    }
+                }
+            }
+            Err(reason) => report.skipped.push(SkippedOperation {
+                name: op.name.clone(),
+                op_type: op.op_type.clone(),
+                reason,
+            }),
+        }
+    }
+
+    for (name, info) in results {
+        if merge_into(&mut model.tensors, &name, info) {
+            report.inferred.push(name);
+        }
+    }
+    Ok(report)
+}
+
+/// Record an inferred value, keeping anything already known about it
+///
+/// Returns true if the tensor was added or refined.
+fn merge_into(tensors: &mut HashMap<String, OnnxTensor>, name: &str, info: ValueInfo) -> bool {
+    let Some(existing) = tensors.get(name) else {
+        let tensor = OnnxTensor::from_dims(name.to_string(), info.data_type, info.dims);
+        tensors.insert(name.to_string(), tensor);
+        return true;
+    };
+    if existing.data_location().is_some() {
+        return false;
+    }
+
+    let mut data_type = existing.data_type();
+    let mut dims = existing.dims().to_vec();
+    let mut changed = false;
+    if data_type == DataType::Undefined && info.data_type != DataType::Undefined {
+        data_type = info.data_type;
+        changed = true;
+    }
+    if dims.len() == info.dims.len() {
+        for (dim, inferred) in dims.iter_mut().zip(info.dims) {
+            if *dim == Dim::Unknown && inferred != Dim::Unknown {
+                *dim = inferred;
+                changed = true;
+            }
+        }
+    }
+    if changed {
+        tensors.insert(
+            name.to_string(),
+            OnnxTensor::from_dims(name.to_string(), data_type, dims),
+        );
+    }
+    changed
+}
+
+/// Read a small Int64 or Int32 tensor's values
+fn int_constant(tensor: &OnnxTensor) -> Option<Vec<i64>> {
+    let count = tensor
+        .shape()
+        .iter()
+        .try_fold(1usize, |acc, &d| acc.checked_mul(usize::try_from(d).ok()?))?;
+    if count > MAX_CONSTANT_ELEMENTS {
+        return None;
+    }
+    match tensor.data_type() {
+        DataType::Int64 => tensor.to_vec::<i64>().ok(),
+        DataType::Int32 => tensor
+            .to_vec::<i32>()
+            .ok()
+            .map(|v| v.into_iter().map(i64::from).collect()),
+        _ => None,
+    }
+}
+
+fn fixed(values: &[i64]) -> Vec<Dim> {
+    values.iter().map(|&d| Dim::Fixed(d)).collect()
+}
+
+/// Product of dims, Unknown unless all are fixed (or one is zero)
+fn dim_product(dims: &[Dim]) -> Result<Dim, String> {
+    if dims.contains(&Dim::Fixed(0)) {
+        return Ok(Dim::Fixed(0));
+    }
+    let mut product = 1i64;
+    for d in dims {
+        let Some(v) = d.value() else {
+            return Ok(Dim::Unknown);
+        };
+        product = product.checked_mul(v).ok_or("product of dims overflows")?;
+    }
+    Ok(Dim::Fixed(product))
+}
+
+/// Resolve a possibly negative axis against a rank
+pub(crate) fn normalize_axis(axis: i64, rank: usize) -> Result<usize, String> {
+    let rank = rank as i64;
+    let resolved = if axis < 0 { axis + rank } else { axis };
+    if (0..rank).contains(&resolved) {
+        Ok(resolved as usize)
+    } else {
+        Err(format!("axis {} is out of range for rank {}", axis, rank))
+    }
+}
+
+/// Resolve a list of axes that must not name the same axis twice
+pub(crate) fn normalize_unique_axes(axes: &[i64], rank: usize) -> Result<Vec<usize>, String> {
+    let mut resolved = Vec::with_capacity(axes.len());
+    for &axis in axes {
+        let axis = normalize_axis(axis, rank)?;
+        if resolved.contains(&axis) {
+            return Err(format!("axis {} is repeated", axis));
+        }
+        resolved.push(axis);
+    }
+    Ok(resolved)
+}
+
+/// Numpy-style multidirectional broadcasting of shapes
+fn broadcast(shapes: &[&[Dim]]) -> Result<Vec<Dim>, String> {
+    let rank = shapes.iter().map(|s| s.len()).max().unwrap_or(0);
+    let mut out = Vec::with_capacity(rank);
+    for i in 0..rank {
+        let mut result = Dim::Fixed(1);
+        for shape in shapes {
+            let Some(offset) = (i + shape.len()).checked_sub(rank) else {
+                continue;
+            };
+            let dim = &shape[offset];
+            result = match (&result, dim) {
+                (_, Dim::Fixed(1)) => result,
+                (Dim::Fixed(1), _) => dim.clone(),
+                (Dim::Fixed(a), Dim::Fixed(b)) if a != b => {
+                    return Err(format!("cannot broadcast dims {} and {}", a, b));
+                }
+                (a, b) if a == b => result,
+                (Dim::Fixed(_), _) => result,
+                (_, Dim::Fixed(_)) => dim.clone(),
+                _ => Dim::Unknown,
+            };
+        }
+        out.push(result);
+    }
+    Ok(out)
+}
+
+/// Spatial output size for convolution and pooling windows
+fn window_output(
+    input: &Dim,
+    kernel: i64,
+    stride: i64,
+    dilation: i64,
+    pads: (i64, i64),
+    auto_pad: &str,
+    ceil_mode: bool,
+) -> Result<Dim, String> {
+    if stride <= 0 || dilation <= 0 {
+        return Err("strides and dilations must be positive".to_string());
+    }
+    let Some(input) = input.value() else {
+        return Ok(Dim::Unknown);
+    };
+    let overflow = || "window size overflows".to_string();
+    let effective = kernel
+        .checked_sub(1)
+        .and_then(|k| k.checked_mul(dilation))
+        .and_then(|k| k.checked_add(1))
+        .ok_or_else(overflow)?;
+    let out = match auto_pad {
+        "SAME_UPPER" | "SAME_LOWER" => input.checked_add(stride - 1).ok_or_else(overflow)? / stride,
+        "VALID" => input.checked_sub(effective).ok_or_else(overflow)? / stride + 1,
+        _ => {
+            let span = input
+                .checked_add(pads.0)
+                .and_then(|v| v.checked_add(pads.1))
+                .and_then(|v| v.checked_sub(effective))
+                .ok_or_else(overflow)?;
+            if ceil_mode {
+                let mut out = span.checked_add(stride - 1).ok_or_else(overflow)? / stride + 1;
+                // the last window must start inside the input or left padding
+                let last = (out - 1).checked_mul(stride).ok_or_else(overflow)?;
+                if last >= input.saturating_add(pads.0) {
+                    out -= 1;
+                }
+                out
+            } else {
+                span / stride + 1
+            }
+        }
+    };
+    Ok(Dim::Fixed(out.max(0)))
+}
+
+struct Inputs<'a> {
+    op: &'a OnnxOperation,
+    values: &'a HashMap<String, ValueInfo>,
+}
+
+impl<'a> Inputs<'a> {
+    /// Required input with known type and shape
+    fn get(&self, index: usize) -> Result<&'a ValueInfo, String> {
+        let name = self
+            .op
+            .inputs
+            .get(index)
+            .filter(|n| !n.is_empty())
+            .ok_or_else(|| format!("missing input {}", index))?;
+        self.values
+            .get(name)
+            .ok_or_else(|| format!("shape of input '{}' is unknown", name))
+    }
+
+    /// Optional input, None when omitted
+    fn optional(&self, index: usize) -> Result<Option<&'a ValueInfo>, String> {
+        match self.op.inputs.get(index) {
+            Some(name) if !name.is_empty() => self.get(index).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Integer values of an optional input, which must be constant when present
+    fn constant(&self, index: usize) -> Result<Option<&'a [i64]>, String> {
+        match self.optional(index)? {
+            Some(info) => info.constant.as_deref().map(Some).ok_or_else(|| {
+                format!("input '{}' is not a known constant", self.op.inputs[index])
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// Integer values from an attribute (older opsets) or an input (newer ones)
+    fn ints(&self, attribute: &str, index: usize) -> Result<Option<Vec<i64>>, String> {
+        if let Some(values) = self.op.get_ints_attribute(attribute) {
+            return Ok(Some(values.to_vec()));
+        }
+        Ok(self.constant(index)?.map(<[i64]>::to_vec))
+    }
+
+    fn int_attr(&self, name: &str, default: i64) -> i64 {
+        self.op.get_int_attribute(name).unwrap_or(default)
+    }
+}
+
+/// Infer output types and shapes of one default-domain operation
+fn infer_operation(
+    op: &OnnxOperation,
+    opset: i64,
+    values: &HashMap<String, ValueInfo>,
+) -> InferResult {
+    let inputs = Inputs { op, values };
+    let one = |info: ValueInfo| Ok(vec![Some(info)]);
+
+    match op.op_type.as_str() {
+        // shape and type preserving
+        "Abs"
+        | "Acos"
+        | "Acosh"
+        | "Asin"
+        | "Asinh"
+        | "Atan"
+        | "Atanh"
+        | "Ceil"
+        | "Celu"
+        | "Cos"
+        | "Cosh"
+        | "Elu"
+        | "Erf"
+        | "Exp"
+        | "Floor"
+        | "Gelu"
+        | "HardSigmoid"
+        | "HardSwish"
+        | "Hardmax"
+        | "Identity"
+        | "InstanceNormalization"
+        | "LeakyRelu"
+        | "Log"
+        | "LogSoftmax"
+        | "LpNormalization"
+        | "LRN"
+        | "Mish"
+        | "Neg"
+        | "Not"
+        | "Reciprocal"
+        | "Relu"
+        | "Round"
+        | "Selu"
+        | "Shrink"
+        | "Sigmoid"
+        | "Sign"
+        | "Sin"
+        | "Sinh"
+        | "Softmax"
+        | "Softplus"
+        | "Softsign"
+        | "Sqrt"
+        | "Tan"
+        | "Tanh"
+        | "ThresholdedRelu"
+        | "Clip"
+        | "BatchNormalization"
+        | "LayerNormalization"
+        | "MeanVarianceNormalization"
+        | "DequantizeLinear"
+        | "QuantizeLinear" => {
+            let x = inputs.get(0)?;
+            let data_type = match op.op_type.as_str() {
+                "DequantizeLinear" => inputs.get(1)?.data_type,
+                "QuantizeLinear" => inputs
+                    .optional(2)?
+                    .map_or(DataType::Uint8, |zp| zp.data_type),
+                _ => x.data_type,
+            };
+            let mut info = ValueInfo::new(data_type, x.dims.clone());
+            if op.op_type == "Identity" {
+                info.constant = x.constant.clone();
+            }
+            one(info)
+        }
+        "IsNaN" | "IsInf" => one(ValueInfo::new(DataType::Bool, inputs.get(0)?.dims.clone())),
+        "Dropout" => {
+            let x = inputs.get(0)?;
+            Ok(vec![
+                Some(ValueInfo::new(x.data_type, x.dims.clone())),
+                Some(ValueInfo::new(DataType::Bool, x.dims.clone())),
+            ])
+        }
+        "Cast" => {
+            let x = inputs.get(0)?;
+            let to = op.get_int_attribute("to").ok_or("missing 'to' attribute")?;
+            let data_type = DataType::from_onnx_type(to as i32);
+            let constant = x.constant.clone().filter(|_| data_type.is_integer());
+            one(ValueInfo::new(data_type, x.dims.clone()).with_constant(constant))
+        }
+        "CastLike" => one(ValueInfo::new(
+            inputs.get(1)?.data_type,
+            inputs.get(0)?.dims.clone(),
+        )),
+
+        // broadcasting elementwise
+        "Add" | "Sub" | "Mul" | "Div" | "Pow" | "Mod" | "PRelu" | "BitShift" | "BitwiseAnd"
+        | "BitwiseOr" | "BitwiseXor" | "Max" | "Min" | "Sum" | "Mean" => {
+            let infos = (0..op.inputs.len())
+                .map(|i| inputs.get(i))
+                .collect::<Result<Vec<_>, _>>()?;
+            let shapes: Vec<&[Dim]> = infos.iter().map(|i| i.dims.as_slice()).collect();
+            let dims = broadcast(&shapes)?;
+            let constant = match infos.as_slice() {
+                [a, b] => fold_binary(&op.op_type, a, b),
+                _ => None,
+            };
+            one(ValueInfo::new(infos[0].data_type, dims).with_constant(constant))
+        }
+        "Equal" | "Greater" | "GreaterOrEqual" | "Less" | "LessOrEqual" | "And" | "Or" | "Xor" => {
+            let dims = broadcast(&[&inputs.get(0)?.dims, &inputs.get(1)?.dims])?;
+            one(ValueInfo::new(DataType::Bool, dims))
+        }
+        "Where" => {
+            let x = inputs.get(1)?;
+            let dims = broadcast(&[&inputs.get(0)?.dims, &x.dims, &inputs.get(2)?.dims])?;
+            one(ValueInfo::new(x.data_type, dims))
+        }
+
+        // linear algebra
+        "MatMul" | "MatMulInteger" => {
+            let a = inputs.get(0)?;
+            let b = inputs.get(1)?;
+            let data_type = if op.op_type == "MatMulInteger" {
+                DataType::Int32
+            } else {
+                a.data_type
+            };
+            one(ValueInfo::new(data_type, matmul_dims(&a.dims, &b.dims)?))
+        }
+        "Gemm" => {
+            let a = inputs.get(0)?;
+            let b = inputs.get(1)?;
+            if a.rank() != 2 || b.rank() != 2 {
+                return Err("Gemm inputs must be 2-D".to_string());
+            }
+            let m = if inputs.int_attr("transA", 0) != 0 {
+                &a.dims[1]
+            } else {
+                &a.dims[0]
+            };
+            let n = if inputs.int_attr("transB", 0) != 0 {
+                &b.dims[0]
+            } else {
+                &b.dims[1]
+            };
+            one(ValueInfo::new(a.data_type, vec![m.clone(), n.clone()]))
+        }
+
+        // convolution and pooling
+        "Conv" | "ConvInteger" | "QLinearConv" => {
+            let (x, w) = if op.op_type == "QLinearConv" {
+                (inputs.get(0)?, inputs.get(3)?)
+            } else {
+                (inputs.get(0)?, inputs.get(1)?)
+            };
+            if x.rank() < 3 || w.rank() != x.rank() {
+                return Err("Conv input and weight ranks do not match".to_string());
+            }
+            let kernel = match op.get_ints_attribute("kernel_shape") {
+                Some(k) => k.to_vec(),
+                None => w.dims[2..]
+                    .iter()
+                    .map(Dim::value)
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or("kernel shape is unknown")?,
+            };
+            let mut dims = vec![x.dims[0].clone(), w.dims[0].clone()];
+            dims.extend(spatial_dims(op, &x.dims[2..], &kernel, false)?);
+            let data_type = match op.op_type.as_str() {
+                "ConvInteger" => DataType::Int32,
+                "QLinearConv" => inputs.get(7)?.data_type,
+                _ => x.data_type,
+            };
+            one(ValueInfo::new(data_type, dims))
+        }
+        "MaxPool" | "AveragePool" | "LpPool" => {
+            let x = inputs.get(0)?;
+            if x.rank() < 3 {
+                return Err("pooling input must have rank >= 3".to_string());
+            }
+            let kernel = op
+                .get_ints_attribute("kernel_shape")
+                .ok_or("missing 'kernel_shape' attribute")?;
+            let ceil_mode = inputs.int_attr("ceil_mode", 0) != 0;
+            let mut dims = x.dims[..2].to_vec();
+            dims.extend(spatial_dims(op, &x.dims[2..], kernel, ceil_mode)?);
+            Ok(vec![
+                Some(ValueInfo::new(x.data_type, dims.clone())),
+                Some(ValueInfo::new(DataType::Int64, dims)),
+            ])
+        }
+        "GlobalAveragePool" | "GlobalMaxPool" | "GlobalLpPool" => {
+            let x = inputs.get(0)?;
+            if x.rank() < 2 {
+                return Err("pooling input must have rank >= 2".to_string());
+            }
+            let mut dims = x.dims[..2].to_vec();
+            dims.resize(x.rank(), Dim::Fixed(1));
+            one(ValueInfo::new(x.data_type, dims))
+        }
+
+        // shape manipulation
+        "Shape" => {
+            let x = inputs.get(0)?;
+            let rank = x.rank() as i64;
+            let clamp = |v: i64| (if v < 0 { v + rank } else { v }).clamp(0, rank) as usize;
+            let start = clamp(inputs.int_attr("start", 0));
+            let end = clamp(inputs.int_attr("end", rank)).max(start);
+            let constant = x.dims[start..end]
+                .iter()
+                .map(Dim::value)
+                .collect::<Option<Vec<_>>>();
+            one(
+                ValueInfo::new(DataType::Int64, vec![Dim::Fixed((end - start) as i64)])
+                    .with_constant(constant),
+            )
+        }
+        "Size" => {
+            let x = inputs.get(0)?;
+            let constant = dim_product(&x.dims)?.value().map(|v| vec![v]);
+            one(ValueInfo::new(DataType::Int64, vec![]).with_constant(constant))
+        }
+        "Constant" => one(infer_constant(op)?),
+        "ConstantOfShape" => {
+            let shape = inputs.constant(0)?.ok_or("missing shape input")?;
+            let data_type = op
+                .get_attribute("value")
+                .and_then(AttributeValue::as_tensor)
+                .map_or(DataType::Float, OnnxTensor::data_type);
+            one(ValueInfo::new(data_type, fixed(shape)))
+        }
+        "Reshape" => {
+            let x = inputs.get(0)?;
+            let shape = inputs.ints("shape", 1)?.ok_or("missing shape input")?;
+            let allow_zero = inputs.int_attr("allowzero", 0) != 0;
+            one(
+                ValueInfo::new(x.data_type, reshape_dims(&x.dims, &shape, allow_zero)?)
+                    .with_constant(x.constant.clone()),
+            )
+        }
+        "Flatten" => {
+            let x = inputs.get(0)?;
+            let axis = inputs.int_attr("axis", 1);
+            let axis = if axis == x.rank() as i64 {
+                x.rank()
+            } else {
+                normalize_axis(axis, x.rank())?
+            };
+            one(ValueInfo::new(
+                x.data_type,
+                vec![dim_product(&x.dims[..axis])?, dim_product(&x.dims[axis..])?],
+            ))
+        }
+        "Transpose" => {
+            let x = inputs.get(0)?;
+            let perm: Vec<i64> = match op.get_ints_attribute("perm") {
+                Some(p) => p.to_vec(),
+                None => (0..x.rank() as i64).rev().collect(),
+            };
+            let perm = normalize_unique_axes(&perm, x.rank())?;
+            if perm.len() != x.rank() {
+                return Err("perm does not match input rank".to_string());
+            }
+            let dims = perm.iter().map(|&a| x.dims[a].clone()).collect();
+            one(ValueInfo::new(x.data_type, dims))
+        }
+        "Squeeze" => {
+            let x = inputs.get(0)?;
+            let axes = inputs.ints("axes", 1)?;
+            let dims = match axes {
+                Some(axes) => {
+                    let axes = axes
+                        .iter()
+                        .map(|&a| normalize_axis(a, x.rank()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    x.dims
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| !axes.contains(i))
+                        .map(|(_, d)| d.clone())
+                        .collect()
+                }
+                None => {
+                    if x.dims.iter().any(|d| d.value().is_none()) {
+                        return Err("cannot squeeze dims of unknown size".to_string());
+                    }
+                    x.dims
+                        .iter()
+                        .filter(|d| **d != Dim::Fixed(1))
+                        .cloned()
+                        .collect()
+                }
+            };
+            one(ValueInfo::new(x.data_type, dims).with_constant(x.constant.clone()))
+        }
+        "Unsqueeze" => {
+            let x = inputs.get(0)?;
+            let axes = inputs.ints("axes", 1)?.ok_or("missing axes")?;
+            let mut axes = normalize_unique_axes(&axes, x.rank() + axes.len())?;
+            axes.sort_unstable();
+            let mut dims = x.dims.clone();
+            for axis in axes {
+                dims.insert(axis, Dim::Fixed(1));
+            }
+            one(ValueInfo::new(x.data_type, dims).with_constant(x.constant.clone()))
+        }
+        "Concat" => {
+            let infos = (0..op.inputs.len())
+                .map(|i| inputs.get(i))
+                .collect::<Result<Vec<_>, _>>()?;
+            let first = infos.first().ok_or("Concat has no inputs")?;
+            let axis = normalize_axis(
+                op.get_int_attribute("axis")
+                    .ok_or("missing 'axis' attribute")?,
+                first.rank(),
+            )?;
+            let mut dims = first.dims.clone();
+            let axis_dims: Vec<Dim> = infos
+                .iter()
+                .map(|i| i.dims.get(axis).cloned().unwrap_or(Dim::Unknown))
+                .collect();
+            dims[axis] = match axis_dims.iter().map(Dim::value).collect::<Option<Vec<_>>>() {
+                Some(sizes) => sizes
+                    .into_iter()
+                    .try_fold(0i64, i64::checked_add)
+                    .map(Dim::Fixed)
+                    .ok_or("concatenated dim overflows")?,
+                None => Dim::Unknown,
+            };
+            let constant = infos
+                .iter()
+                .map(|i| i.constant.clone())
+                .collect::<Option<Vec<_>>>()
+                .filter(|_| first.rank() == 1)
+                .map(|parts| parts.concat());
+            one(ValueInfo::new(first.data_type, dims).with_constant(constant))
+        }
+        "Split" => {
+            let x = inputs.get(0)?;
+            let axis = normalize_axis(inputs.int_attr("axis", 0), x.rank())?;
+            let count = op.outputs.len();
+            if count == 0 {
+                return Err("Split has no outputs".to_string());
+            }
+            let sizes = match inputs.ints("split", 1)? {
+                Some(split) => fixed(&split),
+                None => match x.dims[axis].value() {
+                    Some(d) => {
+                        let chunk = (d + count as i64 - 1) / count as i64;
+                        (0..count as i64)
+                            .map(|i| Dim::Fixed(chunk.min(d - chunk * i).max(0)))
+                            .collect()
+                    }
+                    None => vec![Dim::Unknown; count],
+                },
+            };
+            Ok(sizes
+                .into_iter()
+                .map(|size| {
+                    let mut dims = x.dims.clone();
+                    dims[axis] = size;
+                    Some(ValueInfo::new(x.data_type, dims))
+                })
+                .collect())
+        }
+        "Slice" => {
+            let x = inputs.get(0)?;
+            let (starts, ends, axes, steps) = if opset >= 10 {
+                (
+                    inputs.constant(1)?.ok_or("missing starts")?.to_vec(),
+                    inputs.constant(2)?.ok_or("missing ends")?.to_vec(),
+                    inputs.constant(3)?.map(<[i64]>::to_vec),
+                    inputs.constant(4)?.map(<[i64]>::to_vec),
+                )
+            } else {
+                (
+                    op.get_ints_attribute("starts")
+                        .ok_or("missing starts")?
+                        .to_vec(),
+                    op.get_ints_attribute("ends")
+                        .ok_or("missing ends")?
+                        .to_vec(),
+                    op.get_ints_attribute("axes").map(<[i64]>::to_vec),
+                    None,
+                )
+            };
+            let axes = axes.unwrap_or_else(|| (0..starts.len() as i64).collect());
+            let steps = steps.unwrap_or_else(|| vec![1; starts.len()]);
+            slice_info(x, &starts, &ends, &axes, &steps).map(|info| vec![Some(info)])
+        }
+        "Gather" => {
+            let data = inputs.get(0)?;
+            let indices = inputs.get(1)?;
+            let axis = normalize_axis(inputs.int_attr("axis", 0), data.rank())?;
+            let mut dims = data.dims[..axis].to_vec();
+            dims.extend(indices.dims.iter().cloned());
+            dims.extend(data.dims[axis + 1..].iter().cloned());
+            let constant = match (&data.constant, &indices.constant) {
+                (Some(values), Some(idx)) if data.rank() == 1 => idx
+                    .iter()
+                    .map(|&i| {
+                        let i = if i < 0 { i + values.len() as i64 } else { i };
+                        values.get(usize::try_from(i).ok()?).copied()
+                    })
+                    .collect(),
+                _ => None,
+            };
+            one(ValueInfo::new(data.data_type, dims).with_constant(constant))
+        }
+        "GatherElements" => {
+            let data = inputs.get(0)?;
+            one(ValueInfo::new(data.data_type, inputs.get(1)?.dims.clone()))
+        }
+        "Expand" => {
+            let x = inputs.get(0)?;
+            let shape = inputs.constant(1)?.ok_or("missing shape input")?;
+            one(ValueInfo::new(
+                x.data_type,
+                broadcast(&[&x.dims, &fixed(shape)])?,
+            ))
+        }
+        "Tile" => {
+            let x = inputs.get(0)?;
+            let repeats = inputs.constant(1)?.ok_or("missing repeats input")?;
+            if repeats.len() != x.rank() {
+                return Err("repeats length does not match input rank".to_string());
+            }
+            let dims = x
+                .dims
+                .iter()
+                .zip(repeats)
+                .map(|(d, &r)| dim_product(&[d.clone(), Dim::Fixed(r)]))
+                .collect::<Result<_, _>>()?;
+            one(ValueInfo::new(x.data_type, dims))
+        }
+        "Pad" => {
+            let x = inputs.get(0)?;
+            let pads = inputs.ints("pads", 1)?.ok_or("missing pads")?;
+            let axes: Vec<usize> = match inputs.constant(3)? {
+                Some(axes) => axes
+                    .iter()
+                    .map(|&a| normalize_axis(a, x.rank()))
+                    .collect::<Result<_, _>>()?,
+                None => (0..x.rank()).collect(),
+            };
+            if pads.len() != axes.len() * 2 {
+                return Err("pads length does not match axes".to_string());
+            }
+            let mut dims = x.dims.clone();
+            for (i, &axis) in axes.iter().enumerate() {
+                dims[axis] = match dims[axis].value() {
+                    Some(d) => d
+                        .checked_add(pads[i])
+                        .and_then(|d| d.checked_add(pads[i + axes.len()]))
+                        .map(Dim::Fixed)
+                        .ok_or("padded dim overflows")?,
+                    None => Dim::Unknown,
+                };
+            }
+            one(ValueInfo::new(x.data_type, dims))
+        }
+        "DepthToSpace" | "SpaceToDepth" => {
+            let x = inputs.get(0)?;
+            if x.rank() != 4 {
+                return Err("input must be 4-D".to_string());
+            }
+            let b = op
+                .get_int_attribute("blocksize")
+                .ok_or("missing 'blocksize' attribute")?;
+            if b <= 0 {
+                return Err("blocksize must be positive".to_string());
+            }
+            let area = b.checked_mul(b).ok_or("blocksize overflows")?;
+            let up = op.op_type == "DepthToSpace";
+            let scale = |d: &Dim, factor: i64, up: bool| match d.value() {
+                Some(v) if up => v
+                    .checked_mul(factor)
+                    .map(Dim::Fixed)
+                    .ok_or("output dim overflows"),
+                Some(v) => Ok(Dim::Fixed(v / factor)),
+                None => Ok(Dim::Unknown),
+            };
+            one(ValueInfo::new(
+                x.data_type,
+                vec![
+                    x.dims[0].clone(),
+                    scale(&x.dims[1], area, !up)?,
+                    scale(&x.dims[2], b, up)?,
+                    scale(&x.dims[3], b, up)?,
+                ],
+            ))
+        }
+        "Resize" | "Upsample" => {
+            let x = inputs.get(0)?;
+            let sizes = if op.op_type == "Resize" {
+                inputs.constant(3)?
+            } else {
+                None
+            };
+            let sizes = sizes.ok_or("only constant 'sizes' are supported")?;
+            if sizes.len() != x.rank() {
+                return Err("sizes length does not match input rank".to_string());
+            }
+            one(ValueInfo::new(x.data_type, fixed(sizes)))
+        }
+
+        // reductions
+        "ReduceMean" | "ReduceSum" | "ReduceMax" | "ReduceMin" | "ReduceProd" | "ReduceL1"
+        | "ReduceL2" | "ReduceLogSum" | "ReduceLogSumExp" | "ReduceSumSquare" => {
+            let x = inputs.get(0)?;
+            let keep = inputs.int_attr("keepdims", 1) != 0;
+            let axes = inputs.ints("axes", 1)?.unwrap_or_default();
+            if axes.is_empty() && inputs.int_attr("noop_with_empty_axes", 0) != 0 {
+                return one(ValueInfo::new(x.data_type, x.dims.clone()));
+            }
+            let axes: Vec<usize> = if axes.is_empty() {
+                (0..x.rank()).collect()
+            } else {
+                axes.iter()
+                    .map(|&a| normalize_axis(a, x.rank()))
+                    .collect::<Result<_, _>>()?
+            };
+            one(ValueInfo::new(
+                x.data_type,
+                reduce_dims(&x.dims, &axes, keep),
+            ))
+        }
+        "ArgMax" | "ArgMin" => {
+            let x = inputs.get(0)?;
+            let axis = normalize_axis(inputs.int_attr("axis", 0), x.rank())?;
+            let keep = inputs.int_attr("keepdims", 1) != 0;
+            one(ValueInfo::new(
+                DataType::Int64,
+                reduce_dims(&x.dims, &[axis], keep),
+            ))
+        }
+        "TopK" => {
+            let x = inputs.get(0)?;
+            let axis = normalize_axis(inputs.int_attr("axis", -1), x.rank())?;
+            let k = match inputs.constant(1)? {
+                Some([k]) => Dim::Fixed(*k),
+                Some(_) => return Err("K must be a single value".to_string()),
+                None => op.get_int_attribute("k").map_or(Dim::Unknown, Dim::Fixed),
+            };
+            let mut dims = x.dims.clone();
+            dims[axis] = k;
+            Ok(vec![
+                Some(ValueInfo::new(x.data_type, dims.clone())),
+                Some(ValueInfo::new(DataType::Int64, dims)),
+            ])
+        }
+        "NonZero" => {
+            let x = inputs.get(0)?;
+            one(ValueInfo::new(
+                DataType::Int64,
+                vec![Dim::Fixed(x.rank() as i64), Dim::Unknown],
+            ))
+        }
+        "Range" => {
+            let start = inputs.get(0)?;
+            let len = match (
+                inputs.constant(0)?,
+                inputs.constant(1)?,
+                inputs.constant(2)?,
+            ) {
+                (Some([s]), Some([l]), Some([d])) if *d != 0 => {
+                    Dim::Fixed(((*l as f64 - *s as f64) / *d as f64).ceil().max(0.0) as i64)
+                }
+                _ => Dim::Unknown,
+            };
+            one(ValueInfo::new(start.data_type, vec![len]))
+        }
+        _ => Err(format!("no shape inference for '{}'", op.op_type)),
+    }
+}
+
+/// Output value of a Constant operation
+fn infer_constant(op: &OnnxOperation) -> Result<ValueInfo, String> {
+    let (name, value) = op
+        .attributes
+        .iter()
+        .next()
+        .ok_or("Constant has no value attribute")?;
+    let info = match value {
+        AttributeValue::Tensor(t) => {
+            ValueInfo::new(t.data_type(), t.dims().to_vec()).with_constant(int_constant(t))
+        }
+        AttributeValue::SparseTensor(t) => ValueInfo::new(t.data_type(), fixed(t.shape())),
+        AttributeValue::Int(v) => {
+            ValueInfo::new(DataType::Int64, vec![]).with_constant(Some(vec![*v]))
+        }
+        AttributeValue::Ints(v) => {
+            ValueInfo::new(DataType::Int64, fixed(&[v.len() as i64])).with_constant(Some(v.clone()))
+        }
+        AttributeValue::Float(_) => ValueInfo::new(DataType::Float, vec![]),
+        AttributeValue::Floats(v) => ValueInfo::new(DataType::Float, fixed(&[v.len() as i64])),
+        AttributeValue::String(_) => ValueInfo::new(DataType::String, vec![]),
+        AttributeValue::Strings(v) => ValueInfo::new(DataType::String, fixed(&[v.len() as i64])),
+        _ => return Err(format!("unsupported Constant attribute '{}'", name)),
+    };
+    Ok(info)
+}
+
+/// Evaluate integer arithmetic on small constants, for shape computations
+fn fold_binary(op_type: &str, a: &ValueInfo, b: &ValueInfo) -> Option<Vec<i64>> {
+    let (x, y) = (a.constant.as_ref()?, b.constant.as_ref()?);
+    let apply = |l: i64, r: i64| match op_type {
+        "Add" => l.checked_add(r),
+        "Sub" => l.checked_sub(r),
+        "Mul" => l.checked_mul(r),
+        "Div" => l.checked_div(r),
+        "Max" => Some(l.max(r)),
+        "Min" => Some(l.min(r)),
+        _ => None,
+    };
+    match (x.len(), y.len()) {
+        (n, m) if n == m => x.iter().zip(y).map(|(&l, &r)| apply(l, r)).collect(),
+        (_, 1) => x.iter().map(|&l| apply(l, y[0])).collect(),
+        (1, _) => y.iter().map(|&r| apply(x[0], r)).collect(),
+        _ => None,
+    }
+}
+
+fn matmul_dims(a: &[Dim], b: &[Dim]) -> Result<Vec<Dim>, String> {
+    if a.is_empty() || b.is_empty() {
+        return Err("MatMul inputs must not be scalars".to_string());
+    }
+    // 1-D operands are promoted to matrices and the added dim removed afterwards
+    let a2: Vec<Dim> = if a.len() == 1 {
+        vec![Dim::Fixed(1), a[0].clone()]
+    } else {
+        a.to_vec()
+    };
+    let b2: Vec<Dim> = if b.len() == 1 {
+        vec![b[0].clone(), Dim::Fixed(1)]
+    } else {
+        b.to_vec()
+    };
+
+    let batch = broadcast(&[&a2[..a2.len() - 2], &b2[..b2.len() - 2]])?;
+    let mut dims = batch;
+    if a.len() > 1 {
+        dims.push(a2[a2.len() - 2].clone());
+    }
+    if b.len() > 1 {
+        dims.push(b2[b2.len() - 1].clone());
+    }
+    Ok(dims)
+}
+
+/// Spatial output dims for Conv and pooling attributes
+fn spatial_dims(
+    op: &OnnxOperation,
+    input: &[Dim],
+    kernel: &[i64],
+    ceil_mode: bool,
+) -> Result<Vec<Dim>, String> {
+    let n = input.len();
+    if kernel.len() != n {
+        return Err("kernel_shape does not match spatial rank".to_string());
+    }
+    let strides = op
+        .get_ints_attribute("strides")
+        .map_or(vec![1; n], <[i64]>::to_vec);
+    let dilations = op
+        .get_ints_attribute("dilations")
+        .map_or(vec![1; n], <[i64]>::to_vec);
+    let pads = op
+        .get_ints_attribute("pads")
+        .map_or(vec![0; 2 * n], <[i64]>::to_vec);
+    let auto_pad = op.get_string_attribute("auto_pad").unwrap_or("NOTSET");
+    if strides.len() != n || dilations.len() != n || pads.len() != 2 * n {
+        return Err("strides, dilations or pads do not match spatial rank".to_string());
+    }
+    (0..n)
+        .map(|i| {
+            window_output(
+                &input[i],
+                kernel[i],
+                strides[i],
+                dilations[i],
+                (pads[i], pads[i + n]),
+                auto_pad,
+                ceil_mode,
+            )
+        })
+        .collect()
+}
+
+fn reshape_dims(input: &[Dim], shape: &[i64], allow_zero: bool) -> Result<Vec<Dim>, String> {
+    let mut dims: Vec<Dim> = Vec::with_capacity(shape.len());
+    let mut infer_at = None;
+    for (i, &s) in shape.iter().enumerate() {
+        dims.push(match s {
+            -1 if infer_at.is_none() => {
+                infer_at = Some(i);
+                Dim::Unknown
+            }
+            -1 => return Err("Reshape shape has more than one -1".to_string()),
+            0 if !allow_zero => input
+                .get(i)
+                .cloned()
+                .ok_or("Reshape copies a missing dim")?,
+            s if s < 0 => return Err(format!("invalid Reshape dim {}", s)),
+            s => Dim::Fixed(s),
+        });
+    }
+    if let Some(i) = infer_at {
+        let mut others = dims.clone();
+        others.remove(i);
+        if let Some(total) = dim_product(input)?.value()
+            && let Some(known) = dim_product(&others)?.value()
+            && known != 0
+        {
+            dims[i] = Dim::Fixed(total / known);
+        }
+    }
+    Ok(dims)
+}
+
+fn reduce_dims(dims: &[Dim], axes: &[usize], keep: bool) -> Vec<Dim> {
+    dims.iter()
+        .enumerate()
+        .filter_map(|(i, d)| match (axes.contains(&i), keep) {
+            (true, true) => Some(Dim::Fixed(1)),
+            (true, false) => None,
+            (false, _) => Some(d.clone()),
+        })
+        .collect()
+}
+
+/// Clamped start and element count of a Slice along a dim of size `d`
+pub(crate) fn slice_range(d: i64, start: i64, end: i64, step: i64) -> Result<(i64, i64), String> {
+    if step == 0 {
+        return Err("Slice step must not be zero".to_string());
+    }
+    if d <= 0 {
+        return Ok((0, 0));
+    }
+    let resolve = |v: i64| if v < 0 { v.saturating_add(d) } else { v };
+    let (start, span) = if step > 0 {
+        let start = resolve(start).clamp(0, d);
+        (start, resolve(end).clamp(0, d) - start)
+    } else {
+        let start = resolve(start).clamp(0, d - 1);
+        (start, start - resolve(end).clamp(-1, d - 1))
+    };
+    // a step longer than the dim takes at most one element
+    let stride = step.unsigned_abs().min(d as u64) as i64;
+    let len = if span > 0 { (span - 1) / stride + 1 } else { 0 };
+    Ok((start, len))
+}
+
+fn slice_info(
+    x: &ValueInfo,
+    starts: &[i64],
+    ends: &[i64],
+    axes: &[i64],
+    steps: &[i64],
+) -> Result<ValueInfo, String> {
+    if starts.len() != ends.len() || axes.len() != starts.len() || steps.len() != starts.len() {
+        return Err("starts, ends, axes and steps lengths differ".to_string());
+    }
+    let axes = normalize_unique_axes(axes, x.rank())?;
+    let mut dims = x.dims.clone();
+    let mut ranges: Vec<Option<(i64, i64, i64)>> = vec![None; x.rank()];
+    for (i, &axis) in axes.iter().enumerate() {
+        let step = steps[i];
+        if step == 0 {
+            return Err("Slice step must not be zero".to_string());
+        }
+        let Some(d) = dims[axis].value() else {
+            dims[axis] = Dim::Unknown;
+            continue;
+        };
+        let (start, len) = slice_range(d, starts[i], ends[i], step)?;
+        dims[axis] = Dim::Fixed(len);
+        ranges[axis] = Some((start, len, step));
+    }
+
+    let constant = match (&x.constant, ranges.as_slice()) {
+        (Some(values), [Some((start, len, step))]) => Some(
+            (0..*len)
+                .map(|k| values[(start + k * step) as usize])
+                .collect(),
+        ),
+        (Some(values), [None]) => Some(values.clone()),
+        _ => None,
+    };
+    Ok(ValueInfo::new(x.data_type, dims).with_constant(constant))
+}
@@ -0,0 +1,179 @@
+use crate::{Bytes, DataType, Error, OnnxTensor};
+
+/// Information about an ONNX sparse tensor
+///
+/// Stores the non-default `values` alongside their `indices` into a dense
+/// tensor of the given shape. Indices are either a `[NNZ]` tensor of
+/// linearized positions or a `[NNZ, rank]` tensor of coordinates.
+#[derive(Debug, Clone)]
+pub struct OnnxSparseTensor {
+    values: OnnxTensor,
+    indices: OnnxTensor,
+    shape: Vec<i64>,
+}
+
+impl OnnxSparseTensor {
+    pub(crate) fn new(values: OnnxTensor, indices: OnnxTensor, shape: Vec<i64>) -> Self {
+        OnnxSparseTensor {
+            values,
+            indices,
+            shape,
+        }
+    }
+
+    /// Sparse tensor name, taken from the values tensor
+    pub fn name(&self) -> &str {
+        self.values.name()
+    }
+
+    pub(crate) fn set_name(&mut self, name: String) {
+        self.values.set_name(name);
+    }
+
+    /// Shape of the equivalent dense tensor
+    pub fn shape(&self) -> &[i64] {
+        &self.shape
+    }
+
+    /// Element data type
+    pub fn data_type(&self) -> DataType {
+        self.values.data_type()
+    }
+
+    /// Non-default values with shape `[NNZ]`
+    pub fn values(&self) -> &OnnxTensor {
+        &self.values
+    }
+
+    /// Int64 indices of the non-default values
+    pub fn indices(&self) -> &OnnxTensor {
+        &self.indices
+    }
+
+    /// Number of stored (non-default) values
+    pub fn nnz(&self) -> usize {
+        self.values
+            .shape()
+            .first()
+            .map_or(0, |&n| n.max(0) as usize)
+    }
+
+    /// Expand into a dense tensor with the same name
+    ///
+    /// Positions without a stored value are zero-filled. Call
+    /// [`OnnxTensor::into_data`] on the result for the dense buffer.
+    pub fn to_dense(&self) -> Result<OnnxTensor, Error> {
+        let elem_size = self.data_type().size_in_bytes().ok_or_else(|| {
+            Error::Unsupported(format!(
+                "densifying sparse tensor of type {:?}",
+                self.data_type()
+            ))
+        })?;
+
+        let dense_len = self
+            .shape
+            .iter()
+            .try_fold(1usize, |acc, &d| acc.checked_mul(usize::try_from(d).ok()?));
+        let dense_len = dense_len.ok_or_else(|| {
+            Error::InvalidModel(format!("Invalid sparse tensor shape {:?}", self.shape))
+        })?;
+
+        let nnz = self.nnz();
+        let values = self.values.data()?;
+        let values = values.as_slice();
+        if values.len() != nnz * elem_size {
+            return Err(Error::DataConversion(format!(
+                "Sparse tensor '{}' has {} value bytes, expected {}",
+                self.name(),
+                values.len(),
+                nnz * elem_size
+            )));
+        }
+
+        let positions = self.linear_indices(dense_len)?;
+        let mut dense = vec![0u8; dense_len * elem_size];
+        for (i, pos) in positions.into_iter().enumerate() {
+            let dst = pos * elem_size;
+            let src = i * elem_size;
+            dense[dst..dst + elem_size].copy_from_slice(&values[src..src + elem_size]);
+        }
+
+        Ok(OnnxTensor::from_raw_data(
+            self.name().to_string(),
+            self.shape.clone(),
+            self.data_type(),
+            Bytes::from(dense),
+        ))
+    }
+
+    /// Convert indices of either supported layout into linear positions
+    fn linear_indices(&self, dense_len: usize) -> Result<Vec<usize>, Error> {
+        let nnz = self.nnz();
+        let raw = self.indices.copy_data_as::<i64>()?;
+        let rank = self.shape.len();
+
+        let coords_per_value = match self.indices.shape() {
+            [n] if *n as usize == nnz => 1,
+            [n, r] if *n as usize == nnz && *r as usize == rank => rank,
+            other => {
+                return Err(Error::InvalidModel(format!(
+                    "Sparse tensor '{}' has indices shape {:?}, expected [{}] or [{}, {}]",
+                    self.name(),
+                    other,
+                    nnz,
+                    nnz,
+                    rank
+                )));
+            }
+        };
+        if raw.len() != nnz * coords_per_value {
+            return Err(Error::DataConversion(format!(
+                "Sparse tensor '{}' has {} indices, expected {}",
+                self.name(),
+                raw.len(),
+                nnz * coords_per_value
+            )));
+        }
+
+        let out_of_range = || {
+            Error::InvalidModel(format!(
+                "Sparse tensor '{}' has an index outside shape {:?}",
+                self.name(),
+                self.shape
+            ))
+        };
+
+        let mut positions = Vec::with_capacity(nnz);
+        if rank == 0 && coords_per_value == 0 {
+            // a scalar has no coordinates and room for a single value
+            if nnz > 1 {
+                return Err(Error::InvalidModel(format!(
+                    "Sparse tensor '{}' is a scalar with {} values",
+                    self.name(),
+                    nnz
+                )));
+            }
+            positions.resize(nnz, 0);
+        } else if coords_per_value == 1 {
+            for &idx in raw.iter() {
+                let pos = usize::try_from(idx).map_err(|_| out_of_range())?;
+                if pos >= dense_len {
+                    return Err(out_of_range());
+                }
+                positions.push(pos);
+            }
+        } else {
+            for coords in raw.chunks_exact(rank) {
+                let mut pos = 0usize;
+                for (&c, &d) in coords.iter().zip(&self.shape) {
+                    if c < 0 || c >= d {
+                        return Err(out_of_range());
+                    }
+                    pos = pos * d as usize + c as usize;
+                }
+                positions.push(pos);
+            }
+        }
+        Ok(positions)
+    }
+}
@@ -1,11 +1,12 @@
 use prost::bytes::Bytes;
 use std::borrow::Cow;
-use std::mem::{ManuallyDrop, MaybeUninit};
-use std::{any, mem, ptr, slice};
+use std::collections::HashMap;
+use std::mem::ManuallyDrop;
+use std::{any, mem, slice};
 
 use crate::{
-    DataType, Error, TensorProto, external_data::ExternalDataInfo,
-    tensor_shape_proto::dimension::Value, type_proto::Tensor,
+    DataType, Dim, Error, OnnxElement, PackedElements, TensorProto, convert,
+    external_data::ExternalDataInfo, packed, proto_adapter, type_proto::Tensor,
 };
 
 #[derive(Debug, Clone)]
@@ -21,7 +22,7 @@ pub(crate) enum TensorDataLocation {
 pub enum TensorData<'a> {
     /// Contiguous buffer from raw_data field, Arc-backed
     Raw(Bytes),
-    /// Reinterpreted numeric data from typed fields
+    /// Numeric data from typed fields, packed in the element layout of raw_data
     Numeric(Cow<'a, [u8]>),
     /// String tensor elements, each Arc-backed
     Strings(Vec<Bytes>),
@@ -96,10 +97,11 @@ impl<'a> TensorData<'a> {
 }
 
 /// Information about an ONNX tensor
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct OnnxTensor {
     name: String,
     shape: Vec<i64>,
+    dims: Vec<Dim>,
     data_type: DataType,
     proto: Option<TensorProto>,
     data_location: Option<TensorDataLocation>,
@@ -113,43 +115,120 @@ impl OnnxTensor {
         proto: Option<TensorProto>,
         data_location: Option<TensorDataLocation>,
     ) -> Self {
+        let dims = shape.iter().map(|&d| Dim::Fixed(d)).collect();
         OnnxTensor {
             name,
             shape,
+            dims,
             data_type,
             proto,
             data_location,
         }
     }
 
+    /// Create a shape-only tensor, as for value_info entries
+    pub(crate) fn from_dims(name: String, data_type: DataType, dims: Vec<Dim>) -> Self {
+        OnnxTensor {
+            name,
+            shape: dims.iter().map(Dim::as_i64).collect(),
+            dims,
+            data_type,
+            proto: None,
+            data_location: None,
+        }
+    }
+
+    /// Create a tensor holding little-endian `raw_data` bytes
+    ///
+    /// Useful for adding new initializers before saving a model. The byte
+    /// length is not checked against `shape` and `data_type`.
+    pub fn from_raw_data(
+        name: String,
+        shape: Vec<i64>,
+        data_type: DataType,
+        raw_data: Bytes,
+    ) -> Self {
+        let proto = TensorProto {
+            raw_data: Some(raw_data),
+            ..Default::default()
+        };
+        OnnxTensor::new(
+            name,
+            shape,
+            data_type,
+            Some(proto),
+            Some(TensorDataLocation::Internal),
+        )
+    }
+
     /// Tensor name
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    pub(crate) fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
     /// Tensor shape dimensions
+    ///
+    /// Symbolic and unknown dimensions are reported as `-1`, see
+    /// [`dims`](Self::dims) to tell them apart.
     pub fn shape(&self) -> &[i64] {
         &self.shape
     }
 
+    /// Tensor shape dimensions including symbolic names
+    pub fn dims(&self) -> &[Dim] {
+        &self.dims
+    }
+
+    /// Resolve the shape using values for symbolic dimensions
+    ///
+    /// Returns an error if a symbolic dimension has no binding or a
+    /// dimension is unknown.
+    pub fn bind_shape(&self, bindings: &HashMap<String, i64>) -> Result<Vec<i64>, Error> {
+        self.dims
+            .iter()
+            .enumerate()
+            .map(|(axis, dim)| match dim {
+                Dim::Fixed(v) => Ok(*v),
+                Dim::Symbolic(sym) => bindings.get(sym).copied().ok_or_else(|| {
+                    Error::InvalidModel(format!(
+                        "No binding for symbolic dimension '{}' of tensor '{}'",
+                        sym, self.name
+                    ))
+                }),
+                Dim::Unknown => Err(Error::InvalidModel(format!(
+                    "Dimension {} of tensor '{}' is unknown",
+                    axis, self.name
+                ))),
+            })
+            .collect()
+    }
+
     /// Tensor data type
     pub fn data_type(&self) -> DataType {
         self.data_type
     }
 
+    /// Underlying proto, if the tensor was created from a TensorProto
+    pub(crate) fn proto(&self) -> Option<&TensorProto> {
+        self.proto.as_ref()
+    }
+
+    /// Where the tensor data is stored, or None for shape-only tensors
+    pub(crate) fn data_location(&self) -> Option<&TensorDataLocation> {
+        self.data_location.as_ref()
+    }
+
     pub(crate) fn from_tensor_type(name: String, tensor_type: &Tensor) -> Result<Self, Error> {
-        let shape = if let Some(shape_proto) = &tensor_type.shape {
-            shape_proto
-                .dim
-                .iter()
-                .map(|d| match &d.value {
-                    Some(Value::DimValue(v)) => *v,
-                    _ => -1,
-                })
-                .collect()
-        } else {
-            Vec::new()
-        };
+        let dims: Vec<Dim> = tensor_type
+            .shape
+            .as_ref()
+            .map(proto_adapter::dims_from_shape_proto)
+            .unwrap_or_default();
+        let shape = dims.iter().map(Dim::as_i64).collect();
 
         let elem_type = tensor_type
             .elem_type
@@ -160,18 +239,21 @@ impl OnnxTensor {
             ));
         }
 
-        Ok(OnnxTensor::new(
+        Ok(OnnxTensor {
             name,
             shape,
-            DataType::from_onnx_type(elem_type),
-            None,
-            None,
-        ))
+            dims,
+            data_type: DataType::from_onnx_type(elem_type),
+            proto: None,
+            data_location: None,
+        })
     }
 
     /// Borrow tensor data
     ///
     /// Raw and Strings variants clone Arc pointers only, Numeric borrows directly.
+    /// Typed fields wider than the element type are packed into a new buffer
+    /// matching the raw_data layout.
     /// Call into_owned on the result to detach from tensor lifetime.
     /// For external data, this lazily loads the data from the external file.
     #[inline]
@@ -195,31 +277,27 @@ impl OnnxTensor {
         }
 
         match storage_backing(self.data_type) {
-            Some(StorageBacking::F32) => {
-                Ok(TensorData::Numeric(Cow::Borrowed(slice_bytes_as::<f32>(
-                    t.float_data.as_slice(),
-                ))))
-            }
-            Some(StorageBacking::F64) => {
-                Ok(TensorData::Numeric(Cow::Borrowed(slice_bytes_as::<f64>(
-                    t.double_data.as_slice(),
-                ))))
-            }
-            Some(StorageBacking::I64) => {
-                Ok(TensorData::Numeric(Cow::Borrowed(slice_bytes_as::<i64>(
-                    t.int64_data.as_slice(),
-                ))))
-            }
-            Some(StorageBacking::U64) => {
-                Ok(TensorData::Numeric(Cow::Borrowed(slice_bytes_as::<u64>(
-                    t.uint64_data.as_slice(),
-                ))))
-            }
-            Some(StorageBacking::I32) => {
-                Ok(TensorData::Numeric(Cow::Borrowed(slice_bytes_as::<i32>(
-                    t.int32_data.as_slice(),
-                ))))
-            }
+            Some(StorageBacking::F32) => Ok(TensorData::Numeric(le_bytes_of::<f32>(
+                t.float_data.as_slice(),
+            ))),
+            Some(StorageBacking::F64) => Ok(TensorData::Numeric(le_bytes_of::<f64>(
+                t.double_data.as_slice(),
+            ))),
+            Some(StorageBacking::I64) => Ok(TensorData::Numeric(le_bytes_of::<i64>(
+                t.int64_data.as_slice(),
+            ))),
+            Some(StorageBacking::U64) => Ok(TensorData::Numeric(le_bytes_of::<u64>(
+                t.uint64_data.as_slice(),
+            ))),
+            Some(StorageBacking::I32) => Ok(TensorData::Numeric(le_bytes_of::<i32>(
+                t.int32_data.as_slice(),
+            ))),
+            Some(StorageBacking::NarrowI32(width)) => Ok(TensorData::Numeric(Cow::Owned(
+                narrow_int32_data(&t.int32_data, width),
+            ))),
+            Some(StorageBacking::NarrowU64) => Ok(TensorData::Numeric(Cow::Owned(
+                narrow_uint64_data(&t.uint64_data),
+            ))),
             Some(StorageBacking::Strings) => {
                 if t.string_data.is_empty() && self.shape.iter().any(|&d| d != 0) {
                     return Err(Error::MissingField("tensor data".to_string()));
@@ -233,7 +311,8 @@ impl OnnxTensor {
     /// Consume tensor and return owned data
     ///
     /// All variants returned with no borrowed references.
-    /// Numeric performs zero-copy reinterpretation from typed fields.
+    /// Numeric performs zero-copy reinterpretation from typed fields, except for
+    /// narrower element types which are packed as in [`data`](Self::data).
     /// For external data, this lazily loads the data from the external file.
     #[inline]
     pub fn into_data(mut self) -> Result<TensorData<'static>, Error> {
@@ -256,21 +335,27 @@ impl OnnxTensor {
         }
 
         match storage_backing(self.data_type) {
-            Some(StorageBacking::F32) => Ok(TensorData::Numeric(Cow::Owned(into_vec_u8::<f32>(
-                mem::take(&mut t.float_data),
-            )))),
-            Some(StorageBacking::F64) => Ok(TensorData::Numeric(Cow::Owned(into_vec_u8::<f64>(
-                mem::take(&mut t.double_data),
-            )))),
-            Some(StorageBacking::I64) => Ok(TensorData::Numeric(Cow::Owned(into_vec_u8::<i64>(
-                mem::take(&mut t.int64_data),
-            )))),
-            Some(StorageBacking::U64) => Ok(TensorData::Numeric(Cow::Owned(into_vec_u8::<u64>(
-                mem::take(&mut t.uint64_data),
-            )))),
-            Some(StorageBacking::I32) => Ok(TensorData::Numeric(Cow::Owned(into_vec_u8::<i32>(
-                mem::take(&mut t.int32_data),
-            )))),
+            Some(StorageBacking::F32) => Ok(TensorData::Numeric(Cow::Owned(
+                into_le_vec_u8::<f32>(mem::take(&mut t.float_data)),
+            ))),
+            Some(StorageBacking::F64) => Ok(TensorData::Numeric(Cow::Owned(
+                into_le_vec_u8::<f64>(mem::take(&mut t.double_data)),
+            ))),
+            Some(StorageBacking::I64) => Ok(TensorData::Numeric(Cow::Owned(
+                into_le_vec_u8::<i64>(mem::take(&mut t.int64_data)),
+            ))),
+            Some(StorageBacking::U64) => Ok(TensorData::Numeric(Cow::Owned(
+                into_le_vec_u8::<u64>(mem::take(&mut t.uint64_data)),
+            ))),
+            Some(StorageBacking::I32) => Ok(TensorData::Numeric(Cow::Owned(
+                into_le_vec_u8::<i32>(mem::take(&mut t.int32_data)),
+            ))),
+            Some(StorageBacking::NarrowI32(width)) => Ok(TensorData::Numeric(Cow::Owned(
+                narrow_int32_data(&t.int32_data, width),
+            ))),
+            Some(StorageBacking::NarrowU64) => Ok(TensorData::Numeric(Cow::Owned(
+                narrow_uint64_data(&t.uint64_data),
+            ))),
             Some(StorageBacking::Strings) => {
                 if t.string_data.is_empty() && self.shape.iter().any(|&d| d != 0) {
                     return Err(Error::MissingField("tensor data".to_string()));
@@ -283,18 +368,14 @@ impl OnnxTensor {
 
     /// Reinterpret tensor bytes as typed slice
     ///
-    /// Assumes little-endian platform and standard IEEE 754 for floats.
+    /// Does not check the tensor's data type, see [`to_vec`](Self::to_vec)
+    /// for a checked conversion. Tensor bytes are little-endian as the ONNX
+    /// spec requires and are converted to host byte order.
     /// String tensors are concatenated before conversion.
-    /// Returns error if data size is not aligned to type size.
-    pub fn copy_data_as<T: Copy>(&self) -> Result<Box<[T]>, Error> {
+    /// Returns error if data size is not aligned to type size or a byte
+    /// pattern is not a valid `T` (e.g. a bool other than 0 or 1).
+    pub fn copy_data_as<T: OnnxElement>(&self) -> Result<Box<[T]>, Error> {
         let data_ref = self.data()?;
-        let type_size = mem::size_of::<T>();
-
-        if type_size == 0 {
-            return Err(Error::DataConversion(
-                "Zero-sized types not supported".to_string(),
-            ));
-        }
 
         let bytes_vec;
         let bytes: &[u8] = match &data_ref {
@@ -311,31 +392,201 @@ impl OnnxTensor {
             }
         };
 
-        if bytes.is_empty() {
-            return Ok(Box::from([]));
+        Ok(decode_elements::<T>(bytes, &self.name)?.into_boxed_slice())
+    }
+
+    /// Copy elements out as `T`, checking that `T` matches the data type
+    ///
+    /// Returns [`Error::DataConversion`] if the tensor's data type is not
+    /// `T::DATA_TYPE`. Works for both raw_data and typed-field storage.
+    pub fn to_vec<T: OnnxElement>(&self) -> Result<Vec<T>, Error> {
+        self.expect_element::<T>()?;
+        let data = self.data()?;
+        decode_elements::<T>(&data.as_slice(), &self.name)
+    }
+
+    /// Borrow elements as `&[T]` without copying
+    ///
+    /// Only possible when the stored bytes already have `T`'s layout: raw_data
+    /// that is suitably aligned (and little-endian hosts for multi-byte types),
+    /// or a typed field of the same width as `T`. Returns
+    /// [`Error::DataConversion`] otherwise, in which case use
+    /// [`to_vec`](Self::to_vec). External data is never borrowed.
+    pub fn as_slice<T: OnnxElement>(&self) -> Result<&[T], Error> {
+        self.expect_element::<T>()?;
+        let not_borrowable = |reason: &str| {
+            Error::DataConversion(format!(
+                "Cannot borrow tensor '{}' as {}: {}",
+                self.name,
+                any::type_name::<T>(),
+                reason
+            ))
+        };
+
+        if let Some(TensorDataLocation::External(_)) = self.data_location {
+            return Err(not_borrowable("data is stored externally"));
         }
+        let t = self
+            .proto
+            .as_ref()
+            .ok_or_else(|| Error::MissingField("tensor data".to_string()))?;
 
-        if !bytes.len().is_multiple_of(type_size) {
+        let bytes: &[u8] = match &t.raw_data {
+            Some(raw) if !raw.is_empty() => {
+                if ByteOrder::NATIVE == ByteOrder::Big && mem::size_of::<T>() > 1 {
+                    return Err(not_borrowable("raw data is little-endian"));
+                }
+                raw
+            }
+            _ => match storage_backing(self.data_type) {
+                Some(StorageBacking::F32) => slice_bytes_as(&t.float_data),
+                Some(StorageBacking::F64) => slice_bytes_as(&t.double_data),
+                Some(StorageBacking::I64) => slice_bytes_as(&t.int64_data),
+                Some(StorageBacking::U64) => slice_bytes_as(&t.uint64_data),
+                Some(StorageBacking::I32) => slice_bytes_as(&t.int32_data),
+                _ => return Err(not_borrowable("typed field is wider than the element")),
+            },
+        };
+
+        if !bytes.len().is_multiple_of(mem::size_of::<T>()) {
+            return Err(not_borrowable(
+                "data size is not a multiple of the element size",
+            ));
+        }
+        if !bytes.as_ptr().cast::<T>().is_aligned() {
+            return Err(not_borrowable("data is not aligned"));
+        }
+        if !T::ANY_BIT_PATTERN
+            && bytes
+                .chunks_exact(mem::size_of::<T>())
+                .any(|b| T::from_le_bytes(b).is_none())
+        {
+            return Err(not_borrowable("data contains invalid values"));
+        }
+
+        // SAFETY: length and alignment were checked above, and every element
+        // is a valid bit pattern for T
+        Ok(unsafe {
+            slice::from_raw_parts(
+                bytes.as_ptr().cast::<T>(),
+                bytes.len() / mem::size_of::<T>(),
+            )
+        })
+    }
+
+    fn expect_element<T: OnnxElement>(&self) -> Result<(), Error> {
+        if self.data_type == T::DATA_TYPE {
+            return Ok(());
+        }
+        Err(Error::DataConversion(format!(
+            "Tensor '{}' has type {:?}, which is not {} ({:?})",
+            self.name,
+            self.data_type,
+            any::type_name::<T>(),
+            T::DATA_TYPE
+        )))
+    }
+
+    /// Decode floating point elements into f32 values
+    ///
+    /// Supports every floating [`DataType`], including float16, bfloat16 and
+    /// the FP8/FP4 formats. Double values are rounded to the nearest f32.
+    pub fn to_f32_vec(&self) -> Result<Vec<f32>, Error> {
+        Ok(self.to_f64_vec()?.into_iter().map(|v| v as f32).collect())
+    }
+
+    /// Decode floating point elements into f64 values
+    ///
+    /// Formats without infinities map their NaN encodings to NaN, and the
+    /// `fnuz` variants decode 0x80 as NaN rather than negative zero.
+    pub fn to_f64_vec(&self) -> Result<Vec<f64>, Error> {
+        let count = self.element_count()?;
+        let data = self.data()?;
+        if matches!(data, TensorData::Strings(_)) {
             return Err(Error::DataConversion(format!(
-                "Data size {} not aligned to type size {} for {}",
-                bytes.len(),
-                type_size,
-                any::type_name::<T>()
+                "Cannot decode string tensor '{}' as floating point",
+                self.name
             )));
         }
+        convert::decode_floats(self.data_type, &data.as_slice(), count)
+    }
 
-        let count = bytes.len() / type_size;
-        let mut v: Vec<MaybeUninit<T>> = Vec::with_capacity(count);
-
-        unsafe {
-            v.set_len(count);
-            ptr::copy_nonoverlapping(bytes.as_ptr(), v.as_mut_ptr() as *mut u8, bytes.len());
+    /// Iterate over the bit codes of a sub-byte packed tensor
+    ///
+    /// Works for Int4, Uint4, Int2, Uint2 and Float4e2m1 tensors, yielding
+    /// one code per element in ONNX packing order (least significant bits
+    /// first). Padding bits in the last byte are skipped.
+    pub fn packed_elements(&self) -> Result<PackedElements<'_>, Error> {
+        let bits = self
+            .data_type
+            .bits_per_element()
+            .filter(|_| self.data_type.is_sub_byte())
+            .ok_or_else(|| {
+                Error::DataConversion(format!(
+                    "Tensor '{}' of type {:?} is not sub-byte packed",
+                    self.name, self.data_type
+                ))
+            })?;
+        let count = self.element_count()?;
+        let data = self.data()?;
+        let expected = (count * bits).div_ceil(8);
+        if matches!(data, TensorData::Strings(_)) || data.len() != expected {
+            return Err(Error::DataConversion(format!(
+                "Tensor '{}' has {} data bytes, expected {} for {} {:?} elements",
+                self.name,
+                data.len(),
+                expected,
+                count,
+                self.data_type
+            )));
         }
+        Ok(PackedElements::new(data, bits, count))
+    }
+
+    /// Unpack Int4 or Int2 elements into sign-extended i8 values
+    pub fn unpack_i8(&self) -> Result<impl Iterator<Item = i8> + '_, Error> {
+        self.expect_type(&[DataType::Int4, DataType::Int2])?;
+        let bits = self.data_type.bits_per_element().unwrap_or(8);
+        Ok(self
+            .packed_elements()?
+            .map(move |code| packed::sign_extend(code, bits)))
+    }
+
+    /// Unpack Uint4 or Uint2 elements into u8 values
+    pub fn unpack_u8(&self) -> Result<impl Iterator<Item = u8> + '_, Error> {
+        self.expect_type(&[DataType::Uint4, DataType::Uint2])?;
+        self.packed_elements()
+    }
+
+    /// Unpack Float4e2m1 elements into f32 values
+    pub fn unpack_f32(&self) -> Result<impl Iterator<Item = f32> + '_, Error> {
+        self.expect_type(&[DataType::Float4e2m1])?;
+        Ok(self
+            .packed_elements()?
+            .map(|code| convert::f4e2m1_to_f64(code) as f32))
+    }
 
-        let out_uninit = v.into_boxed_slice();
-        let out: Box<[T]> = unsafe { mem::transmute(out_uninit) };
+    fn expect_type(&self, allowed: &[DataType]) -> Result<(), Error> {
+        if allowed.contains(&self.data_type) {
+            return Ok(());
+        }
+        Err(Error::DataConversion(format!(
+            "Tensor '{}' has type {:?}, expected one of {:?}",
+            self.name, self.data_type, allowed
+        )))
+    }
 
-        Ok(out)
+    /// Number of elements implied by the fixed shape
+    fn element_count(&self) -> Result<usize, Error> {
+        self.shape
+            .iter()
+            .try_fold(1usize, |acc, &d| acc.checked_mul(usize::try_from(d).ok()?))
+            .ok_or_else(|| {
+                Error::DataConversion(format!(
+                    "Tensor '{}' has no fixed element count for shape {:?}",
+                    self.name, self.shape
+                ))
+            })
     }
 }
 
@@ -345,6 +596,10 @@ enum StorageBacking {
     I64,
     U64,
     I32,
+    /// int32_data values each holding `width` little-endian bytes of packed data
+    NarrowI32(usize),
+    /// uint64_data values each holding one uint32 element
+    NarrowU64,
     Strings,
 }
 
@@ -353,30 +608,123 @@ fn storage_backing(dt: DataType) -> Option<StorageBacking> {
         DataType::Float | DataType::Complex64 => Some(StorageBacking::F32),
         DataType::Double | DataType::Complex128 => Some(StorageBacking::F64),
         DataType::Int64 => Some(StorageBacking::I64),
-        DataType::Uint32 | DataType::Uint64 => Some(StorageBacking::U64),
-        DataType::Int32
-        | DataType::Int16
-        | DataType::Int8
+        DataType::Uint64 => Some(StorageBacking::U64),
+        DataType::Uint32 => Some(StorageBacking::NarrowU64),
+        DataType::Int32 => Some(StorageBacking::I32),
+        DataType::Int16 | DataType::Uint16 | DataType::Float16 | DataType::Bfloat16 => {
+            Some(StorageBacking::NarrowI32(2))
+        }
+        // 4-bit and 2-bit types store one byte of packed elements per value
+        DataType::Int8
         | DataType::Int4
         | DataType::Int2
-        | DataType::Uint16
         | DataType::Uint8
         | DataType::Uint4
         | DataType::Uint2
         | DataType::Bool
-        | DataType::Float16
-        | DataType::Bfloat16
         | DataType::Float8e4m3fn
         | DataType::Float8e4m3fnuz
         | DataType::Float8e5m2
         | DataType::Float8e5m2fnuz
         | DataType::Float8e8m0
-        | DataType::Float4e2m1 => Some(StorageBacking::I32),
+        | DataType::Float4e2m1 => Some(StorageBacking::NarrowI32(1)),
         DataType::String => Some(StorageBacking::Strings),
         DataType::Undefined => None,
     }
 }
 
+/// Keep the low `width` bytes of each int32_data value
+fn narrow_int32_data(values: &[i32], width: usize) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|v| v.to_le_bytes().into_iter().take(width))
+        .collect()
+}
+
+/// Truncate uint64_data values holding uint32 elements
+fn narrow_uint64_data(values: &[u64]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|&v| (v as u32).to_le_bytes())
+        .collect()
+}
+
+/// Decode little-endian bytes into elements of type `T`
+fn decode_elements<T: OnnxElement>(bytes: &[u8], name: &str) -> Result<Vec<T>, Error> {
+    let type_size = mem::size_of::<T>();
+    if !bytes.len().is_multiple_of(type_size) {
+        return Err(Error::DataConversion(format!(
+            "Data size {} not aligned to type size {} for {}",
+            bytes.len(),
+            type_size,
+            any::type_name::<T>()
+        )));
+    }
+
+    bytes
+        .chunks_exact(type_size)
+        .map(|b| {
+            T::from_le_bytes(b).ok_or_else(|| {
+                Error::DataConversion(format!(
+                    "Tensor '{}' contains a value that is not a valid {}",
+                    name,
+                    any::type_name::<T>()
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Byte order of values in host memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ByteOrder {
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    const NATIVE: ByteOrder = if cfg!(target_endian = "big") {
+        ByteOrder::Big
+    } else {
+        ByteOrder::Little
+    };
+}
+
+/// View typed-field values as little-endian bytes
+///
+/// Borrows on little-endian hosts, byte-swaps into a new buffer otherwise.
+fn le_bytes_of<T: Copy>(values: &[T]) -> Cow<'_, [u8]> {
+    to_little_endian(
+        Cow::Borrowed(slice_bytes_as(values)),
+        mem::size_of::<T>(),
+        ByteOrder::NATIVE,
+    )
+}
+
+/// Convert owned typed-field values into little-endian bytes
+fn into_le_vec_u8<T: Copy>(values: Vec<T>) -> Vec<u8> {
+    to_little_endian(
+        Cow::Owned(into_vec_u8(values)),
+        mem::size_of::<T>(),
+        ByteOrder::NATIVE,
+    )
+    .into_owned()
+}
+
+/// Reorder `size`-byte elements stored in `order` into little-endian
+///
+/// Little-endian input is returned as is, borrowed or not.
+fn to_little_endian(bytes: Cow<'_, [u8]>, size: usize, order: ByteOrder) -> Cow<'_, [u8]> {
+    if order == ByteOrder::Little || size <= 1 {
+        return bytes;
+    }
+    let mut swapped = bytes.into_owned();
+    for element in swapped.chunks_exact_mut(size) {
+        element.reverse();
+    }
+    Cow::Owned(swapped)
+}
+
 fn slice_bytes_as<T: Copy>(slice: &[T]) -> &[u8] {
     assert!(mem::size_of::<T>() > 0, "zero-sized types not supported");
     unsafe { slice::from_raw_parts(slice.as_ptr() as *const u8, mem::size_of_val(slice)) }
@@ -393,3 +741,36 @@ fn into_vec_u8<T: Copy>(v: Vec<T>) -> Vec<u8> {
     let cap = v.capacity().checked_mul(t_size).expect("capacity overflow");
     unsafe { Vec::from_raw_parts(ptr, len, cap) }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_little_endian() {
+        let values = [0x0102u16, 0xA0B0];
+        let be: Vec<u8> = values.iter().flat_map(|v| v.to_be_bytes()).collect();
+        let le: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        assert_eq!(*to_little_endian(Cow::Borrowed(&be), 2, ByteOrder::Big), le);
+
+        let values = [0x01020304u32, 0xDEADBEEF];
+        let be: Vec<u8> = values.iter().flat_map(|v| v.to_be_bytes()).collect();
+        let le: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        assert_eq!(*to_little_endian(Cow::Borrowed(&be), 4, ByteOrder::Big), le);
+
+        let values = [0x0102030405060708u64, u64::MAX - 1];
+        let be: Vec<u8> = values.iter().flat_map(|v| v.to_be_bytes()).collect();
+        let le: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        assert_eq!(*to_little_endian(Cow::Borrowed(&be), 8, ByteOrder::Big), le);
+
+        // little-endian input and single bytes are borrowed unchanged
+        assert!(matches!(
+            to_little_endian(Cow::Borrowed(&le), 8, ByteOrder::Little),
+            Cow::Borrowed(bytes) if bytes == le.as_slice()
+        ));
+        assert!(matches!(
+            to_little_endian(Cow::Borrowed(&be), 1, ByteOrder::Big),
+            Cow::Borrowed(bytes) if bytes == be.as_slice()
+        ));
+    }
+}
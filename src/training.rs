@@ -0,0 +1,41 @@
+//! Training information carried by on-device training models.
+
+use std::collections::HashMap;
+
+use crate::OnnxGraph;
+
+/// One `TrainingInfoProto` entry: an initialization step and an algorithm step
+///
+/// Binding maps go from the name of an initializer to the graph output whose
+/// value is assigned to it. Targets live in the main graph or in this entry's
+/// `algorithm` graph.
+#[derive(Debug, Clone, Default)]
+pub struct OnnxTrainingInfo {
+    /// Graph computing initial values, run once before training starts
+    pub initialization: Option<OnnxGraph>,
+    /// Graph for one training step, run on top of the main graph
+    pub algorithm: Option<OnnxGraph>,
+    /// Initializer name to `initialization` output
+    pub initialization_binding: HashMap<String, String>,
+    /// Initializer name to `algorithm` output, applied after each step
+    pub update_binding: HashMap<String, String>,
+}
+
+impl OnnxTrainingInfo {
+    /// Check if an initializer is defined by this entry's algorithm graph
+    pub fn is_algorithm_initializer(&self, name: &str) -> bool {
+        self.algorithm.as_ref().is_some_and(|g| {
+            g.tensors
+                .get(name)
+                .is_some_and(|t| t.data_location().is_some())
+                || g.sparse_tensors.contains_key(name)
+        })
+    }
+
+    /// Names of the initializers updated by each training step, sorted
+    pub fn updated_tensors(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.update_binding.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
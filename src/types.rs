@@ -1,5 +1,8 @@
+use crate::graph::OnnxGraph;
+use crate::sparse::OnnxSparseTensor;
 use crate::tensor::OnnxTensor;
 
+pub use crate::attribute_proto::AttributeType;
 pub use crate::tensor_proto::DataType;
 
 /// ONNX tensor data types
@@ -10,12 +13,24 @@ impl DataType {
     }
 
     /// Get the size in bytes for numeric types
+    ///
+    /// Returns None for strings and for sub-byte types, which are packed
+    /// several elements per byte. Use
+    /// [`storage_bytes_for`](Self::storage_bytes_for) for those.
     pub fn size_in_bytes(&self) -> Option<usize> {
+        match self.bits_per_element()? {
+            bits if bits >= 8 => Some(bits / 8),
+            _ => None,
+        }
+    }
+
+    /// Get the number of bits used by one element
+    pub fn bits_per_element(&self) -> Option<usize> {
         match self {
-            DataType::Complex128 => Some(16),
-            DataType::Double | DataType::Int64 | DataType::Uint64 | DataType::Complex64 => Some(8),
-            DataType::Float | DataType::Int32 | DataType::Uint32 => Some(4),
-            DataType::Float16 | DataType::Bfloat16 | DataType::Int16 | DataType::Uint16 => Some(2),
+            DataType::Complex128 => Some(128),
+            DataType::Double | DataType::Int64 | DataType::Uint64 | DataType::Complex64 => Some(64),
+            DataType::Float | DataType::Int32 | DataType::Uint32 => Some(32),
+            DataType::Float16 | DataType::Bfloat16 | DataType::Int16 | DataType::Uint16 => Some(16),
             DataType::Int8
             | DataType::Uint8
             | DataType::Bool
@@ -23,16 +38,31 @@ impl DataType {
             | DataType::Float8e4m3fnuz
             | DataType::Float8e5m2
             | DataType::Float8e5m2fnuz
-            | DataType::Float8e8m0
-            | DataType::Uint4
-            | DataType::Int4
-            | DataType::Float4e2m1
-            | DataType::Uint2
-            | DataType::Int2 => Some(1),
+            | DataType::Float8e8m0 => Some(8),
+            DataType::Uint4 | DataType::Int4 | DataType::Float4e2m1 => Some(4),
+            DataType::Uint2 | DataType::Int2 => Some(2),
             DataType::String | DataType::Undefined => None,
         }
     }
 
+    /// Check if elements are packed several to a byte
+    pub fn is_sub_byte(&self) -> bool {
+        self.bits_per_element().is_some_and(|bits| bits < 8)
+    }
+
+    /// Get the packed storage size in bytes for a tensor of the given shape
+    ///
+    /// Sub-byte elements are packed densely and the last byte is padded.
+    /// Returns None for non-numeric types, negative (unknown) dimensions or
+    /// on overflow.
+    pub fn storage_bytes_for(&self, shape: &[i64]) -> Option<usize> {
+        let bits = self.bits_per_element()?;
+        let count = shape
+            .iter()
+            .try_fold(1usize, |acc, &d| acc.checked_mul(usize::try_from(d).ok()?))?;
+        Some(count.checked_mul(bits)?.div_ceil(8))
+    }
+
     /// Check if this is a floating point type
     pub fn is_float(&self) -> bool {
         matches!(
@@ -70,16 +100,121 @@ impl DataType {
     }
 }
 
+/// Operator set imported by a model
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OpsetImport {
+    /// Operator set domain, empty for the default `ai.onnx` domain
+    pub domain: String,
+    /// Operator set version
+    pub version: i64,
+}
+
+/// A single tensor dimension
+///
+/// Symbolic dimensions keep their `dim_param` name so tensors sharing a
+/// dimension (e.g. `batch_size`) can be bound together at runtime.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Dim {
+    /// Known dimension size
+    Fixed(i64),
+    /// Named dimension whose size is bound at runtime
+    Symbolic(String),
+    /// Anonymous dimension with no size or name
+    Unknown,
+}
+
+impl Dim {
+    /// Get the size for fixed dimensions
+    pub fn value(&self) -> Option<i64> {
+        match self {
+            Dim::Fixed(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Get the name for symbolic dimensions
+    pub fn symbol(&self) -> Option<&str> {
+        match self {
+            Dim::Symbolic(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Size used in the flat `i64` shape, `-1` when not fixed
+    pub fn as_i64(&self) -> i64 {
+        self.value().unwrap_or(-1)
+    }
+}
+
+/// Type of an ONNX value, mirroring `TypeProto`
+///
+/// Tensor shapes are `None` when the rank is unknown.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ValueType {
+    Tensor {
+        data_type: DataType,
+        shape: Option<Vec<Dim>>,
+    },
+    SparseTensor {
+        data_type: DataType,
+        shape: Option<Vec<Dim>>,
+    },
+    Sequence(Box<ValueType>),
+    Map {
+        key_type: DataType,
+        value_type: Box<ValueType>,
+    },
+    Optional(Box<ValueType>),
+}
+
+impl ValueType {
+    /// Check if this is a dense tensor type
+    pub fn is_tensor(&self) -> bool {
+        matches!(self, ValueType::Tensor { .. })
+    }
+
+    /// Element type for tensor and sparse tensor types
+    pub fn data_type(&self) -> Option<DataType> {
+        match self {
+            ValueType::Tensor { data_type, .. } | ValueType::SparseTensor { data_type, .. } => {
+                Some(*data_type)
+            }
+            _ => None,
+        }
+    }
+
+    /// Element type for sequence and optional types
+    pub fn elem_type(&self) -> Option<&ValueType> {
+        match self {
+            ValueType::Sequence(elem) | ValueType::Optional(elem) => Some(elem),
+            _ => None,
+        }
+    }
+}
+
 /// ONNX attribute values
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum AttributeValue {
     Int(i64),
     Float(f32),
     String(String),
     Tensor(Box<OnnxTensor>),
+    Graph(Box<OnnxGraph>),
+    SparseTensor(Box<OnnxSparseTensor>),
+    Type(ValueType),
     Ints(Vec<i64>),
     Floats(Vec<f32>),
     Strings(Vec<String>),
+    Tensors(Vec<OnnxTensor>),
+    Graphs(Vec<OnnxGraph>),
+    SparseTensors(Vec<OnnxSparseTensor>),
+    Types(Vec<ValueType>),
+    /// Reference to an attribute of the calling node, only valid inside
+    /// function bodies
+    Ref {
+        name: String,
+        attr_type: AttributeType,
+    },
 }
 
 impl AttributeValue {
@@ -115,6 +250,30 @@ impl AttributeValue {
         }
     }
 
+    /// Try to get subgraph value
+    pub fn as_graph(&self) -> Option<&OnnxGraph> {
+        match self {
+            AttributeValue::Graph(g) => Some(g.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// Try to get sparse tensor value
+    pub fn as_sparse_tensor(&self) -> Option<&OnnxSparseTensor> {
+        match self {
+            AttributeValue::SparseTensor(t) => Some(t.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// Try to get type value
+    pub fn as_type(&self) -> Option<&ValueType> {
+        match self {
+            AttributeValue::Type(t) => Some(t),
+            _ => None,
+        }
+    }
+
     /// Try to get integer array value
     pub fn as_ints(&self) -> Option<&[i64]> {
         match self {
@@ -138,4 +297,44 @@ impl AttributeValue {
             _ => None,
         }
     }
+
+    /// Try to get tensor array value
+    pub fn as_tensors(&self) -> Option<&[OnnxTensor]> {
+        match self {
+            AttributeValue::Tensors(tensors) => Some(tensors),
+            _ => None,
+        }
+    }
+
+    /// Try to get subgraph array value
+    pub fn as_graphs(&self) -> Option<&[OnnxGraph]> {
+        match self {
+            AttributeValue::Graphs(graphs) => Some(graphs),
+            _ => None,
+        }
+    }
+
+    /// Try to get sparse tensor array value
+    pub fn as_sparse_tensors(&self) -> Option<&[OnnxSparseTensor]> {
+        match self {
+            AttributeValue::SparseTensors(tensors) => Some(tensors),
+            _ => None,
+        }
+    }
+
+    /// Try to get type array value
+    pub fn as_types(&self) -> Option<&[ValueType]> {
+        match self {
+            AttributeValue::Types(types) => Some(types),
+            _ => None,
+        }
+    }
+
+    /// Try to get the name of the referenced caller attribute
+    pub fn as_ref_name(&self) -> Option<&str> {
+        match self {
+            AttributeValue::Ref { name, .. } => Some(name),
+            _ => None,
+        }
+    }
 }
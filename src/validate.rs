@@ -0,0 +1,538 @@
+//! Structural model checks, similar in spirit to `onnx.checker`.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::graph::GraphParts;
+use crate::operation::is_default_domain;
+use crate::quantization::SCALE_TENSOR;
+use crate::tensor::TensorDataLocation;
+use crate::{DataType, OnnxGraph, OnnxModel, OnnxTensor, OnnxTrainingInfo, TensorData};
+
+/// How serious a validation finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Suspicious but loadable by most runtimes
+    Warning,
+    /// Violates the ONNX specification
+    Error,
+}
+
+/// Where in the model a validation finding applies
+///
+/// `graph` is a slash-separated path: the main graph name, followed by the
+/// node name and attribute name for each enclosing subgraph. Training graphs
+/// start with `training_info[i]/initialization` or `training_info[i]/algorithm`,
+/// their bindings are reported as [`TrainingBinding`](Self::TrainingBinding).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Location {
+    /// The model header, e.g. IR version or opset imports
+    Model,
+    /// A graph as a whole, e.g. its inputs or outputs
+    Graph { graph: String },
+    /// A node, by position in its graph's operation list
+    Node {
+        graph: String,
+        index: usize,
+        name: String,
+    },
+    /// An initializer or sparse initializer
+    Tensor { graph: String, name: String },
+    /// One entry of a `training_info` binding, keyed by its target initializer
+    TrainingBinding {
+        index: usize,
+        field: String,
+        target: String,
+    },
+}
+
+/// A single validation finding
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub location: Location,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Check if this finding is an error rather than a warning
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        match &self.location {
+            Location::Model => write!(f, "{}: model: {}", severity, self.message),
+            Location::Graph { graph } => {
+                write!(f, "{}: graph '{}': {}", severity, graph, self.message)
+            }
+            Location::Node { graph, index, name } => write!(
+                f,
+                "{}: node {} '{}' in graph '{}': {}",
+                severity, index, name, graph, self.message
+            ),
+            Location::Tensor { graph, name } => write!(
+                f,
+                "{}: tensor '{}' in graph '{}': {}",
+                severity, name, graph, self.message
+            ),
+            Location::TrainingBinding {
+                index,
+                field,
+                target,
+            } => write!(
+                f,
+                "{}: training_info[{}] {} '{}': {}",
+                severity, index, field, target, self.message
+            ),
+        }
+    }
+}
+
+/// Run all checks over a model and its subgraphs
+pub(crate) fn validate_model(model: &OnnxModel) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        diagnostics: Vec::new(),
+        domains: model
+            .opset_import
+            .iter()
+            .map(|o| o.domain.as_str())
+            .collect(),
+    };
+
+    checker.check_header(model);
+    checker.check_graph(
+        &model.graph_parts(),
+        model.graph_name.clone(),
+        &HashSet::new(),
+    );
+    checker.check_training(model);
+    checker.diagnostics
+}
+
+struct Checker<'m> {
+    diagnostics: Vec<Diagnostic>,
+    domains: HashSet<&'m str>,
+}
+
+impl<'m> Checker<'m> {
+    fn report(&mut self, severity: Severity, location: Location, message: String) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            location,
+            message,
+        });
+    }
+
+    fn check_header(&mut self, model: &OnnxModel) {
+        if model.ir_version <= 0 {
+            self.report(
+                Severity::Error,
+                Location::Model,
+                "ir_version is missing".to_string(),
+            );
+        }
+        if model.opset_import.is_empty() {
+            self.report(
+                Severity::Error,
+                Location::Model,
+                "model does not import any opset".to_string(),
+            );
+        }
+
+        let mut seen = HashSet::new();
+        for opset in &model.opset_import {
+            let domain = if is_default_domain(&opset.domain) {
+                ""
+            } else {
+                opset.domain.as_str()
+            };
+            if !seen.insert(domain) {
+                self.report(
+                    Severity::Error,
+                    Location::Model,
+                    format!("opset domain '{}' is imported more than once", opset.domain),
+                );
+            }
+        }
+    }
+
+    /// Check one graph, with `outer` holding the names visible from enclosing graphs
+    fn check_graph(&mut self, graph: &GraphParts<'m>, path: String, outer: &HashSet<&str>) {
+        let graph_loc = || Location::Graph {
+            graph: path.clone(),
+        };
+
+        if graph.name.is_empty() {
+            self.report(
+                Severity::Error,
+                graph_loc(),
+                "graph has no name".to_string(),
+            );
+        }
+
+        // names defined so far, in order of definition
+        let mut defined: HashSet<&str> = HashSet::new();
+        for name in graph.inputs {
+            if name.is_empty() {
+                self.report(
+                    Severity::Error,
+                    graph_loc(),
+                    "graph input has no name".into(),
+                );
+            } else if !defined.insert(name) {
+                self.report(
+                    Severity::Error,
+                    graph_loc(),
+                    format!("graph input '{}' is declared more than once", name),
+                );
+            }
+        }
+
+        let mut initializers: Vec<(&String, &OnnxTensor)> = graph
+            .tensors
+            .iter()
+            .filter(|(_, t)| t.data_location().is_some())
+            .collect();
+        initializers.sort_by_key(|(name, _)| *name);
+        for (name, tensor) in initializers {
+            let location = Location::Tensor {
+                graph: path.clone(),
+                name: name.clone(),
+            };
+            if let Some(message) = check_tensor_data(tensor) {
+                self.report(Severity::Error, location.clone(), message);
+            }
+            if graph.sparse_tensors.contains_key(name) {
+                self.report(
+                    Severity::Error,
+                    location,
+                    "name is used by both a dense and a sparse initializer".to_string(),
+                );
+            }
+            defined.insert(name);
+        }
+
+        let mut sparse: Vec<_> = graph.sparse_tensors.iter().collect();
+        sparse.sort_by_key(|(name, _)| *name);
+        for (name, tensor) in sparse {
+            let location = Location::Tensor {
+                graph: path.clone(),
+                name: name.clone(),
+            };
+            if tensor.indices().data_type() != DataType::Int64 {
+                self.report(
+                    Severity::Error,
+                    location.clone(),
+                    format!(
+                        "sparse indices must be Int64, found {:?}",
+                        tensor.indices().data_type()
+                    ),
+                );
+            }
+            if tensor.values().shape().len() != 1 {
+                self.report(
+                    Severity::Error,
+                    location,
+                    format!(
+                        "sparse values must be 1-D, found shape {:?}",
+                        tensor.values().shape()
+                    ),
+                );
+            }
+            defined.insert(name);
+        }
+
+        // every output produced by some node, to tell misordered from undefined
+        let produced: HashMap<&str, usize> = graph
+            .operations
+            .iter()
+            .enumerate()
+            .flat_map(|(i, op)| op.outputs.iter().map(move |o| (o.as_str(), i)))
+            .collect();
+
+        let mut node_names: HashSet<&str> = HashSet::new();
+        for (index, op) in graph.operations.iter().enumerate() {
+            let node_loc = || Location::Node {
+                graph: path.clone(),
+                index,
+                name: op.name.clone(),
+            };
+
+            if op.op_type.is_empty() {
+                self.report(Severity::Error, node_loc(), "node has no op_type".into());
+            }
+            if !op.name.is_empty() && !node_names.insert(&op.name) {
+                self.report(
+                    Severity::Warning,
+                    node_loc(),
+                    format!("node name '{}' is not unique", op.name),
+                );
+            }
+            let domain_imported = if is_default_domain(&op.domain) {
+                self.domains.iter().any(|d| is_default_domain(d))
+            } else {
+                self.domains.contains(op.domain.as_str())
+            };
+            if !domain_imported {
+                self.report(
+                    Severity::Error,
+                    node_loc(),
+                    format!("no opset is imported for domain '{}'", op.domain),
+                );
+            }
+
+            for input in op.inputs.iter().filter(|i| !i.is_empty()) {
+                if defined.contains(input.as_str()) || outer.contains(input.as_str()) {
+                    continue;
+                }
+                let message = match produced.get(input.as_str()) {
+                    Some(&producer) => format!(
+                        "input '{}' is produced by later node {}, nodes are not topologically sorted",
+                        input, producer
+                    ),
+                    None => format!("input '{}' is not defined", input),
+                };
+                self.report(Severity::Error, node_loc(), message);
+            }
+
+            if !op.subgraphs().is_empty() {
+                let mut visible = outer.clone();
+                visible.extend(defined.iter().copied());
+                for (attr, subgraph) in op.subgraphs() {
+                    let sub_path = format!("{}/{}/{}", path, op.name, attr);
+                    self.check_graph(&GraphParts::from(subgraph), sub_path, &visible);
+                }
+            }
+
+            for output in op.outputs.iter().filter(|o| !o.is_empty()) {
+                if !defined.insert(output) {
+                    self.report(
+                        Severity::Error,
+                        node_loc(),
+                        format!("output '{}' is already defined", output),
+                    );
+                }
+            }
+        }
+
+        let mut outputs: HashSet<&str> = HashSet::new();
+        for name in graph.outputs {
+            if name.is_empty() {
+                self.report(
+                    Severity::Error,
+                    graph_loc(),
+                    "graph output has no name".into(),
+                );
+                continue;
+            }
+            if !outputs.insert(name) {
+                self.report(
+                    Severity::Error,
+                    graph_loc(),
+                    format!("graph output '{}' is declared more than once", name),
+                );
+            }
+            if !defined.contains(name.as_str()) && !outer.contains(name.as_str()) {
+                self.report(
+                    Severity::Error,
+                    graph_loc(),
+                    format!("graph output '{}' is never produced", name),
+                );
+            }
+        }
+
+        let mut annotated: Vec<_> = graph.quantization_annotation.iter().collect();
+        annotated.sort_by_key(|(name, _)| *name);
+        for (name, params) in annotated {
+            let location = Location::Tensor {
+                graph: path.clone(),
+                name: name.clone(),
+            };
+            if !defined.contains(name.as_str()) && !outer.contains(name.as_str()) {
+                self.report(
+                    Severity::Warning,
+                    location.clone(),
+                    "quantization annotation refers to an undefined tensor".to_string(),
+                );
+            }
+            if !params.contains_key(SCALE_TENSOR) {
+                self.report(
+                    Severity::Error,
+                    location.clone(),
+                    format!("quantization annotation has no {}", SCALE_TENSOR),
+                );
+            }
+            let mut keys: Vec<_> = params.iter().collect();
+            keys.sort();
+            for (key, param) in keys {
+                let is_initializer = graph
+                    .tensors
+                    .get(param)
+                    .is_some_and(|t| t.data_location().is_some());
+                if !is_initializer {
+                    self.report(
+                        Severity::Error,
+                        location.clone(),
+                        format!(
+                            "quantization annotation {} '{}' is not an initializer",
+                            key, param
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Check training graphs and that bindings name initializers and outputs
+    fn check_training(&mut self, model: &'m OnnxModel) {
+        // the algorithm graph runs on top of the main graph and sees all of it
+        let mut main_names: HashSet<&str> = model.inputs.iter().map(String::as_str).collect();
+        main_names.extend(model.tensors.keys().map(String::as_str));
+        main_names.extend(model.sparse_tensors.keys().map(String::as_str));
+        for op in &model.operations {
+            main_names.extend(op.outputs.iter().map(String::as_str));
+        }
+
+        let mut updated: HashSet<&str> = HashSet::new();
+        for (i, info) in model.training_info.iter().enumerate() {
+            let path = format!("training_info[{}]", i);
+            if let Some(graph) = &info.initialization {
+                let sub_path = format!("{}/initialization", path);
+                self.check_graph(&GraphParts::from(graph), sub_path, &HashSet::new());
+            }
+            if let Some(graph) = &info.algorithm {
+                let sub_path = format!("{}/algorithm", path);
+                self.check_graph(&GraphParts::from(graph), sub_path, &main_names);
+            }
+
+            let bindings = [
+                (
+                    "initialization_binding",
+                    &info.initialization_binding,
+                    &info.initialization,
+                ),
+                ("update_binding", &info.update_binding, &info.algorithm),
+            ];
+            for (field, binding, graph) in bindings {
+                self.check_binding(model, info, i, field, binding, graph.as_ref());
+            }
+
+            let mut targets: Vec<&String> = info.update_binding.keys().collect();
+            targets.sort();
+            for target in targets {
+                if !updated.insert(target) {
+                    self.report(
+                        Severity::Error,
+                        binding_location(i, "update_binding", target),
+                        "target is already updated by an earlier entry".to_string(),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_binding(
+        &mut self,
+        model: &OnnxModel,
+        info: &OnnxTrainingInfo,
+        index: usize,
+        field: &str,
+        binding: &HashMap<String, String>,
+        graph: Option<&OnnxGraph>,
+    ) {
+        let mut entries: Vec<(&String, &String)> = binding.iter().collect();
+        entries.sort();
+        for (target, source) in entries {
+            let is_initializer = model
+                .tensors
+                .get(target)
+                .is_some_and(|t| t.data_location().is_some())
+                || model.sparse_tensors.contains_key(target)
+                || info.is_algorithm_initializer(target);
+            if !is_initializer {
+                self.report(
+                    Severity::Error,
+                    binding_location(index, field, target),
+                    "target is not an initializer".to_string(),
+                );
+            }
+            if !graph.is_some_and(|g| g.outputs.contains(source)) {
+                self.report(
+                    Severity::Error,
+                    binding_location(index, field, target),
+                    format!("source '{}' is not a graph output", source),
+                );
+            }
+        }
+    }
+}
+
+fn binding_location(index: usize, field: &str, target: &str) -> Location {
+    Location::TrainingBinding {
+        index,
+        field: field.to_string(),
+        target: target.to_string(),
+    }
+}
+
+/// Compare an initializer's stored data against its type and shape
+fn check_tensor_data(tensor: &OnnxTensor) -> Option<String> {
+    let data_type = tensor.data_type();
+    if data_type == DataType::Undefined {
+        return Some("data type is undefined".to_string());
+    }
+    if let Some(&dim) = tensor.shape().iter().find(|&&d| d < 0) {
+        return Some(format!(
+            "shape {:?} has negative dim {}",
+            tensor.shape(),
+            dim
+        ));
+    }
+
+    let element_count: Option<usize> = tensor
+        .shape()
+        .iter()
+        .try_fold(1usize, |acc, &d| acc.checked_mul(usize::try_from(d).ok()?));
+    let Some(element_count) = element_count else {
+        return Some(format!("shape {:?} overflows", tensor.shape()));
+    };
+
+    if data_type == DataType::String {
+        return match tensor.data() {
+            Ok(TensorData::Strings(parts)) if parts.len() != element_count => Some(format!(
+                "has {} strings, shape {:?} needs {}",
+                parts.len(),
+                tensor.shape(),
+                element_count
+            )),
+            Err(e) => Some(format!("data cannot be read: {}", e)),
+            _ => None,
+        };
+    }
+
+    let expected = data_type.storage_bytes_for(tensor.shape())?;
+    let actual = match tensor.data_location()? {
+        // avoid reading external files, the declared length is enough
+        TensorDataLocation::External(info) => info.length? as usize,
+        TensorDataLocation::Internal => match tensor.data() {
+            Ok(data) => data.len(),
+            Err(e) => return Some(format!("data cannot be read: {}", e)),
+        },
+    };
+    (actual != expected).then(|| {
+        format!(
+            "has {} data bytes, shape {:?} of {:?} needs {}",
+            actual,
+            tensor.shape(),
+            data_type,
+            expected
+        )
+    })
+}
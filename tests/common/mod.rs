@@ -0,0 +1,152 @@
+//! Helpers for building small ONNX models directly from protobuf messages.
+
+#![allow(dead_code)]
+
+#[allow(clippy::all)]
+#[allow(rustdoc::all)]
+pub mod onnx {
+    include!(concat!(env!("OUT_DIR"), "/onnx.rs"));
+}
+
+use onnx::{
+    AttributeProto, GraphProto, ModelProto, NodeProto, OperatorSetIdProto, StringStringEntryProto,
+    TensorProto, TensorShapeProto, TypeProto, ValueInfoProto,
+    tensor_shape_proto::{Dimension, dimension::Value},
+    type_proto,
+};
+use prost::Message;
+
+/// Shape entry for [`tensor_value_info`]: a fixed size or a symbolic name
+pub enum D {
+    V(i64),
+    P(&'static str),
+    Unknown,
+}
+
+/// Build a tensor ValueInfoProto with the given element type and dims
+pub fn tensor_value_info(name: &str, elem_type: i32, dims: &[D]) -> ValueInfoProto {
+    let dim = dims
+        .iter()
+        .map(|d| Dimension {
+            value: match d {
+                D::V(v) => Some(Value::DimValue(*v)),
+                D::P(p) => Some(Value::DimParam(p.to_string())),
+                D::Unknown => None,
+            },
+            ..Default::default()
+        })
+        .collect();
+    ValueInfoProto {
+        name: Some(name.to_string()),
+        r#type: Some(TypeProto {
+            value: Some(type_proto::Value::TensorType(type_proto::Tensor {
+                elem_type: Some(elem_type),
+                shape: Some(TensorShapeProto { dim }),
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Wrap a graph into an encoded opset 13 model
+pub fn model_bytes(graph: GraphProto) -> Vec<u8> {
+    ModelProto {
+        ir_version: Some(8),
+        opset_import: vec![OperatorSetIdProto {
+            domain: Some(String::new()),
+            version: Some(13),
+        }],
+        graph: Some(graph),
+        ..Default::default()
+    }
+    .encode_to_vec()
+}
+
+/// Build a default-domain node
+pub fn node(
+    op_type: &str,
+    inputs: &[&str],
+    outputs: &[&str],
+    attribute: Vec<AttributeProto>,
+) -> NodeProto {
+    NodeProto {
+        input: inputs.iter().map(|s| s.to_string()).collect(),
+        output: outputs.iter().map(|s| s.to_string()).collect(),
+        name: Some(format!("{}_{}", op_type, outputs.join("_"))),
+        op_type: Some(op_type.to_string()),
+        attribute,
+        ..Default::default()
+    }
+}
+
+/// Build a GRAPH attribute
+pub fn graph_attr(name: &str, graph: GraphProto) -> AttributeProto {
+    AttributeProto {
+        name: Some(name.to_string()),
+        r#type: Some(5),
+        g: Some(graph),
+        ..Default::default()
+    }
+}
+
+/// Build an INT attribute
+pub fn int_attr(name: &str, value: i64) -> AttributeProto {
+    AttributeProto {
+        name: Some(name.to_string()),
+        r#type: Some(2),
+        i: Some(value),
+        ..Default::default()
+    }
+}
+
+/// Build an INTS attribute
+pub fn ints_attr(name: &str, values: &[i64]) -> AttributeProto {
+    AttributeProto {
+        name: Some(name.to_string()),
+        r#type: Some(7),
+        ints: values.to_vec(),
+        ..Default::default()
+    }
+}
+
+/// Build a FLOAT tensor stored in raw_data
+pub fn float_tensor(name: &str, dims: &[i64], values: &[f32]) -> TensorProto {
+    TensorProto {
+        dims: dims.to_vec(),
+        data_type: Some(1),
+        name: Some(name.to_string()),
+        raw_data: Some(values.iter().flat_map(|v| v.to_le_bytes()).collect()),
+        ..Default::default()
+    }
+}
+
+/// Build a tensor of any data type from little-endian raw_data bytes
+pub fn raw_tensor(name: &str, data_type: i32, dims: &[i64], raw: &[u8]) -> TensorProto {
+    TensorProto {
+        dims: dims.to_vec(),
+        data_type: Some(data_type),
+        name: Some(name.to_string()),
+        raw_data: Some(raw.to_vec().into()),
+        ..Default::default()
+    }
+}
+
+/// Build an INT64 tensor stored in int64_data
+pub fn int64_tensor(name: &str, dims: &[i64], values: &[i64]) -> TensorProto {
+    TensorProto {
+        dims: dims.to_vec(),
+        data_type: Some(7),
+        name: Some(name.to_string()),
+        int64_data: values.to_vec(),
+        ..Default::default()
+    }
+}
+
+/// Build a key/value entry, as used by external_data and metadata_props
+pub fn entry(key: &str, value: &str) -> StringStringEntryProto {
+    StringStringEntryProto {
+        key: Some(key.to_string()),
+        value: Some(value.to_string()),
+    }
+}
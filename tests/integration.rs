@@ -1,4 +1,20 @@
-use onnx_extractor::OnnxModel;
+mod common;
+
+use common::onnx::{
+    AttributeProto, FunctionProto, GraphProto, ModelProto, NodeProto, OperatorSetIdProto,
+    SparseTensorProto, TensorAnnotation, TensorProto, TrainingInfoProto, TypeProto, ValueInfoProto,
+    type_proto,
+};
+use common::{
+    D, entry, float_tensor, graph_attr, int_attr, int64_tensor, ints_attr, model_bytes, node,
+    raw_tensor, tensor_value_info,
+};
+use onnx_extractor::{
+    AttributeType, AttributeValue, Bytes, CachePolicy, DataType, Dim, LoadOptions, Location,
+    OnnxModel, OnnxTensor, OpId, Severity, TensorData, ValueType,
+};
+use prost::Message;
+use std::collections::HashMap;
 
 const MODEL_PATH: &str = "tests/mnist-12.onnx";
 
@@ -131,3 +147,2494 @@ fn test_no_data_tensors_report_no_data() {
         "ReLU114_Output_0 should not have embedded data and data() must error"
     );
 }
+
+#[test]
+fn test_save_round_trip() {
+    let path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), MODEL_PATH);
+    let model = OnnxModel::load_from_file(&path).expect("Failed to load mnist model");
+
+    let bytes = model.to_bytes().expect("to_bytes should succeed");
+    let reloaded = OnnxModel::load_from_bytes(bytes).expect("saved model should load");
+
+    assert_eq!(reloaded.inputs, model.inputs);
+    assert_eq!(reloaded.outputs, model.outputs);
+    assert_eq!(reloaded.graph_name, model.graph_name);
+    assert_eq!(reloaded.operations.len(), model.operations.len());
+    assert_eq!(reloaded.tensors.len(), model.tensors.len());
+
+    for (original, saved) in model.operations.iter().zip(&reloaded.operations) {
+        assert_eq!(saved.name, original.name);
+        assert_eq!(saved.op_type, original.op_type);
+        assert_eq!(saved.inputs, original.inputs);
+        assert_eq!(saved.outputs, original.outputs);
+        assert_eq!(
+            saved.attribute_names().len(),
+            original.attribute_names().len()
+        );
+    }
+
+    for (name, tensor) in &model.tensors {
+        let saved = reloaded
+            .get_tensor(name)
+            .expect("every tensor should survive the round trip");
+        assert_eq!(saved.shape(), tensor.shape(), "shape mismatch for {name}");
+        assert_eq!(saved.data_type(), tensor.data_type());
+        if let Ok(data) = tensor.data() {
+            let saved_data = saved.data().expect("saved tensor should keep its data");
+            assert_eq!(saved_data.as_slice(), data.as_slice());
+        }
+    }
+}
+
+#[test]
+fn test_save_edited_model() {
+    let path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), MODEL_PATH);
+    let mut model = OnnxModel::load_from_file(&path).expect("Failed to load mnist model");
+
+    let weights: Vec<u8> = [1.0f32, 2.0, 3.0]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    let tensor = OnnxTensor::from_raw_data(
+        "extra_weight".to_string(),
+        vec![3],
+        DataType::Float,
+        Bytes::from(weights),
+    );
+    model.tensors.insert("extra_weight".to_string(), tensor);
+
+    let op_name = model.operations[0].name.clone();
+    model.operations[0]
+        .attributes
+        .insert("custom_flag".to_string(), AttributeValue::Int(7));
+
+    let file = std::env::temp_dir().join("onnx_extractor_save_edited.onnx");
+    let file = file.to_str().expect("temp path should be UTF-8");
+    model
+        .save_to_file(file)
+        .expect("save_to_file should succeed");
+    let reloaded = OnnxModel::load_from_file(file).expect("saved model should load");
+    std::fs::remove_file(file).ok();
+
+    let extra = reloaded
+        .get_tensor("extra_weight")
+        .expect("added initializer should be saved");
+    assert_eq!(&*extra.copy_data_as::<f32>().unwrap(), &[1.0, 2.0, 3.0]);
+
+    let op = reloaded.get_operation(&op_name).unwrap();
+    assert_eq!(op.get_int_attribute("custom_flag"), Some(7));
+}
+
+#[test]
+fn test_initializer_inputs() {
+    // IR version 3 models list every initializer as a graph input
+    let graph = GraphProto {
+        name: Some("ir3".to_string()),
+        initializer: vec![
+            float_tensor("w", &[2], &[1.0, 2.0]),
+            float_tensor("b", &[2], &[0.5, 0.5]),
+        ],
+        input: vec![
+            tensor_value_info("x", 1, &[D::V(2)]),
+            tensor_value_info("w", 1, &[D::V(2)]),
+            tensor_value_info("b", 1, &[D::V(2)]),
+        ],
+        node: vec![
+            node("Mul", &["x", "w"], &["t"], vec![]),
+            node("Add", &["t", "b"], &["y"], vec![]),
+        ],
+        output: vec![tensor_value_info("y", 1, &[D::V(2)])],
+        ..Default::default()
+    };
+    let proto = ModelProto {
+        ir_version: Some(3),
+        opset_import: vec![OperatorSetIdProto {
+            domain: Some(String::new()),
+            version: Some(8),
+        }],
+        graph: Some(graph),
+        ..Default::default()
+    };
+    let mut model = OnnxModel::load_from_bytes(proto.encode_to_vec()).unwrap();
+    assert_eq!(model.inputs, ["x"]);
+    assert_eq!(model.initializer_inputs, ["w", "b"]);
+
+    let input_names = |model: &OnnxModel| -> Vec<String> {
+        let bytes = model.to_bytes().unwrap();
+        let graph = ModelProto::decode(bytes.as_slice()).unwrap().graph.unwrap();
+        graph.input.into_iter().filter_map(|i| i.name).collect()
+    };
+    assert_eq!(input_names(&model), ["x", "w", "b"]);
+    let reloaded = OnnxModel::load_from_bytes(model.to_bytes().unwrap()).unwrap();
+    assert_eq!(reloaded.inputs, ["x"]);
+    assert_eq!(reloaded.initializer_inputs, ["w", "b"]);
+    assert_eq!(reloaded.tensors["w"].to_vec::<f32>().unwrap(), [1.0, 2.0]);
+
+    // a removed initializer is not listed as a required input
+    model.tensors.remove("b");
+    assert_eq!(input_names(&model), ["x", "w"]);
+}
+
+#[test]
+fn test_symbolic_dims() {
+    let graph = GraphProto {
+        name: Some("symbolic".to_string()),
+        input: vec![tensor_value_info(
+            "x",
+            1,
+            &[D::P("batch"), D::V(3), D::Unknown],
+        )],
+        output: vec![tensor_value_info("y", 1, &[D::P("batch"), D::V(3)])],
+        ..Default::default()
+    };
+    let model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+
+    let x = model.get_tensor("x").unwrap();
+    assert_eq!(x.shape(), &[-1, 3, -1]);
+    assert_eq!(
+        x.dims(),
+        &[
+            Dim::Symbolic("batch".to_string()),
+            Dim::Fixed(3),
+            Dim::Unknown
+        ]
+    );
+
+    let symbols = model.symbolic_dims();
+    assert_eq!(symbols["batch"], vec![("x", 0), ("y", 0)]);
+
+    let bindings = HashMap::from([("batch".to_string(), 8)]);
+    let y = model.get_tensor("y").unwrap();
+    assert_eq!(y.bind_shape(&bindings).unwrap(), vec![8, 3]);
+    assert!(x.bind_shape(&bindings).is_err(), "unknown dims cannot bind");
+
+    // dim_param names survive saving
+    let reloaded = OnnxModel::load_from_bytes(model.to_bytes().unwrap()).unwrap();
+    assert_eq!(reloaded.get_tensor("x").unwrap().dims(), x.dims());
+}
+
+#[test]
+fn test_model_header() {
+    let path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), MODEL_PATH);
+    let mut model = OnnxModel::load_from_file(&path).expect("Failed to load mnist model");
+
+    assert_eq!(model.ir_version, 7);
+    assert_eq!(model.domain, "ai.cntk");
+    assert_eq!(model.opset_version(""), Some(12));
+    assert_eq!(model.opset_version("ai.onnx"), Some(12));
+    assert_eq!(model.opset_version("com.microsoft"), None);
+
+    let conv = model.get_operations_by_type("Conv")[0];
+    assert!(conv.is_default_domain());
+    assert_eq!(model.operation_opset_version(conv), Some(12));
+
+    // header fields survive saving
+    model
+        .metadata_props
+        .insert("author".to_string(), "tests".to_string());
+    let reloaded = OnnxModel::load_from_bytes(model.to_bytes().unwrap()).unwrap();
+    assert_eq!(reloaded.ir_version, model.ir_version);
+    assert_eq!(reloaded.opset_import, model.opset_import);
+    assert_eq!(reloaded.domain, model.domain);
+    assert_eq!(reloaded.metadata_props, model.metadata_props);
+}
+
+#[test]
+fn test_operation_domains() {
+    let mut contrib = node("Attention", &["x", "w"], &["y"], vec![]);
+    contrib.domain = Some("com.microsoft".to_string());
+    contrib.overload = Some("fast".to_string());
+    contrib.doc_string = Some("fused attention".to_string());
+    contrib.metadata_props = vec![entry("origin", "fusion")];
+    let mut standard = node("Attention", &["x", "x", "x"], &["z"], vec![]);
+    standard.domain = Some("ai.onnx".to_string());
+    let graph = GraphProto {
+        name: Some("domains".to_string()),
+        initializer: vec![float_tensor("w", &[1], &[1.0])],
+        input: vec![tensor_value_info("x", 1, &[D::V(1)])],
+        node: vec![contrib, standard, node("Relu", &["y"], &["r"], vec![])],
+        output: vec![tensor_value_info("r", 1, &[D::V(1)])],
+        ..Default::default()
+    };
+    let model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+
+    let contrib = &model.operations[0];
+    assert_eq!(contrib.domain, "com.microsoft");
+    assert_eq!(contrib.overload, "fast");
+    assert_eq!(contrib.doc_string, "fused attention");
+    assert_eq!(contrib.metadata_props["origin"], "fusion");
+    assert_eq!(contrib.qualified_type(), "com.microsoft::Attention");
+    assert_eq!(model.operations[1].qualified_type(), "Attention");
+
+    // a bare type only matches the default domain
+    let names = |op_type: &str| -> Vec<&str> {
+        model
+            .get_operations_by_type(op_type)
+            .iter()
+            .map(|op| op.name.as_str())
+            .collect()
+    };
+    assert_eq!(names("Attention"), ["Attention_z"]);
+    assert_eq!(names("ai.onnx::Attention"), ["Attention_z"]);
+    assert_eq!(names("::Attention"), ["Attention_z"]);
+    assert_eq!(names("com.microsoft::Attention"), ["Attention_y"]);
+    assert!(names("com.example::Attention").is_empty());
+
+    assert_eq!(
+        model.operation_types(),
+        ["Attention", "Relu", "com.microsoft::Attention"]
+    );
+    let counts = model.count_operations_by_type();
+    assert_eq!(counts.len(), 3);
+    assert_eq!(counts["com.microsoft::Attention"], 1);
+    assert_eq!(counts["Attention"], 1);
+
+    let reloaded = OnnxModel::load_from_bytes(model.to_bytes().unwrap()).unwrap();
+    let op = &reloaded.operations[0];
+    assert_eq!(op.overload, "fast");
+    assert_eq!(op.doc_string, "fused attention");
+    assert_eq!(op.metadata_props, contrib.metadata_props);
+}
+
+#[test]
+fn test_if_subgraphs() {
+    let then_branch = GraphProto {
+        name: Some("then".to_string()),
+        node: vec![node("Identity", &["x"], &["then_out"], vec![])],
+        output: vec![tensor_value_info("then_out", 1, &[D::V(2)])],
+        ..Default::default()
+    };
+    let else_branch = GraphProto {
+        name: Some("else".to_string()),
+        node: vec![node("Neg", &["x"], &["else_out"], vec![])],
+        output: vec![tensor_value_info("else_out", 1, &[D::V(2)])],
+        ..Default::default()
+    };
+    let graph = GraphProto {
+        name: Some("control_flow".to_string()),
+        node: vec![node(
+            "If",
+            &["cond"],
+            &["y"],
+            vec![
+                graph_attr("then_branch", then_branch),
+                graph_attr("else_branch", else_branch),
+            ],
+        )],
+        input: vec![
+            tensor_value_info("cond", 9, &[]),
+            tensor_value_info("x", 1, &[D::V(2)]),
+        ],
+        output: vec![tensor_value_info("y", 1, &[D::V(2)])],
+        ..Default::default()
+    };
+    let model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("If model should load");
+
+    let if_op = &model.operations[0];
+    let subgraphs = if_op.subgraphs();
+    let names: Vec<&str> = subgraphs.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, vec!["else_branch", "then_branch"]);
+
+    let then_graph = if_op.get_graph_attribute("then_branch").unwrap();
+    assert_eq!(then_graph.outputs, vec!["then_out".to_string()]);
+    assert_eq!(then_graph.outer_scope_names(), vec!["x"]);
+
+    // names captured from the outer scope resolve to the main graph tensor
+    let root = model.scope();
+    let inner = root.enter(then_graph);
+    assert_eq!(inner.depth(), 1);
+    assert_eq!(inner.get_tensor("x").unwrap().shape(), &[2]);
+    assert!(inner.get_tensor("then_out").is_some());
+    assert!(root.get_tensor("then_out").is_none());
+
+    let reloaded = OnnxModel::load_from_bytes(model.to_bytes().unwrap()).unwrap();
+    let saved_else = reloaded.operations[0]
+        .get_graph_attribute("else_branch")
+        .expect("subgraphs should survive saving");
+    assert_eq!(saved_else.operations[0].op_type, "Neg");
+}
+
+#[test]
+fn test_non_tensor_value_types() {
+    let tensor_type = |elem_type: i32, dims: &[D]| tensor_value_info("", elem_type, dims).r#type;
+    let typed = |name: &str, value: type_proto::Value| ValueInfoProto {
+        name: Some(name.to_string()),
+        r#type: Some(TypeProto {
+            value: Some(value),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let seq = typed(
+        "seq",
+        type_proto::Value::SequenceType(Box::new(type_proto::Sequence {
+            elem_type: tensor_type(1, &[D::P("n")]).map(Box::new),
+        })),
+    );
+    let maybe = typed(
+        "maybe",
+        type_proto::Value::OptionalType(Box::new(type_proto::Optional {
+            elem_type: tensor_type(7, &[]).map(Box::new),
+        })),
+    );
+    let sparse = typed(
+        "sp",
+        type_proto::Value::SparseTensorType(type_proto::SparseTensor {
+            elem_type: Some(1),
+            shape: tensor_value_info("", 1, &[D::V(2), D::V(3)])
+                .r#type
+                .and_then(|t| match t.value {
+                    Some(type_proto::Value::TensorType(t)) => t.shape,
+                    _ => None,
+                }),
+        }),
+    );
+    let pairs = typed(
+        "pairs",
+        type_proto::Value::SequenceType(Box::new(type_proto::Sequence {
+            elem_type: Some(Box::new(TypeProto {
+                value: Some(type_proto::Value::MapType(Box::new(type_proto::Map {
+                    key_type: Some(7),
+                    value_type: tensor_type(1, &[]).map(Box::new),
+                }))),
+                ..Default::default()
+            })),
+        })),
+    );
+    let graph = GraphProto {
+        name: Some("typed".to_string()),
+        input: vec![seq, maybe, tensor_value_info("x", 1, &[D::V(2)])],
+        node: vec![
+            node("SequenceLength", &["seq"], &["len"], vec![]),
+            node("OptionalHasElement", &["maybe"], &["has"], vec![]),
+            node("Identity", &["x"], &["sp_dense"], vec![]),
+            node("DenseToSparse", &["sp_dense"], &["sp"], vec![]),
+            node("ZipMap", &["x"], &["pairs"], vec![]),
+        ],
+        value_info: vec![sparse],
+        output: vec![
+            tensor_value_info("len", 7, &[]),
+            tensor_value_info("has", 9, &[]),
+            pairs,
+        ],
+        ..Default::default()
+    };
+    let model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+
+    // non-tensor inputs keep their place but have no OnnxTensor
+    assert_eq!(model.inputs, ["seq", "maybe", "x"]);
+    assert_eq!(model.get_input_tensors().len(), 1);
+    let mut typed_names: Vec<&String> = model.value_types.keys().collect();
+    typed_names.sort();
+    assert_eq!(typed_names, ["maybe", "pairs", "seq", "sp"]);
+
+    let float_tensor_type = |shape: Vec<Dim>| ValueType::Tensor {
+        data_type: DataType::Float,
+        shape: Some(shape),
+    };
+    let seq_type = model.value_type("seq").unwrap();
+    assert_eq!(
+        seq_type,
+        ValueType::Sequence(Box::new(float_tensor_type(vec![Dim::Symbolic(
+            "n".to_string()
+        )])))
+    );
+    assert!(!seq_type.is_tensor());
+    assert_eq!(
+        model.value_type("maybe").unwrap().elem_type(),
+        Some(&ValueType::Tensor {
+            data_type: DataType::Int64,
+            shape: Some(vec![]),
+        })
+    );
+    assert_eq!(
+        model.value_type("sp"),
+        Some(ValueType::SparseTensor {
+            data_type: DataType::Float,
+            shape: Some(vec![Dim::Fixed(2), Dim::Fixed(3)]),
+        })
+    );
+    assert_eq!(
+        model.value_type("pairs"),
+        Some(ValueType::Sequence(Box::new(ValueType::Map {
+            key_type: DataType::Int64,
+            value_type: Box::new(float_tensor_type(vec![])),
+        })))
+    );
+    // dense tensors are reported from `tensors`
+    assert_eq!(
+        model.value_type("x"),
+        Some(float_tensor_type(vec![Dim::Fixed(2)]))
+    );
+    assert_eq!(model.value_type("missing"), None);
+
+    let reloaded = OnnxModel::load_from_bytes(model.to_bytes().unwrap()).unwrap();
+    assert_eq!(reloaded.inputs, model.inputs);
+    assert_eq!(reloaded.outputs, model.outputs);
+    assert_eq!(reloaded.value_types, model.value_types);
+}
+
+#[test]
+fn test_all_attribute_types() {
+    let sparse = SparseTensorProto {
+        values: Some(float_tensor("sparse", &[2], &[1.5, -2.0])),
+        indices: Some(int64_tensor("sparse_indices", &[2], &[1, 4])),
+        dims: vec![2, 3],
+    };
+    let seq_of_float = TypeProto {
+        value: Some(type_proto::Value::SequenceType(Box::new(
+            type_proto::Sequence {
+                elem_type: Some(Box::new(
+                    tensor_value_info("", 1, &[D::P("n")]).r#type.unwrap(),
+                )),
+            },
+        ))),
+        ..Default::default()
+    };
+    let attrs = vec![
+        AttributeProto {
+            name: Some("tensors".to_string()),
+            r#type: Some(9),
+            tensors: vec![
+                float_tensor("a", &[1], &[1.0]),
+                float_tensor("b", &[2], &[2.0, 3.0]),
+            ],
+            ..Default::default()
+        },
+        AttributeProto {
+            name: Some("sparse".to_string()),
+            r#type: Some(11),
+            sparse_tensor: Some(sparse.clone()),
+            ..Default::default()
+        },
+        AttributeProto {
+            name: Some("sparses".to_string()),
+            r#type: Some(12),
+            sparse_tensors: vec![sparse],
+            ..Default::default()
+        },
+        AttributeProto {
+            name: Some("type".to_string()),
+            r#type: Some(13),
+            tp: Some(seq_of_float.clone()),
+            ..Default::default()
+        },
+        AttributeProto {
+            name: Some("types".to_string()),
+            r#type: Some(14),
+            type_protos: vec![seq_of_float],
+            ..Default::default()
+        },
+    ];
+    let graph = GraphProto {
+        name: Some("attributes".to_string()),
+        node: vec![node("CustomOp", &[], &["y"], attrs)],
+        output: vec![tensor_value_info("y", 1, &[D::V(1)])],
+        ..Default::default()
+    };
+    let model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+
+    let check = |model: &OnnxModel| {
+        let op = &model.operations[0];
+        let tensors = op.get_attribute("tensors").unwrap().as_tensors().unwrap();
+        assert_eq!(tensors.len(), 2);
+        assert_eq!(tensors[1].shape(), &[2]);
+
+        let sparse = op
+            .get_attribute("sparse")
+            .unwrap()
+            .as_sparse_tensor()
+            .unwrap();
+        assert_eq!(sparse.name(), "sparse");
+        assert_eq!(sparse.shape(), &[2, 3]);
+        assert_eq!(sparse.nnz(), 2);
+        assert_eq!(sparse.data_type(), DataType::Float);
+        let sparses = op.get_attribute("sparses").unwrap().as_sparse_tensors();
+        assert_eq!(sparses.unwrap().len(), 1);
+
+        let expected = ValueType::Sequence(Box::new(ValueType::Tensor {
+            data_type: DataType::Float,
+            shape: Some(vec![Dim::Symbolic("n".to_string())]),
+        }));
+        assert_eq!(op.get_attribute("type").unwrap().as_type(), Some(&expected));
+        assert_eq!(
+            op.get_attribute("types").unwrap().as_types(),
+            Some(std::slice::from_ref(&expected))
+        );
+    };
+    check(&model);
+
+    let reloaded = OnnxModel::load_from_bytes(model.to_bytes().unwrap()).unwrap();
+    check(&reloaded);
+}
+
+#[test]
+fn test_sparse_initializers() {
+    let linear = SparseTensorProto {
+        values: Some(float_tensor("w_linear", &[2], &[1.5, -2.0])),
+        indices: Some(int64_tensor("w_linear_idx", &[2], &[1, 5])),
+        dims: vec![2, 3],
+    };
+    let coords = SparseTensorProto {
+        values: Some(float_tensor("w_coords", &[2], &[4.0, 8.0])),
+        indices: Some(int64_tensor("w_coords_idx", &[2, 2], &[0, 2, 1, 0])),
+        dims: vec![2, 3],
+    };
+    let graph = GraphProto {
+        name: Some("sparse".to_string()),
+        node: vec![
+            node("Add", &["x", "w_linear"], &["t"], vec![]),
+            node("Add", &["t", "w_coords"], &["y"], vec![]),
+        ],
+        sparse_initializer: vec![linear, coords],
+        input: vec![
+            tensor_value_info("x", 1, &[D::V(2), D::V(3)]),
+            tensor_value_info("w_linear", 1, &[D::V(2), D::V(3)]),
+        ],
+        output: vec![tensor_value_info("y", 1, &[D::V(2), D::V(3)])],
+        ..Default::default()
+    };
+    let model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+
+    assert_eq!(model.inputs, vec!["x".to_string()]);
+    assert_eq!(model.sparse_tensors.len(), 2);
+
+    let dense = model
+        .get_sparse_tensor("w_linear")
+        .unwrap()
+        .to_dense()
+        .unwrap();
+    assert_eq!(dense.shape(), &[2, 3]);
+    assert_eq!(
+        &*dense.copy_data_as::<f32>().unwrap(),
+        &[0.0, 1.5, 0.0, 0.0, 0.0, -2.0]
+    );
+
+    let dense = model
+        .get_sparse_tensor("w_coords")
+        .unwrap()
+        .to_dense()
+        .unwrap();
+    assert_eq!(
+        &*dense.copy_data_as::<f32>().unwrap(),
+        &[0.0, 0.0, 4.0, 8.0, 0.0, 0.0]
+    );
+
+    // the input listing does not add a dense entry for a sparse initializer
+    assert!(!model.tensors.contains_key("w_linear"));
+
+    let bytes = model.to_bytes().unwrap();
+    let saved = ModelProto::decode(bytes.as_slice()).unwrap().graph.unwrap();
+    assert!(
+        saved
+            .value_info
+            .iter()
+            .all(|v| v.name.as_deref() != Some("w_linear"))
+    );
+    let listed: Vec<_> = saved
+        .input
+        .iter()
+        .filter_map(|i| i.name.as_deref())
+        .collect();
+    assert_eq!(listed, ["x", "w_linear"]);
+    let reloaded = OnnxModel::load_from_bytes(bytes).unwrap();
+    assert_eq!(reloaded.inputs, model.inputs);
+    assert_eq!(reloaded.initializer_inputs, ["w_linear"]);
+    assert!(!reloaded.tensors.contains_key("w_linear"));
+    let saved = reloaded.get_sparse_tensor("w_coords").unwrap();
+    assert_eq!(saved.indices().shape(), &[2, 2]);
+
+    // scalars have zero coordinates per value and room for one value
+    let scalar = |name: &str, values: &[f32]| SparseTensorProto {
+        values: Some(float_tensor(name, &[values.len() as i64], values)),
+        indices: Some(int64_tensor(
+            &format!("{name}_idx"),
+            &[values.len() as i64, 0],
+            &[],
+        )),
+        dims: vec![],
+    };
+    let graph = GraphProto {
+        name: Some("scalars".to_string()),
+        sparse_initializer: vec![scalar("s", &[3.0]), scalar("s_bad", &[1.0, 2.0])],
+        ..Default::default()
+    };
+    let model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+    let dense = model.get_sparse_tensor("s").unwrap().to_dense().unwrap();
+    assert!(dense.shape().is_empty());
+    assert_eq!(dense.to_vec::<f32>().unwrap(), [3.0]);
+    assert!(matches!(
+        model.get_sparse_tensor("s_bad").unwrap().to_dense(),
+        Err(onnx_extractor::Error::InvalidModel(_))
+    ));
+}
+
+#[test]
+fn test_model_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<OnnxModel>();
+    assert_send_sync::<OnnxTensor>();
+    assert_send_sync::<TensorData<'static>>();
+}
+
+/// Write a model with four external FLOAT[8] initializers `w0..w3`
+///
+/// Tensor `wi` holds values `i * 100 + 8 ..= i * 100 + 15`, stored at offset
+/// 32 of the file named by `file_for(i)`. Files shared by several tensors get
+/// one 64-byte block per tensor.
+fn write_external_model(
+    dir: &std::path::Path,
+    file_for: impl Fn(usize) -> String,
+) -> std::path::PathBuf {
+    std::fs::create_dir_all(dir).unwrap();
+
+    let mut files: HashMap<String, Vec<u8>> = HashMap::new();
+    let mut initializer = Vec::new();
+    for i in 0..4 {
+        let file = file_for(i);
+        let contents = files.entry(file.clone()).or_default();
+        let base = contents.len();
+        let values: Vec<f32> = (0..16).map(|v| (v + i * 100) as f32).collect();
+        contents.extend(values.iter().flat_map(|v| v.to_le_bytes()));
+
+        let mut tensor = float_tensor(&format!("w{i}"), &[8], &[]);
+        tensor.raw_data = None;
+        tensor.external_data = vec![
+            entry("location", &file),
+            entry("offset", &(base + 32).to_string()),
+            entry("length", "32"),
+        ];
+        initializer.push(tensor);
+    }
+    for (file, contents) in files {
+        std::fs::write(dir.join(file), contents).unwrap();
+    }
+
+    let graph = GraphProto {
+        name: Some("external".to_string()),
+        initializer,
+        ..Default::default()
+    };
+    let path = dir.join("model.onnx");
+    std::fs::write(&path, model_bytes(graph)).unwrap();
+    path
+}
+
+#[test]
+fn test_external_data_shared_across_threads() {
+    let dir = std::env::temp_dir().join("onnx_extractor_external_threads");
+    let path = write_external_model(&dir, |i| format!("weights_{i}.bin"));
+    let model = OnnxModel::load_from_file(path.to_str().unwrap()).expect("model should load");
+    let model = std::sync::Arc::new(model);
+
+    let handles: Vec<_> = (0..8)
+        .map(|t| {
+            let model = model.clone();
+            std::thread::spawn(move || {
+                let i = t % 4;
+                let tensor = model.get_tensor(&format!("w{i}")).unwrap();
+                let values = tensor.copy_data_as::<f32>().unwrap();
+                let expected: Vec<f32> = (8..16).map(|v| (v + i * 100) as f32).collect();
+                assert_eq!(&*values, expected.as_slice());
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().expect("worker thread should not panic");
+    }
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_external_attribute_tensors() {
+    let dir = std::env::temp_dir().join("onnx_extractor_external_attributes");
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut contents: Vec<u8> = [1.5f32, -2.0, 4.0]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    contents.extend(2i64.to_le_bytes());
+    std::fs::write(dir.join("attributes.bin"), contents).unwrap();
+
+    let external = |mut tensor: TensorProto, offset: usize, length: usize| {
+        tensor.raw_data = None;
+        tensor.float_data.clear();
+        tensor.int64_data.clear();
+        tensor.data_location = Some(1);
+        tensor.external_data = vec![
+            entry("location", "attributes.bin"),
+            entry("offset", &offset.to_string()),
+            entry("length", &length.to_string()),
+        ];
+        tensor
+    };
+    let dense = AttributeProto {
+        name: Some("value".to_string()),
+        r#type: Some(4),
+        t: Some(external(float_tensor("dense", &[2], &[]), 0, 8)),
+        ..Default::default()
+    };
+    let sparse = AttributeProto {
+        name: Some("sparse_value".to_string()),
+        r#type: Some(11),
+        sparse_tensor: Some(SparseTensorProto {
+            values: Some(external(float_tensor("sparse", &[1], &[]), 8, 4)),
+            indices: Some(external(int64_tensor("sparse_idx", &[1], &[]), 12, 8)),
+            dims: vec![3],
+        }),
+        ..Default::default()
+    };
+    let graph = GraphProto {
+        name: Some("constants".to_string()),
+        node: vec![
+            node("Constant", &[], &["a"], vec![dense]),
+            node("Constant", &[], &["b"], vec![sparse]),
+        ],
+        ..Default::default()
+    };
+    let path = dir.join("model.onnx");
+    std::fs::write(&path, model_bytes(graph)).unwrap();
+
+    let check = |model: &OnnxModel| {
+        let value = model.operations[0].get_attribute("value").unwrap();
+        let value = value.as_tensor().unwrap();
+        assert_eq!(value.to_vec::<f32>().unwrap(), [1.5, -2.0]);
+        let sparse = model.operations[1].get_attribute("sparse_value").unwrap();
+        let dense = sparse.as_sparse_tensor().unwrap().to_dense().unwrap();
+        assert_eq!(dense.to_vec::<f32>().unwrap(), [0.0, 0.0, 4.0]);
+    };
+    let model = OnnxModel::load_from_file(path.to_str().unwrap()).expect("model should load");
+    check(&model);
+    // saving inlines the external attribute data
+    let bytes = model.to_bytes().unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+    check(&OnnxModel::load_from_bytes(bytes).unwrap());
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_mmap_loading() {
+    let path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), MODEL_PATH);
+    let options = LoadOptions {
+        mmap: true,
+        ..Default::default()
+    };
+    let mapped = OnnxModel::load_from_file_with_options(&path, &options)
+        .expect("mmap loading should succeed");
+    let read = OnnxModel::load_from_file(&path).expect("Failed to load mnist model");
+
+    assert_eq!(mapped.operations.len(), read.operations.len());
+    for tensor in read.get_weight_tensors() {
+        let mapped_tensor = mapped.get_tensor(tensor.name()).unwrap();
+        assert_eq!(
+            mapped_tensor.data().unwrap().as_slice(),
+            tensor.data().unwrap().as_slice()
+        );
+    }
+}
+
+#[cfg(not(feature = "mmap"))]
+#[test]
+fn test_mmap_requires_feature() {
+    let path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), MODEL_PATH);
+    let options = LoadOptions {
+        mmap: true,
+        ..Default::default()
+    };
+    let result = OnnxModel::load_from_file_with_options(&path, &options);
+    assert!(matches!(result, Err(onnx_extractor::Error::Unsupported(_))));
+}
+
+#[test]
+fn test_external_data_cache_policies() {
+    let dir = std::env::temp_dir().join("onnx_extractor_cache_policies");
+    let path = write_external_model(&dir, |_| "weights.bin".to_string());
+    let path = path.to_str().unwrap();
+    let file = dir.join("weights.bin");
+
+    let first = |model: &OnnxModel, name: &str| {
+        model
+            .get_tensor(name)
+            .unwrap()
+            .copy_data_as::<f32>()
+            .unwrap()[0]
+    };
+    let load = |cache_policy| {
+        let options = LoadOptions {
+            cache_policy,
+            ..Default::default()
+        };
+        OnnxModel::load_from_file_with_options(path, &options).unwrap()
+    };
+    // overwrite the first value of every tensor so re-reads are visible
+    let rewrite = |value: f32| {
+        let mut contents = std::fs::read(&file).unwrap();
+        for i in 0..4 {
+            let at = i * 64 + 32;
+            contents[at..at + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        std::fs::write(&file, contents).unwrap();
+    };
+
+    // whole file: cached on first access, later tensors see the old file
+    rewrite(8.0);
+    let model = load(CachePolicy::WholeFile);
+    assert_eq!(first(&model, "w0"), 8.0);
+    rewrite(-1.0);
+    assert_eq!(first(&model, "w1"), 8.0);
+
+    // none: every access reads the range again
+    let model = load(CachePolicy::None);
+    assert_eq!(first(&model, "w0"), -1.0);
+    rewrite(-2.0);
+    assert_eq!(first(&model, "w0"), -2.0);
+
+    // per tensor: each range is cached separately
+    let model = load(CachePolicy::PerTensor);
+    assert_eq!(first(&model, "w0"), -2.0);
+    rewrite(-3.0);
+    assert_eq!(first(&model, "w0"), -2.0);
+    assert_eq!(first(&model, "w1"), -3.0);
+
+    // lru with room for one tensor: w0 is evicted once w1 is cached
+    let model = load(CachePolicy::Lru { max_bytes: 32 });
+    assert_eq!(first(&model, "w0"), -3.0);
+    assert_eq!(first(&model, "w1"), -3.0);
+    rewrite(-4.0);
+    assert_eq!(first(&model, "w1"), -3.0);
+    assert_eq!(first(&model, "w0"), -4.0);
+
+    // lru with room for two tensors: a hit refreshes w0, so w1 is evicted
+    let model = load(CachePolicy::Lru { max_bytes: 64 });
+    assert_eq!(first(&model, "w0"), -4.0);
+    assert_eq!(first(&model, "w1"), -4.0);
+    assert_eq!(first(&model, "w0"), -4.0);
+    assert_eq!(first(&model, "w2"), -4.0);
+    rewrite(-5.0);
+    assert_eq!(first(&model, "w0"), -4.0);
+    assert_eq!(first(&model, "w2"), -4.0);
+    assert_eq!(first(&model, "w1"), -5.0);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_float_decoding() {
+    let f16: Vec<u8> = [0x3C00u16, 0xC000, 0x7C00, 0xFC00, 0x7E00, 0x0001]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    let bf16: Vec<u8> = [0x3F80u16, 0xC040, 0x7F80]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    let graph = GraphProto {
+        name: Some("floats".to_string()),
+        initializer: vec![
+            float_tensor("f32", &[2], &[1.5, -2.0]),
+            raw_tensor("f64", 11, &[1], &0.1f64.to_le_bytes()),
+            raw_tensor("f16", 10, &[6], &f16),
+            raw_tensor("bf16", 16, &[3], &bf16),
+            raw_tensor("e4m3fn", 17, &[5], &[0x38, 0x7E, 0x01, 0x7F, 0xFF]),
+            raw_tensor("e4m3fnuz", 18, &[4], &[0x40, 0x7F, 0x80, 0xC0]),
+            raw_tensor("e5m2", 19, &[5], &[0x3C, 0x7B, 0x7C, 0xFC, 0x7D]),
+            raw_tensor("e5m2fnuz", 20, &[3], &[0x40, 0x7F, 0x80]),
+            raw_tensor("e8m0", 24, &[4], &[0x7F, 0x80, 0x00, 0xFF]),
+            // low nibble first, odd element count leaves the last nibble unused
+            raw_tensor("e2m1", 23, &[3], &[0x72, 0x0F]),
+            int64_tensor("ints", &[1], &[1]),
+        ],
+        ..Default::default()
+    };
+    let model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+    let decode = |name: &str| model.get_tensor(name).unwrap().to_f64_vec().unwrap();
+
+    assert_eq!(decode("f32"), [1.5, -2.0]);
+    assert_eq!(decode("f64"), [0.1]);
+    assert_eq!(
+        model.get_tensor("f64").unwrap().to_f32_vec().unwrap(),
+        [0.1f32]
+    );
+
+    let f16 = decode("f16");
+    assert_eq!(f16[..4], [1.0, -2.0, f64::INFINITY, f64::NEG_INFINITY]);
+    assert!(f16[4].is_nan());
+    assert_eq!(f16[5], 2f64.powi(-24));
+
+    let bf16 = decode("bf16");
+    assert_eq!(bf16, [1.0, -3.0, f64::INFINITY]);
+
+    let e4m3fn = decode("e4m3fn");
+    assert_eq!(e4m3fn[..3], [1.0, 448.0, 2f64.powi(-9)]);
+    assert!(e4m3fn[3].is_nan() && e4m3fn[4].is_nan());
+
+    let e4m3fnuz = decode("e4m3fnuz");
+    assert_eq!(e4m3fnuz[..2], [1.0, 240.0]);
+    assert!(e4m3fnuz[2].is_nan());
+    assert_eq!(e4m3fnuz[3], -1.0);
+
+    let e5m2 = decode("e5m2");
+    assert_eq!(e5m2[..4], [1.0, 57344.0, f64::INFINITY, f64::NEG_INFINITY]);
+    assert!(e5m2[4].is_nan());
+
+    let e5m2fnuz = decode("e5m2fnuz");
+    assert_eq!(e5m2fnuz[..2], [1.0, 57344.0]);
+    assert!(e5m2fnuz[2].is_nan());
+
+    let e8m0 = decode("e8m0");
+    assert_eq!(e8m0[..3], [1.0, 2.0, 2f64.powi(-127)]);
+    assert!(e8m0[3].is_nan());
+
+    assert_eq!(decode("e2m1"), [1.0, 6.0, -6.0]);
+
+    assert!(model.get_tensor("ints").unwrap().to_f32_vec().is_err());
+}
+
+#[test]
+fn test_typed_field_data_matrix() {
+    let le = |values: &[u16]| -> Vec<u8> { values.iter().flat_map(|v| v.to_le_bytes()).collect() };
+    let typed = |data_type: i32, dims: &[i64]| TensorProto {
+        dims: dims.to_vec(),
+        data_type: Some(data_type),
+        ..Default::default()
+    };
+    let int32 = |data_type: i32, dims: &[i64], values: &[i32]| TensorProto {
+        int32_data: values.to_vec(),
+        ..typed(data_type, dims)
+    };
+    let fp8 = |data_type: i32| {
+        (
+            data_type,
+            int32(data_type, &[2], &[0x38, 0xC0]),
+            vec![0x38, 0xC0],
+        )
+    };
+
+    // (data type, tensor using the typed field, expected canonical bytes)
+    let cases: Vec<(i32, TensorProto, Vec<u8>)> = vec![
+        (
+            1,
+            TensorProto {
+                float_data: vec![1.5, -2.0],
+                ..typed(1, &[2])
+            },
+            [1.5f32, -2.0]
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect(),
+        ),
+        (2, int32(2, &[2], &[1, 255]), vec![1, 255]),
+        (3, int32(3, &[2], &[-1, 127]), vec![0xFF, 0x7F]),
+        (4, int32(4, &[2], &[1, 65535]), le(&[1, 65535])),
+        (5, int32(5, &[2], &[-2, 300]), le(&[0xFFFE, 300])),
+        (
+            6,
+            int32(6, &[2], &[-5, 7]),
+            [-5i32, 7].iter().flat_map(|v| v.to_le_bytes()).collect(),
+        ),
+        (
+            7,
+            TensorProto {
+                int64_data: vec![-5, 1 << 40],
+                ..typed(7, &[2])
+            },
+            [-5i64, 1 << 40]
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect(),
+        ),
+        (9, int32(9, &[3], &[1, 0, 1]), vec![1, 0, 1]),
+        (
+            10,
+            int32(10, &[2], &[0x3C00, 0xC000]),
+            le(&[0x3C00, 0xC000]),
+        ),
+        (
+            11,
+            TensorProto {
+                double_data: vec![0.25],
+                ..typed(11, &[1])
+            },
+            0.25f64.to_le_bytes().to_vec(),
+        ),
+        (
+            12,
+            TensorProto {
+                uint64_data: vec![7, u32::MAX as u64],
+                ..typed(12, &[2])
+            },
+            [7u32, u32::MAX]
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect(),
+        ),
+        (
+            13,
+            TensorProto {
+                uint64_data: vec![u64::MAX],
+                ..typed(13, &[1])
+            },
+            u64::MAX.to_le_bytes().to_vec(),
+        ),
+        (
+            14,
+            TensorProto {
+                float_data: vec![1.0, 2.0],
+                ..typed(14, &[1])
+            },
+            [1.0f32, 2.0].iter().flat_map(|v| v.to_le_bytes()).collect(),
+        ),
+        (
+            15,
+            TensorProto {
+                double_data: vec![1.0, 2.0],
+                ..typed(15, &[1])
+            },
+            [1.0f64, 2.0].iter().flat_map(|v| v.to_le_bytes()).collect(),
+        ),
+        (16, int32(16, &[1], &[0x3F80]), le(&[0x3F80])),
+        fp8(17),
+        fp8(18),
+        fp8(19),
+        fp8(20),
+        // 4-bit types hold two elements per int32_data value
+        (21, int32(21, &[3], &[0x21, 0x03]), vec![0x21, 0x03]),
+        (22, int32(22, &[2], &[0xF1]), vec![0xF1]),
+        (23, int32(23, &[2], &[0x72]), vec![0x72]),
+        fp8(24),
+        // 2-bit types hold four elements per int32_data value
+        (25, int32(25, &[4], &[0xE4]), vec![0xE4]),
+        (26, int32(26, &[5], &[0x1B, 0x03]), vec![0x1B, 0x03]),
+    ];
+
+    let mut covered: Vec<i32> = cases.iter().map(|(dt, _, _)| *dt).collect();
+    covered.push(8); // strings are checked below
+    covered.sort_unstable();
+    let numeric: Vec<i32> = (1..=26)
+        .filter(|&dt| DataType::from_onnx_type(dt) != DataType::Undefined)
+        .collect();
+    assert_eq!(covered, numeric, "every data type should be covered");
+
+    let mut initializers = Vec::new();
+    for (dt, tensor, expected) in &cases {
+        initializers.push(TensorProto {
+            name: Some(format!("typed_{dt}")),
+            ..tensor.clone()
+        });
+        initializers.push(raw_tensor(
+            &format!("raw_{dt}"),
+            *dt,
+            &tensor.dims,
+            expected,
+        ));
+    }
+    initializers.push(TensorProto {
+        name: Some("strings".to_string()),
+        string_data: vec![Bytes::from_static(b"ab"), Bytes::from_static(b"c")],
+        ..typed(8, &[2])
+    });
+    let graph = GraphProto {
+        name: Some("matrix".to_string()),
+        initializer: initializers,
+        ..Default::default()
+    };
+    let mut model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+
+    for (dt, _, expected) in &cases {
+        for prefix in ["typed", "raw"] {
+            let name = format!("{prefix}_{dt}");
+            let tensor = model.get_tensor(&name).unwrap();
+            assert_eq!(
+                tensor.data().unwrap().as_slice().as_ref(),
+                expected.as_slice(),
+                "data() for {name}"
+            );
+            let owned = model.tensors.remove(&name).unwrap().into_data().unwrap();
+            assert_eq!(
+                owned.as_slice().as_ref(),
+                expected.as_slice(),
+                "into_data() for {name}"
+            );
+        }
+    }
+
+    let strings = model.get_tensor("strings").unwrap().data().unwrap();
+    assert!(matches!(&strings, TensorData::Strings(parts) if parts.len() == 2));
+}
+
+#[test]
+fn test_typed_int8_weights() {
+    let graph = GraphProto {
+        name: Some("int8".to_string()),
+        initializer: vec![TensorProto {
+            name: Some("w".to_string()),
+            dims: vec![3],
+            data_type: Some(3),
+            int32_data: vec![-128, 0, 127],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+    let w = model.get_tensor("w").unwrap();
+    assert_eq!(*w.copy_data_as::<i8>().unwrap(), [-128, 0, 127]);
+    assert_eq!(*w.copy_data_as::<u8>().unwrap(), [0x80, 0, 0x7F]);
+}
+
+#[test]
+fn test_sub_byte_types() {
+    assert_eq!(DataType::Int4.size_in_bytes(), None);
+    assert_eq!(DataType::Int4.bits_per_element(), Some(4));
+    assert_eq!(DataType::Uint2.bits_per_element(), Some(2));
+    assert!(DataType::Float4e2m1.is_sub_byte() && !DataType::Uint8.is_sub_byte());
+    assert_eq!(DataType::Int4.storage_bytes_for(&[3]), Some(2));
+    assert_eq!(DataType::Int2.storage_bytes_for(&[5]), Some(2));
+    assert_eq!(DataType::Float.storage_bytes_for(&[2, 3]), Some(24));
+    assert_eq!(DataType::Float.storage_bytes_for(&[]), Some(4));
+    assert_eq!(DataType::Int4.storage_bytes_for(&[-1, 2]), None);
+    assert_eq!(DataType::String.storage_bytes_for(&[1]), None);
+
+    let graph = GraphProto {
+        name: Some("packed".to_string()),
+        initializer: vec![
+            raw_tensor("int4", 22, &[3], &[0x7F, 0x08]),
+            raw_tensor("uint4", 21, &[3], &[0x21, 0x03]),
+            raw_tensor("int2", 26, &[5], &[0xE4, 0x03]),
+            raw_tensor("uint2", 25, &[5], &[0xE4, 0x03]),
+            raw_tensor("fp4", 23, &[3], &[0x72, 0x0F]),
+            raw_tensor("short", 22, &[4], &[0x7F]),
+        ],
+        ..Default::default()
+    };
+    let model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+    let tensor = |name: &str| model.get_tensor(name).unwrap();
+
+    let int4: Vec<i8> = tensor("int4").unpack_i8().unwrap().collect();
+    assert_eq!(int4, [-1, 7, -8]);
+    let uint4: Vec<u8> = tensor("uint4").unpack_u8().unwrap().collect();
+    assert_eq!(uint4, [1, 2, 3]);
+    let int2: Vec<i8> = tensor("int2").unpack_i8().unwrap().collect();
+    assert_eq!(int2, [0, 1, -2, -1, -1]);
+    let uint2: Vec<u8> = tensor("uint2").unpack_u8().unwrap().collect();
+    assert_eq!(uint2, [0, 1, 2, 3, 3]);
+    let fp4: Vec<f32> = tensor("fp4").unpack_f32().unwrap().collect();
+    assert_eq!(fp4, [1.0, 6.0, -6.0]);
+    assert_eq!(tensor("int2").packed_elements().unwrap().len(), 5);
+
+    assert!(tensor("short").packed_elements().is_err());
+    assert!(tensor("uint4").unpack_i8().is_err());
+}
+
+#[test]
+fn test_byte_order_independent_decoding() {
+    // the same values stored as little-endian raw_data and as typed fields
+    let ints: [i32; 3] = [1, -2, 0x0102_0304];
+    let raw_ints: Vec<u8> = ints.iter().flat_map(|v| v.to_le_bytes()).collect();
+    let halves: [u16; 2] = [0x3C00, 0xC000];
+    let graph = GraphProto {
+        name: Some("endian".to_string()),
+        initializer: vec![
+            raw_tensor("raw_i32", 6, &[3], &raw_ints),
+            TensorProto {
+                name: Some("typed_i32".to_string()),
+                dims: vec![3],
+                data_type: Some(6),
+                int32_data: ints.to_vec(),
+                ..Default::default()
+            },
+            float_tensor("raw_f32", &[2], &[0.5, -3.25]),
+            TensorProto {
+                name: Some("typed_f32".to_string()),
+                dims: vec![2],
+                data_type: Some(1),
+                float_data: vec![0.5, -3.25],
+                ..Default::default()
+            },
+            TensorProto {
+                name: Some("typed_u64".to_string()),
+                dims: vec![1],
+                data_type: Some(13),
+                uint64_data: vec![0x0102_0304_0506_0708],
+                ..Default::default()
+            },
+            TensorProto {
+                name: Some("typed_f16".to_string()),
+                dims: vec![2],
+                data_type: Some(10),
+                int32_data: halves.iter().map(|&h| h as i32).collect(),
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+    let model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+    let tensor = |name: &str| model.get_tensor(name).unwrap();
+
+    for name in ["raw_i32", "typed_i32"] {
+        assert_eq!(*tensor(name).copy_data_as::<i32>().unwrap(), ints, "{name}");
+        assert_eq!(tensor(name).data().unwrap().as_slice().as_ref(), raw_ints);
+    }
+    for name in ["raw_f32", "typed_f32"] {
+        assert_eq!(*tensor(name).copy_data_as::<f32>().unwrap(), [0.5, -3.25]);
+        assert_eq!(tensor(name).to_f32_vec().unwrap(), [0.5, -3.25]);
+    }
+    let u64_tensor = tensor("typed_u64");
+    assert_eq!(
+        u64_tensor.data().unwrap().as_slice().as_ref(),
+        [8, 7, 6, 5, 4, 3, 2, 1]
+    );
+    assert_eq!(
+        *u64_tensor.copy_data_as::<u64>().unwrap(),
+        [0x0102_0304_0506_0708]
+    );
+    assert_eq!(*tensor("typed_f16").copy_data_as::<u16>().unwrap(), halves);
+    assert_eq!(tensor("typed_f16").to_f32_vec().unwrap(), [1.0, -2.0]);
+
+    let owned = model
+        .get_tensor("typed_i32")
+        .unwrap()
+        .data()
+        .unwrap()
+        .into_owned();
+    assert_eq!(owned.as_slice().as_ref(), raw_ints);
+}
+
+#[test]
+fn test_typed_element_access() {
+    let graph = GraphProto {
+        name: Some("elements".to_string()),
+        initializer: vec![
+            float_tensor("raw_f32", &[2], &[1.0, 2.5]),
+            TensorProto {
+                name: Some("typed_f32".to_string()),
+                dims: vec![2],
+                data_type: Some(1),
+                float_data: vec![1.0, 2.5],
+                ..Default::default()
+            },
+            int64_tensor("i64", &[2], &[-1, 1 << 33]),
+            TensorProto {
+                name: Some("typed_bool".to_string()),
+                dims: vec![3],
+                data_type: Some(9),
+                int32_data: vec![1, 0, 1],
+                ..Default::default()
+            },
+            raw_tensor("raw_bool", 9, &[2], &[0, 1]),
+            raw_tensor("bad_bool", 9, &[2], &[0, 2]),
+            raw_tensor("raw_u8", 2, &[3], &[7, 8, 9]),
+            TensorProto {
+                name: Some("typed_i8".to_string()),
+                dims: vec![2],
+                data_type: Some(3),
+                int32_data: vec![-3, 4],
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+    let model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+    let tensor = |name: &str| model.get_tensor(name).unwrap();
+
+    assert_eq!(tensor("raw_f32").to_vec::<f32>().unwrap(), [1.0, 2.5]);
+    assert_eq!(tensor("typed_f32").to_vec::<f32>().unwrap(), [1.0, 2.5]);
+    assert_eq!(tensor("i64").to_vec::<i64>().unwrap(), [-1, 1 << 33]);
+    assert_eq!(
+        tensor("typed_bool").to_vec::<bool>().unwrap(),
+        [true, false, true]
+    );
+    assert_eq!(tensor("typed_i8").to_vec::<i8>().unwrap(), [-3, 4]);
+
+    // data type must match the element type
+    assert!(matches!(
+        tensor("i64").to_vec::<f64>(),
+        Err(onnx_extractor::Error::DataConversion(_))
+    ));
+    assert!(tensor("raw_u8").to_vec::<i8>().is_err());
+
+    // invalid bit patterns are rejected instead of transmuted
+    assert!(tensor("bad_bool").to_vec::<bool>().is_err());
+    assert!(tensor("bad_bool").copy_data_as::<bool>().is_err());
+    assert!(tensor("bad_bool").as_slice::<bool>().is_err());
+
+    // zero-copy borrows where the stored layout matches
+    assert_eq!(tensor("typed_f32").as_slice::<f32>().unwrap(), [1.0, 2.5]);
+    assert_eq!(tensor("i64").as_slice::<i64>().unwrap(), [-1, 1 << 33]);
+    assert_eq!(tensor("raw_u8").as_slice::<u8>().unwrap(), [7, 8, 9]);
+    assert_eq!(
+        tensor("raw_bool").as_slice::<bool>().unwrap(),
+        [false, true]
+    );
+    assert!(tensor("typed_i8").as_slice::<i8>().is_err());
+    assert!(tensor("typed_f32").as_slice::<f64>().is_err());
+}
+
+#[cfg(feature = "ndarray")]
+#[test]
+fn test_ndarray_conversion() {
+    use onnx_extractor::ndarray::{ArrayD, IxDyn};
+
+    let graph = GraphProto {
+        name: Some("arrays".to_string()),
+        initializer: vec![
+            float_tensor("w", &[2, 3], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]),
+            int64_tensor("ids", &[3], &[4, 5, 6]),
+            raw_tensor("empty", 1, &[0, 3], &[]),
+            TensorProto {
+                name: Some("scalar".to_string()),
+                data_type: Some(1),
+                float_data: vec![7.5],
+                ..Default::default()
+            },
+        ],
+        input: vec![tensor_value_info("x", 1, &[D::P("batch"), D::V(3)])],
+        ..Default::default()
+    };
+    let model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+    let tensor = |name: &str| model.get_tensor(name).unwrap();
+
+    let w = tensor("w").to_array::<f32>().unwrap();
+    assert_eq!(w.shape(), &[2, 3]);
+    assert_eq!(w[[1, 2]], 6.0);
+
+    let ids = tensor("ids").array_view::<i64>().unwrap();
+    assert_eq!(
+        ids,
+        ArrayD::from_shape_vec(IxDyn(&[3]), vec![4i64, 5, 6]).unwrap()
+    );
+
+    let empty = tensor("empty").to_array::<f32>().unwrap();
+    assert_eq!(empty.shape(), &[0, 3]);
+    assert_eq!(tensor("empty").array_view::<f32>().unwrap().len(), 0);
+
+    let scalar = tensor("scalar").to_array::<f32>().unwrap();
+    assert_eq!(scalar.ndim(), 0);
+    assert_eq!(scalar.into_iter().next(), Some(7.5));
+
+    assert!(tensor("x").to_array::<f32>().is_err());
+    assert!(tensor("w").to_array::<f64>().is_err());
+}
+
+#[test]
+fn test_validate() {
+    let path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), MODEL_PATH);
+    let mnist = OnnxModel::load_from_file(&path).expect("Failed to load mnist model");
+    let diagnostics = mnist.validate();
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    let mut custom = node("Custom", &["x"], &["c"], vec![]);
+    custom.domain = Some("com.example".to_string());
+    let mut unnamed_op = node("Relu", &["x"], &["r"], vec![]);
+    unnamed_op.op_type = Some(String::new());
+    let mut then_branch = GraphProto {
+        name: Some("then".to_string()),
+        node: vec![node("Identity", &["a", "ghost"], &["t"], vec![])],
+        output: vec![tensor_value_info("t", 1, &[])],
+        ..Default::default()
+    };
+    then_branch.node[0].name = Some("inner".to_string());
+
+    let graph = GraphProto {
+        name: Some("broken".to_string()),
+        initializer: vec![float_tensor("w", &[3], &[1.0, 2.0])],
+        input: vec![tensor_value_info("x", 1, &[D::V(3)])],
+        node: vec![
+            node("Add", &["x", "b"], &["a"], vec![]),
+            node("Neg", &["x"], &["b"], vec![]),
+            node("Mul", &["x", "missing"], &["a"], vec![]),
+            custom,
+            unnamed_op,
+            node(
+                "If",
+                &["x"],
+                &["i"],
+                vec![graph_attr("then_branch", then_branch)],
+            ),
+        ],
+        output: vec![
+            tensor_value_info("a", 1, &[]),
+            tensor_value_info("nope", 1, &[]),
+        ],
+        ..Default::default()
+    };
+    let model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+    let diagnostics = model.validate();
+    let find = |needle: &str| {
+        diagnostics
+            .iter()
+            .find(|d| d.message.contains(needle))
+            .unwrap_or_else(|| panic!("no diagnostic containing {needle:?}: {diagnostics:#?}"))
+    };
+
+    let w = find("data bytes");
+    assert_eq!(
+        w.location,
+        Location::Tensor {
+            graph: "broken".to_string(),
+            name: "w".to_string()
+        }
+    );
+    assert!(w.is_error());
+
+    let sorted = find("input 'b' is produced by later node 1");
+    assert_eq!(
+        sorted.location,
+        Location::Node {
+            graph: "broken".to_string(),
+            index: 0,
+            name: "Add_a".to_string()
+        }
+    );
+    assert!(matches!(
+        find("input 'missing' is not defined").location,
+        Location::Node { index: 2, .. }
+    ));
+    assert!(matches!(
+        find("output 'a' is already defined").location,
+        Location::Node { index: 2, .. }
+    ));
+    assert!(matches!(
+        find("domain 'com.example'").location,
+        Location::Node { index: 3, .. }
+    ));
+    assert!(matches!(
+        find("no op_type").location,
+        Location::Node { index: 4, .. }
+    ));
+    assert_eq!(
+        find("graph output 'nope'").location,
+        Location::Graph {
+            graph: "broken".to_string()
+        }
+    );
+
+    // subgraph nodes see outer values but report their own undefined inputs
+    let ghost = find("input 'ghost'");
+    assert_eq!(
+        ghost.location,
+        Location::Node {
+            graph: "broken/If_i/then_branch".to_string(),
+            index: 0,
+            name: "inner".to_string()
+        }
+    );
+    assert!(!diagnostics.iter().any(|d| d.message.contains("input 'a'")));
+    assert_eq!(
+        ghost.to_string(),
+        "error: node 0 'inner' in graph 'broken/If_i/then_branch': input 'ghost' is not defined"
+    );
+    assert!(diagnostics.iter().all(|d| d.severity == Severity::Error));
+}
+
+#[test]
+fn test_infer_shapes() {
+    let path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), MODEL_PATH);
+    let mut mnist = OnnxModel::load_from_file(&path).expect("Failed to load mnist model");
+    let report = mnist.infer_shapes().expect("mnist should be sortable");
+    assert!(report.skipped.is_empty(), "{:?}", report.skipped);
+    for op in &mnist.operations {
+        for output in &op.outputs {
+            let tensor = mnist
+                .tensors
+                .get(output)
+                .expect("output should have a tensor");
+            assert!(tensor.shape().iter().all(|&d| d > 0), "{}", output);
+        }
+    }
+    let output = &mnist.outputs[0];
+    assert_eq!(mnist.tensors[output].shape(), &[1, 10]);
+
+    let mut custom = node("Custom", &["x"], &["z"], vec![]);
+    custom.domain = Some("com.example".to_string());
+    let graph = GraphProto {
+        name: Some("shapes".to_string()),
+        initializer: vec![
+            float_tensor("w", &[4, 3, 3, 3], &[0.0; 108]),
+            float_tensor("wg", &[10, 64], &[0.0; 640]),
+            int64_tensor("target", &[2], &[0, -1]),
+            int64_tensor("starts", &[1], &[1]),
+            int64_tensor("ends", &[1], &[i64::MAX]),
+            int64_tensor("axes", &[1], &[1]),
+        ],
+        input: vec![tensor_value_info(
+            "x",
+            1,
+            &[D::P("N"), D::V(3), D::V(8), D::V(8)],
+        )],
+        node: vec![
+            node(
+                "Conv",
+                &["x", "w"],
+                &["c"],
+                vec![ints_attr("pads", &[1, 1, 1, 1])],
+            ),
+            node(
+                "MaxPool",
+                &["c"],
+                &["p"],
+                vec![
+                    ints_attr("kernel_shape", &[2, 2]),
+                    ints_attr("strides", &[2, 2]),
+                ],
+            ),
+            node("Reshape", &["p", "target"], &["r"], vec![]),
+            node("Gemm", &["r", "wg"], &["g"], vec![int_attr("transB", 1)]),
+            node("Transpose", &["r"], &["t"], vec![]),
+            node("Concat", &["x", "c"], &["cat"], vec![int_attr("axis", 1)]),
+            node("Slice", &["x", "starts", "ends", "axes"], &["s"], vec![]),
+            node("Shape", &["p"], &["shape"], vec![]),
+            node("Unsqueeze", &["g", "axes"], &["u"], vec![]),
+            node(
+                "ReduceMean",
+                &["c"],
+                &["m"],
+                vec![ints_attr("axes", &[2, 3])],
+            ),
+            node("Greater", &["g", "g"], &["cmp"], vec![]),
+            custom,
+            node("Relu", &["z"], &["zr"], vec![]),
+        ],
+        output: vec![tensor_value_info("g", 1, &[D::P("N"), D::Unknown])],
+        ..Default::default()
+    };
+    let mut model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+    let report = model.infer_shapes().unwrap();
+
+    let n = || Dim::Symbolic("N".to_string());
+    let dims = |name: &str| model.tensors[name].dims().to_vec();
+    assert_eq!(
+        dims("c"),
+        [n(), Dim::Fixed(4), Dim::Fixed(8), Dim::Fixed(8)]
+    );
+    assert_eq!(
+        dims("p"),
+        [n(), Dim::Fixed(4), Dim::Fixed(4), Dim::Fixed(4)]
+    );
+    assert_eq!(dims("r"), [n(), Dim::Unknown]);
+    assert_eq!(dims("g"), [n(), Dim::Fixed(10)]);
+    assert_eq!(dims("t"), [Dim::Unknown, n()]);
+    assert_eq!(
+        dims("cat"),
+        [n(), Dim::Fixed(7), Dim::Fixed(8), Dim::Fixed(8)]
+    );
+    assert_eq!(
+        dims("s"),
+        [n(), Dim::Fixed(2), Dim::Fixed(8), Dim::Fixed(8)]
+    );
+    assert_eq!(dims("shape"), [Dim::Fixed(4)]);
+    assert_eq!(dims("u"), [n(), Dim::Fixed(1), Dim::Fixed(10)]);
+    assert_eq!(
+        dims("m"),
+        [n(), Dim::Fixed(4), Dim::Fixed(1), Dim::Fixed(1)]
+    );
+    assert_eq!(model.tensors["shape"].data_type(), DataType::Int64);
+    assert_eq!(model.tensors["cmp"].data_type(), DataType::Bool);
+    assert!(model.tensors["g"].data().is_err());
+    assert!(report.inferred.contains(&"g".to_string()));
+    // initializers are never replaced
+    assert!(!report.inferred.contains(&"w".to_string()));
+    assert_eq!(model.tensors["w"].shape(), &[4, 3, 3, 3]);
+
+    let skipped: Vec<&str> = report.skipped.iter().map(|s| s.op_type.as_str()).collect();
+    assert_eq!(skipped, ["Custom", "Relu"]);
+    assert!(report.skipped[1].reason.contains("'z'"));
+}
+
+#[test]
+fn test_infer_shapes_degenerate() {
+    let graph = GraphProto {
+        name: Some("degenerate".to_string()),
+        initializer: vec![
+            int64_tensor("starts", &[1], &[-1]),
+            int64_tensor("ends", &[1], &[i64::MIN]),
+            int64_tensor("axes", &[1], &[1]),
+            int64_tensor("steps", &[1], &[-1]),
+            int64_tensor("huge", &[2], &[i64::MAX, 4]),
+            int64_tensor("twice", &[2], &[1, 1]),
+        ],
+        input: vec![
+            tensor_value_info("x", 1, &[D::V(2), D::V(0), D::V(4), D::V(4)]),
+            tensor_value_info("scalar", 1, &[]),
+        ],
+        node: vec![
+            node(
+                "Slice",
+                &["x", "starts", "ends", "axes", "steps"],
+                &["s"],
+                vec![],
+            ),
+            node(
+                "MaxPool",
+                &["x"],
+                &["p"],
+                vec![
+                    ints_attr("kernel_shape", &[2, 2]),
+                    ints_attr("strides", &[0, 1]),
+                ],
+            ),
+            node(
+                "DepthToSpace",
+                &["x"],
+                &["d"],
+                vec![int_attr("blocksize", 0)],
+            ),
+            node("Split", &["x"], &[], vec![]),
+            node("ConstantOfShape", &["huge"], &["big"], vec![]),
+            node("Size", &["big"], &["n"], vec![]),
+            // repeated axes
+            node("Unsqueeze", &["scalar", "twice"], &["u"], vec![]),
+            node(
+                "Transpose",
+                &["x"],
+                &["t"],
+                vec![ints_attr("perm", &[0, 0, 1, 2])],
+            ),
+            node("Slice", &["x", "twice", "huge", "twice"], &["r"], vec![]),
+        ],
+        ..Default::default()
+    };
+    let mut model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+    let report = model.infer_shapes().expect("graph should be sortable");
+
+    // an empty dim stays empty for a negative step
+    assert_eq!(model.tensors["s"].shape(), &[2, 0, 4, 4]);
+    let skipped: Vec<&str> = report.skipped.iter().map(|s| s.op_type.as_str()).collect();
+    assert_eq!(
+        skipped,
+        [
+            "MaxPool",
+            "DepthToSpace",
+            "Split",
+            "Unsqueeze",
+            "Transpose",
+            "Slice",
+            "Size"
+        ]
+    );
+    assert!(report.skipped[6].reason.contains("overflows"));
+    assert!(
+        report.skipped[3..6]
+            .iter()
+            .all(|s| s.reason.contains("repeated"))
+    );
+}
+
+#[test]
+fn test_run_interpreter() {
+    let path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), MODEL_PATH);
+    let mnist = OnnxModel::load_from_file(&path).expect("Failed to load mnist model");
+    let input = &mnist.inputs[0];
+    let classify = |on: &dyn Fn(usize, usize) -> bool| {
+        let pixels: Vec<u8> = (0..28 * 28)
+            .map(|i| if on(i / 28, i % 28) { 255.0f32 } else { 0.0 })
+            .flat_map(f32::to_le_bytes)
+            .collect();
+        let image = OnnxTensor::from_raw_data(
+            input.clone(),
+            vec![1, 1, 28, 28],
+            DataType::Float,
+            Bytes::from(pixels),
+        );
+        let outputs = mnist.run(&HashMap::from([(input.clone(), image)])).unwrap();
+        let scores = outputs[&mnist.outputs[0]].to_vec::<f32>().unwrap();
+        assert_eq!(scores.len(), 10);
+        (0..10)
+            .max_by(|&a, &b| scores[a].total_cmp(&scores[b]))
+            .unwrap()
+    };
+    // a vertical bar reads as 1, an ellipse as 0
+    assert_eq!(
+        classify(&|y, x| (4..24).contains(&y) && (13..16).contains(&x)),
+        1
+    );
+    assert_eq!(
+        classify(&|y, x| {
+            let (dx, dy) = (x as f32 - 14.0, y as f32 - 14.0);
+            let r = (dx * dx / 36.0 + dy * dy / 81.0).sqrt();
+            (4..24).contains(&y) && (0.8..1.2).contains(&r)
+        }),
+        0
+    );
+
+    let graph = GraphProto {
+        name: Some("small".to_string()),
+        initializer: vec![
+            float_tensor("w", &[1, 1, 2, 2], &[1.0; 4]),
+            float_tensor("b", &[1], &[0.5]),
+            float_tensor("wg", &[2, 4], &[1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0]),
+            float_tensor("cg", &[1], &[1.0]),
+            int64_tensor("two", &[], &[2]),
+            int64_tensor("zero", &[1], &[0]),
+            int64_tensor("back", &[1], &[-1]),
+            int64_tensor("first", &[1], &[i64::MIN]),
+            int64_tensor("rows", &[1], &[2]),
+        ],
+        input: vec![tensor_value_info(
+            "x",
+            1,
+            &[D::V(1), D::V(1), D::V(3), D::V(3)],
+        )],
+        node: vec![
+            node("Conv", &["x", "w", "b"], &["c"], vec![]),
+            node(
+                "MaxPool",
+                &["c"],
+                &["p"],
+                vec![ints_attr("kernel_shape", &[2, 2])],
+            ),
+            node(
+                "AveragePool",
+                &["x"],
+                &["a"],
+                vec![
+                    ints_attr("kernel_shape", &[2, 2]),
+                    ints_attr("strides", &[2, 2]),
+                    ints_attr("pads", &[1, 1, 1, 1]),
+                ],
+            ),
+            node("Flatten", &["c"], &["f"], vec![]),
+            node(
+                "Gemm",
+                &["f", "wg", "cg"],
+                &["g"],
+                vec![int_attr("transB", 1)],
+            ),
+            node("Shape", &["x"], &["s"], vec![]),
+            node("Gather", &["s", "two"], &["n"], vec![]),
+            node(
+                "Slice",
+                &["x", "back", "first", "rows", "back"],
+                &["rev"],
+                vec![],
+            ),
+            node("Slice", &["x", "zero", "zero", "rows"], &["e"], vec![]),
+            node(
+                "Slice",
+                &["e", "back", "first", "rows", "back"],
+                &["er"],
+                vec![],
+            ),
+        ],
+        output: vec![
+            tensor_value_info("rev", 1, &[]),
+            tensor_value_info("er", 1, &[]),
+            tensor_value_info("c", 1, &[]),
+            tensor_value_info("p", 1, &[]),
+            tensor_value_info("a", 1, &[]),
+            tensor_value_info("g", 1, &[]),
+            tensor_value_info("n", 7, &[]),
+        ],
+        ..Default::default()
+    };
+    let model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+    let x = OnnxTensor::from_raw_data(
+        "x".to_string(),
+        vec![1, 1, 3, 3],
+        DataType::Float,
+        Bytes::from(
+            (1..=9)
+                .flat_map(|v| (v as f32).to_le_bytes())
+                .collect::<Vec<_>>(),
+        ),
+    );
+    let inputs = HashMap::from([("x".to_string(), x)]);
+    let outputs = model.run(&inputs).unwrap();
+    let floats = |name: &str| outputs[name].to_vec::<f32>().unwrap();
+    assert_eq!(outputs["c"].shape(), &[1, 1, 2, 2]);
+    assert_eq!(floats("c"), [12.5, 16.5, 24.5, 28.5]);
+    assert_eq!(floats("p"), [28.5]);
+    // padding is excluded from the average by default
+    assert_eq!(floats("a"), [1.0, 2.5, 5.5, 7.0]);
+    assert_eq!(outputs["g"].shape(), &[1, 2]);
+    assert_eq!(floats("g"), [13.5, 29.5]);
+    assert_eq!(outputs["n"].data_type(), DataType::Int64);
+    assert_eq!(outputs["n"].to_vec::<i64>().unwrap(), [3]);
+    assert_eq!(floats("rev"), [7.0, 8.0, 9.0, 4.0, 5.0, 6.0, 1.0, 2.0, 3.0]);
+    // reversing an empty dim leaves it empty
+    assert_eq!(outputs["er"].shape(), &[1, 1, 0, 3]);
+
+    assert!(matches!(
+        model.run(&HashMap::new()),
+        Err(onnx_extractor::Error::MissingField(_))
+    ));
+
+    let mut custom = node("Custom", &["x"], &["y"], vec![]);
+    custom.domain = Some("com.example".to_string());
+    let graph = GraphProto {
+        name: Some("custom".to_string()),
+        input: vec![tensor_value_info(
+            "x",
+            1,
+            &[D::V(1), D::V(1), D::V(3), D::V(3)],
+        )],
+        node: vec![custom],
+        output: vec![tensor_value_info("y", 1, &[])],
+        ..Default::default()
+    };
+    let model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+    assert!(matches!(
+        model.run(&inputs),
+        Err(onnx_extractor::Error::Unsupported(_))
+    ));
+}
+
+#[test]
+fn test_run_repeated_axes() {
+    let run = |shape: &[i64], op: NodeProto, initializer: Vec<TensorProto>| {
+        let graph = GraphProto {
+            name: Some("repeated".to_string()),
+            initializer,
+            input: vec![tensor_value_info(
+                "x",
+                1,
+                &shape.iter().map(|&d| D::V(d)).collect::<Vec<_>>(),
+            )],
+            node: vec![op],
+            output: vec![tensor_value_info("y", 1, &[])],
+            ..Default::default()
+        };
+        let model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+        let count = shape.iter().product::<i64>() as usize;
+        let x = OnnxTensor::from_raw_data(
+            "x".to_string(),
+            shape.to_vec(),
+            DataType::Float,
+            Bytes::from(vec![0u8; count * 4]),
+        );
+        model.run(&HashMap::from([("x".to_string(), x)]))
+    };
+    let rejected = |result: Result<HashMap<String, OnnxTensor>, onnx_extractor::Error>| matches!(result, Err(onnx_extractor::Error::InvalidModel(m)) if m.contains("repeated"));
+
+    let unsqueeze = node("Unsqueeze", &["x", "axes"], &["y"], vec![]);
+    let axes = int64_tensor("axes", &[2], &[1, 1]);
+    assert!(rejected(run(&[], unsqueeze, vec![axes])));
+
+    for perm in [[0, 0], [1, 1]] {
+        let transpose = node("Transpose", &["x"], &["y"], vec![ints_attr("perm", &perm)]);
+        assert!(rejected(run(&[2, 3], transpose, vec![])));
+    }
+
+    let slice = node("Slice", &["x", "starts", "ends", "axes"], &["y"], vec![]);
+    let initializer = vec![
+        int64_tensor("starts", &[2], &[1, 1]),
+        int64_tensor("ends", &[2], &[4, 4]),
+        int64_tensor("axes", &[2], &[0, 0]),
+    ];
+    assert!(rejected(run(&[4], slice, initializer)));
+}
+
+#[test]
+fn test_graph_index() {
+    let mut unnamed = node("Mul", &["a", "a"], &["e"], vec![]);
+    unnamed.name = None;
+    let mut duplicate = node("Exp", &["a"], &["c"], vec![]);
+    duplicate.name = Some("Neg_b".to_string());
+    let graph = GraphProto {
+        name: Some("diamond".to_string()),
+        initializer: vec![float_tensor("w", &[2], &[1.0, 2.0])],
+        input: vec![tensor_value_info("x", 1, &[D::V(2)])],
+        node: vec![
+            node("Add", &["b", "c"], &["d"], vec![]),
+            node("Relu", &["x"], &["a"], vec![]),
+            node("Neg", &["a"], &["b"], vec![]),
+            duplicate,
+            unnamed,
+        ],
+        output: vec![
+            tensor_value_info("d", 1, &[]),
+            tensor_value_info("e", 1, &[]),
+        ],
+        ..Default::default()
+    };
+    let model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+    let index = model.graph_index();
+    let types = |ids: Vec<OpId>| -> Vec<&str> {
+        ids.into_iter()
+            .map(|id| model.operation(id).unwrap().op_type.as_str())
+            .collect()
+    };
+
+    // every node has an id, named or not
+    assert_eq!(index.op_count(), 5);
+    assert_eq!(
+        types(index.op_ids().collect()),
+        ["Add", "Relu", "Neg", "Exp", "Mul"]
+    );
+    let add = index.op_named("Add_d").unwrap();
+    let relu = index.op_named("Relu_a").unwrap();
+    assert_eq!(add.index(), 0);
+    assert_eq!(index.operation(relu).unwrap().op_type, "Relu");
+    assert_eq!(model.operation(relu).unwrap().name, "Relu_a");
+
+    // duplicate names are detected rather than silently resolved
+    assert_eq!(index.op_named("Neg_b"), None);
+    assert_eq!(types(index.ops_named("Neg_b").to_vec()), ["Neg", "Exp"]);
+    assert_eq!(index.duplicate_op_names(), ["Neg_b"]);
+    assert!(index.ops_named("").is_empty());
+
+    // tensors: inputs, initializers, then outputs in node order
+    assert_eq!(index.tensor_count(), 7);
+    for (i, name) in ["x", "w", "d", "a", "b", "c", "e"].into_iter().enumerate() {
+        let id = index.tensor_id(name).unwrap();
+        assert_eq!(id.index(), i);
+        assert_eq!(index.tensor_name(id), Some(name));
+    }
+    assert!(index.tensor_id("missing").is_none());
+    assert_eq!(
+        index.op_inputs(add),
+        [index.tensor_id("b").unwrap(), index.tensor_id("c").unwrap()]
+    );
+    assert_eq!(index.op_outputs(relu), [index.tensor_id("a").unwrap()]);
+
+    assert_eq!(types(vec![index.producer_of("a").unwrap()]), ["Relu"]);
+    assert!(index.producer_of("x").is_none());
+    assert_eq!(types(index.consumers_of("a")), ["Neg", "Exp", "Mul"]);
+    assert!(index.consumers_of("d").is_empty());
+    let a = index.tensor_id("a").unwrap();
+    assert_eq!(index.producer(a), Some(relu));
+    assert_eq!(index.consumers(a), index.consumers_of("a"));
+    let x = index.tensor_id("x").unwrap();
+    assert!(index.producer(x).is_none());
+    assert_eq!(types(index.consumers(x)), ["Relu"]);
+    let d = index.tensor_id("d").unwrap();
+    assert_eq!(index.producer(d), Some(add));
+    assert!(index.consumers(d).is_empty());
+    assert_eq!(types(index.predecessors(add)), ["Neg", "Exp"]);
+    assert_eq!(types(index.successors(relu)), ["Neg", "Exp", "Mul"]);
+    assert_eq!(types(index.upstream(add).collect()), ["Neg", "Exp", "Relu"]);
+    assert_eq!(
+        types(index.downstream(relu).collect()),
+        ["Neg", "Exp", "Mul", "Add"]
+    );
+
+    // ordering is unchanged by the shared index
+    let order: Vec<&str> = model
+        .topological_order()
+        .unwrap()
+        .iter()
+        .map(|o| o.op_type.as_str())
+        .collect();
+    assert_eq!(order, ["Relu", "Neg", "Exp", "Mul", "Add"]);
+}
+
+#[test]
+fn test_inline_functions() {
+    let opset = |domain: &str, version: i64| OperatorSetIdProto {
+        domain: Some(domain.to_string()),
+        version: Some(version),
+    };
+    let call = |name: Option<&str>, input: &str, output: &str, attribute| {
+        let mut call = node("ScaledAct", &[input], &[output], attribute);
+        call.name = name.map(str::to_string);
+        call.domain = Some("local".to_string());
+        call
+    };
+
+    // Y = LeakyRelu(X, alpha) + X, alpha taken from the caller
+    let alpha_ref = AttributeProto {
+        name: Some("alpha".to_string()),
+        r#type: Some(1),
+        ref_attr_name: Some("alpha".to_string()),
+        ..Default::default()
+    };
+    let scaled_act = FunctionProto {
+        name: Some("ScaledAct".to_string()),
+        domain: Some("local".to_string()),
+        input: vec!["X".to_string()],
+        output: vec!["Y".to_string()],
+        attribute: vec!["beta".to_string()],
+        attribute_proto: vec![AttributeProto {
+            name: Some("alpha".to_string()),
+            r#type: Some(1),
+            f: Some(0.25),
+            ..Default::default()
+        }],
+        node: vec![
+            node("LeakyRelu", &["X"], &["T"], vec![alpha_ref]),
+            node("Add", &["T", "X"], &["Y"], vec![]),
+        ],
+        opset_import: vec![opset("", 13), opset("local.extra", 1)],
+        ..Default::default()
+    };
+    let mut self_call = call(None, "X", "Y", vec![]);
+    self_call.op_type = Some("Forever".to_string());
+    let recursive = FunctionProto {
+        name: Some("Forever".to_string()),
+        domain: Some("local".to_string()),
+        input: vec!["X".to_string()],
+        output: vec!["Y".to_string()],
+        node: vec![self_call],
+        ..Default::default()
+    };
+
+    let alpha = AttributeProto {
+        name: Some("alpha".to_string()),
+        r#type: Some(1),
+        f: Some(0.5),
+        ..Default::default()
+    };
+    let graph = GraphProto {
+        name: Some("calls".to_string()),
+        input: vec![tensor_value_info("x", 1, &[D::V(2)])],
+        node: vec![
+            // takes the name the first call would mangle its body into
+            node("Relu", &["x"], &["act/T"], vec![]),
+            call(Some("act"), "x", "y1", vec![alpha]),
+            call(None, "y1", "y2", vec![]),
+        ],
+        output: vec![
+            tensor_value_info("act/T", 1, &[D::V(2)]),
+            tensor_value_info("y2", 1, &[D::V(2)]),
+        ],
+        ..Default::default()
+    };
+    let proto = ModelProto {
+        ir_version: Some(8),
+        opset_import: vec![opset("", 13), opset("local", 1)],
+        graph: Some(graph),
+        functions: vec![scaled_act, recursive],
+        ..Default::default()
+    };
+    let mut model = OnnxModel::load_from_bytes(proto.encode_to_vec()).expect("model should load");
+
+    assert_eq!(model.functions.len(), 2);
+    let function = model.get_function("local", "ScaledAct").unwrap();
+    assert_eq!(function.inputs, ["X"]);
+    assert_eq!(function.outputs, ["Y"]);
+    assert_eq!(function.attribute_names(), ["alpha", "beta"]);
+    assert_eq!(function.attribute_defaults["alpha"].as_float(), Some(0.25));
+    assert_eq!(function.operations.len(), 2);
+    assert_eq!(function.opset_import.len(), 2);
+    assert!(function.is_called_by(&model.operations[1]));
+    assert!(matches!(
+        function.operations[0].get_attribute("alpha"),
+        Some(AttributeValue::Ref { name, attr_type: AttributeType::Float }) if name == "alpha"
+    ));
+    assert!(model.get_function("", "ScaledAct").is_none());
+
+    // functions and attribute references survive serialization
+    let reloaded = OnnxModel::load_from_bytes(model.to_bytes().unwrap()).unwrap();
+    let function = reloaded.get_function("local", "ScaledAct").unwrap();
+    assert_eq!(function.attributes, ["beta"]);
+    assert_eq!(
+        function.operations[0]
+            .get_attribute("alpha")
+            .and_then(AttributeValue::as_ref_name),
+        Some("alpha")
+    );
+
+    assert_eq!(model.inline_functions().unwrap(), 2);
+
+    let summary: Vec<(&str, &str, &[String], &[String])> = model
+        .operations
+        .iter()
+        .map(|op| {
+            (
+                op.name.as_str(),
+                op.op_type.as_str(),
+                op.inputs.as_slice(),
+                op.outputs.as_slice(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (
+                "Relu_act/T",
+                "Relu",
+                &["x".to_string()][..],
+                &["act/T".to_string()][..]
+            ),
+            (
+                "act_1/LeakyRelu_T",
+                "LeakyRelu",
+                &["x".to_string()],
+                &["act_1/T".to_string()]
+            ),
+            (
+                "act_1/Add_Y",
+                "Add",
+                &["act_1/T".to_string(), "x".to_string()],
+                &["y1".to_string()]
+            ),
+            (
+                "ScaledAct/LeakyRelu_T",
+                "LeakyRelu",
+                &["y1".to_string()],
+                &["ScaledAct/T".to_string()]
+            ),
+            (
+                "ScaledAct/Add_Y",
+                "Add",
+                &["ScaledAct/T".to_string(), "y1".to_string()],
+                &["y2".to_string()]
+            ),
+        ]
+    );
+    // the caller's attribute wins over the function default
+    assert_eq!(model.operations[1].get_float_attribute("alpha"), Some(0.5));
+    assert_eq!(model.operations[3].get_float_attribute("alpha"), Some(0.25));
+    assert_eq!(model.opset_version("local.extra"), Some(1));
+    assert!(model.validate().iter().all(|d| !d.is_error()));
+
+    let x = OnnxTensor::from_raw_data(
+        "x".to_string(),
+        vec![2],
+        DataType::Float,
+        Bytes::from(
+            [-2.0f32, 4.0]
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect::<Vec<_>>(),
+        ),
+    );
+    let outputs = model.run(&HashMap::from([("x".to_string(), x)])).unwrap();
+    // y1 = [-2 * 0.5 - 2, 4 + 4], y2 = [-3 * 0.25 - 3, 8 + 8]
+    assert_eq!(outputs["y2"].to_vec::<f32>().unwrap(), [-3.75, 16.0]);
+
+    // nodes inside a body's subgraphs are prefixed per call as well
+    let branch = |name: &str, op_type: &str| GraphProto {
+        name: Some(name.to_string()),
+        node: vec![node(op_type, &["X"], &[name], vec![])],
+        output: vec![tensor_value_info(name, 1, &[D::V(2)])],
+        ..Default::default()
+    };
+    let gated = FunctionProto {
+        name: Some("Gated".to_string()),
+        domain: Some("local".to_string()),
+        input: vec!["C".to_string(), "X".to_string()],
+        output: vec!["Y".to_string()],
+        node: vec![node(
+            "If",
+            &["C"],
+            &["Y"],
+            vec![
+                graph_attr("then_branch", branch("T", "Identity")),
+                graph_attr("else_branch", branch("E", "Neg")),
+            ],
+        )],
+        opset_import: vec![opset("", 13)],
+        ..Default::default()
+    };
+    let gated_call = |output: &str| {
+        let mut call = call(None, "x", output, vec![]);
+        call.op_type = Some("Gated".to_string());
+        call.input.insert(0, "c".to_string());
+        call
+    };
+    let mut branches = OnnxModel::load_from_bytes(
+        ModelProto {
+            ir_version: Some(8),
+            opset_import: vec![opset("", 13), opset("local", 1)],
+            graph: Some(GraphProto {
+                name: Some("branches".to_string()),
+                input: vec![
+                    tensor_value_info("c", 9, &[]),
+                    tensor_value_info("x", 1, &[D::V(2)]),
+                ],
+                node: vec![gated_call("g1"), gated_call("g2")],
+                output: vec![
+                    tensor_value_info("g1", 1, &[D::V(2)]),
+                    tensor_value_info("g2", 1, &[D::V(2)]),
+                ],
+                ..Default::default()
+            }),
+            functions: vec![gated],
+            ..Default::default()
+        }
+        .encode_to_vec(),
+    )
+    .unwrap();
+    assert_eq!(branches.inline_functions().unwrap(), 2);
+    let nested: Vec<&str> = branches
+        .operations
+        .iter()
+        .flat_map(|op| op.subgraphs())
+        .map(|(_, graph)| graph.operations[0].name.as_str())
+        .collect();
+    assert_eq!(
+        nested,
+        [
+            "Gated/Neg_E",
+            "Gated/Identity_T",
+            "Gated_1/Neg_E",
+            "Gated_1/Identity_T"
+        ]
+    );
+
+    // a function calling itself cannot be expanded
+    let mut forever = OnnxModel::load_from_bytes(proto.encode_to_vec()).unwrap();
+    forever.operations[2].op_type = "Forever".to_string();
+    assert!(matches!(
+        forever.inline_functions(),
+        Err(onnx_extractor::Error::InvalidModel(_))
+    ));
+
+    // a body written against another default opset version cannot be inlined
+    let mut mismatch = OnnxModel::load_from_bytes(proto.encode_to_vec()).unwrap();
+    mismatch.functions[0].opset_import[0].version = 18;
+    match mismatch.inline_functions() {
+        Err(onnx_extractor::Error::InvalidModel(message)) => {
+            assert!(message.contains("version 18"), "{}", message);
+        }
+        other => panic!("expected an opset mismatch, got {:?}", other),
+    }
+    assert_eq!(mismatch.operations.len(), 3);
+    assert_eq!(mismatch.opset_version(""), Some(13));
+    assert!(mismatch.opset_version("local.extra").is_none());
+}
+
+#[test]
+fn test_training_info() {
+    let graph = GraphProto {
+        name: Some("inference".to_string()),
+        initializer: vec![
+            float_tensor("W", &[2, 1], &[0.5, -0.5]),
+            float_tensor("B", &[1], &[0.0]),
+        ],
+        input: vec![tensor_value_info("x", 1, &[D::V(1), D::V(2)])],
+        node: vec![
+            node("MatMul", &["x", "W"], &["h"], vec![]),
+            node("Add", &["h", "B"], &["y"], vec![]),
+        ],
+        output: vec![tensor_value_info("y", 1, &[D::V(1), D::V(1)])],
+        ..Default::default()
+    };
+    let initialization = GraphProto {
+        name: Some("init".to_string()),
+        node: vec![node(
+            "Constant",
+            &[],
+            &["W_init"],
+            vec![AttributeProto {
+                name: Some("value".to_string()),
+                r#type: Some(4),
+                t: Some(float_tensor("", &[2, 1], &[0.0, 0.0])),
+                ..Default::default()
+            }],
+        )],
+        output: vec![tensor_value_info("W_init", 1, &[D::V(2), D::V(1)])],
+        ..Default::default()
+    };
+    // one plain gradient-free step: W -= lr * (y - label), step += 1
+    let algorithm = GraphProto {
+        name: Some("step".to_string()),
+        initializer: vec![
+            float_tensor("lr", &[], &[0.1]),
+            int64_tensor("step", &[], &[0]),
+            int64_tensor("one", &[], &[1]),
+        ],
+        input: vec![tensor_value_info("label", 1, &[D::V(1), D::V(1)])],
+        node: vec![
+            node("Sub", &["y", "label"], &["err"], vec![]),
+            node("Mul", &["err", "lr"], &["delta"], vec![]),
+            node("Sub", &["W", "delta"], &["W_new"], vec![]),
+            node("Add", &["step", "one"], &["step_new"], vec![]),
+        ],
+        output: vec![
+            tensor_value_info("W_new", 1, &[D::V(2), D::V(1)]),
+            tensor_value_info("step_new", 7, &[]),
+        ],
+        ..Default::default()
+    };
+    let info = TrainingInfoProto {
+        initialization: Some(initialization),
+        algorithm: Some(algorithm),
+        initialization_binding: vec![entry("W", "W_init")],
+        update_binding: vec![entry("W", "W_new"), entry("step", "step_new")],
+    };
+    let proto = ModelProto {
+        ir_version: Some(8),
+        opset_import: vec![OperatorSetIdProto {
+            domain: Some(String::new()),
+            version: Some(13),
+        }],
+        graph: Some(graph),
+        training_info: vec![info],
+        ..Default::default()
+    };
+    let mut model = OnnxModel::load_from_bytes(proto.encode_to_vec()).expect("model should load");
+
+    assert_eq!(model.training_info.len(), 1);
+    let info = &model.training_info[0];
+    assert_eq!(info.initialization.as_ref().unwrap().name, "init");
+    assert_eq!(info.algorithm.as_ref().unwrap().operations.len(), 4);
+    assert_eq!(info.initialization_binding["W"], "W_init");
+    assert_eq!(info.update_binding["step"], "step_new");
+    assert_eq!(info.updated_tensors(), ["W", "step"]);
+    assert!(info.is_algorithm_initializer("step"));
+    assert!(!info.is_algorithm_initializer("W"));
+
+    assert_eq!(model.trainable_parameters(), ["W"]);
+    assert_eq!(model.optimizer_state_tensors(), ["step"]);
+    let diagnostics = model.validate();
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    let reloaded = OnnxModel::load_from_bytes(model.to_bytes().unwrap()).unwrap();
+    let saved = &reloaded.training_info[0];
+    assert_eq!(saved.update_binding, info.update_binding);
+    assert_eq!(saved.initialization_binding, info.initialization_binding);
+    assert_eq!(
+        saved
+            .algorithm
+            .as_ref()
+            .unwrap()
+            .get_tensor("lr")
+            .unwrap()
+            .to_vec::<f32>()
+            .unwrap(),
+        [0.1]
+    );
+
+    // bindings must name initializers and graph outputs, each target once
+    let info = &mut model.training_info[0];
+    info.update_binding
+        .insert("h".to_string(), "W_new".to_string());
+    info.initialization_binding
+        .insert("B".to_string(), "B_init".to_string());
+    model.training_info.push(onnx_extractor::OnnxTrainingInfo {
+        update_binding: HashMap::from([("W".to_string(), "W_new".to_string())]),
+        ..Default::default()
+    });
+    let diagnostics = model.validate();
+    let messages: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+    assert_eq!(
+        messages,
+        [
+            "error: training_info[0] initialization_binding 'B': source 'B_init' is not a graph output",
+            "error: training_info[0] update_binding 'h': target is not an initializer",
+            "error: training_info[1] update_binding 'W': source 'W_new' is not a graph output",
+            "error: training_info[1] update_binding 'W': target is already updated by an earlier entry",
+        ],
+        "{:#?}",
+        diagnostics
+    );
+    assert_eq!(
+        diagnostics[1].location,
+        Location::TrainingBinding {
+            index: 0,
+            field: "update_binding".to_string(),
+            target: "h".to_string(),
+        }
+    );
+    // binding targets that are not initializers are not optimizer state
+    assert_eq!(model.optimizer_state_tensors(), ["step"]);
+}
+
+#[test]
+fn test_quantization_annotation() {
+    let annotation = |tensor: &str, params: &[(&str, &str)]| TensorAnnotation {
+        tensor_name: Some(tensor.to_string()),
+        quant_parameter_tensor_names: params.iter().map(|(k, v)| entry(k, v)).collect(),
+    };
+    let graph = GraphProto {
+        name: Some("quantized".to_string()),
+        initializer: vec![
+            float_tensor("x_scale", &[], &[0.05]),
+            raw_tensor("x_zp", 2, &[], &[128]),
+            raw_tensor("w", 3, &[2, 2], &[1, 2, 3, 4]),
+            float_tensor("w_scale", &[2], &[0.5, 0.25]),
+            // Int4 zero points 1 and -1, low nibble first
+            raw_tensor("w_zp", 22, &[2], &[0xF1]),
+        ],
+        input: vec![tensor_value_info("x", 1, &[D::V(1), D::V(2)])],
+        node: vec![
+            node("QuantizeLinear", &["x", "x_scale", "x_zp"], &["xq"], vec![]),
+            node(
+                "MatMulInteger",
+                &["xq", "w", "x_zp", "w_zp"],
+                &["y"],
+                vec![],
+            ),
+        ],
+        output: vec![tensor_value_info("y", 6, &[D::V(1), D::V(2)])],
+        quantization_annotation: vec![
+            annotation(
+                "xq",
+                &[("SCALE_TENSOR", "x_scale"), ("ZERO_POINT_TENSOR", "x_zp")],
+            ),
+            annotation(
+                "w",
+                &[("SCALE_TENSOR", "w_scale"), ("ZERO_POINT_TENSOR", "w_zp")],
+            ),
+            annotation("x", &[("SCALE_TENSOR", "x_scale")]),
+        ],
+        ..Default::default()
+    };
+    let mut model = OnnxModel::load_from_bytes(model_bytes(graph)).expect("model should load");
+
+    assert_eq!(model.quantization_annotation.len(), 3);
+    assert_eq!(
+        model.quantization_annotation["w"]["SCALE_TENSOR"],
+        "w_scale"
+    );
+
+    let xq = model.quant_params("xq").unwrap();
+    assert_eq!(xq.scale.name(), "x_scale");
+    assert_eq!(xq.zero_point.unwrap().name(), "x_zp");
+    assert!(xq.is_per_tensor());
+    assert_eq!(xq.scales().unwrap(), [0.05]);
+    assert_eq!(xq.zero_points().unwrap(), [128]);
+
+    let w = model.quant_params("w").unwrap();
+    assert!(!w.is_per_tensor());
+    assert_eq!(w.scales().unwrap(), [0.5, 0.25]);
+    assert_eq!(w.zero_points().unwrap(), [1, -1]);
+
+    // symmetric quantization has no zero point
+    let x = model.quant_params("x").unwrap();
+    assert!(x.zero_point.is_none());
+    assert_eq!(x.zero_points().unwrap(), [0]);
+    assert!(model.quant_params("y").is_none());
+    assert!(model.validate().is_empty(), "{:?}", model.validate());
+
+    let reloaded = OnnxModel::load_from_bytes(model.to_bytes().unwrap()).unwrap();
+    assert_eq!(
+        reloaded.quantization_annotation,
+        model.quantization_annotation
+    );
+    assert_eq!(reloaded.quant_params("w").unwrap().scale.name(), "w_scale");
+
+    // annotations must name a scale and existing initializers
+    model.quantization_annotation.insert(
+        "y".to_string(),
+        HashMap::from([("ZERO_POINT_TENSOR".to_string(), "y_zp".to_string())]),
+    );
+    model.quantization_annotation.insert(
+        "ghost".to_string(),
+        HashMap::from([("SCALE_TENSOR".to_string(), "x_scale".to_string())]),
+    );
+    assert!(model.quant_params("y").is_none());
+    let messages: Vec<String> = model.validate().iter().map(|d| d.to_string()).collect();
+    assert_eq!(
+        messages,
+        [
+            "warning: tensor 'ghost' in graph 'quantized': quantization annotation refers to an undefined tensor",
+            "error: tensor 'y' in graph 'quantized': quantization annotation has no SCALE_TENSOR",
+            "error: tensor 'y' in graph 'quantized': quantization annotation ZERO_POINT_TENSOR 'y_zp' is not an initializer",
+        ]
+    );
+
+    // parameters naming values that only have a declared shape are not resolved
+    let params = |scale: &str, zero_point: &str| {
+        HashMap::from([
+            ("SCALE_TENSOR".to_string(), scale.to_string()),
+            ("ZERO_POINT_TENSOR".to_string(), zero_point.to_string()),
+        ])
+    };
+    model
+        .quantization_annotation
+        .insert("y".to_string(), params("x", "x_zp"));
+    assert!(model.tensors.contains_key("x"));
+    assert!(model.quant_params("y").is_none());
+    model
+        .quantization_annotation
+        .insert("y".to_string(), params("x_scale", "xq"));
+    assert!(model.quant_params("y").is_none());
+    model
+        .quantization_annotation
+        .insert("y".to_string(), params("x_scale", "x_zp"));
+    assert!(model.quant_params("y").is_some());
+}